@@ -0,0 +1,5 @@
+// Prints the canonical vesting math test vectors as JSON, e.g.
+// cargo run --example test_vectors --features test-vectors > tests/vectors/vesting-math.json
+fn main() {
+    print!("{}", vesting::test_vectors::generate_test_vectors());
+}
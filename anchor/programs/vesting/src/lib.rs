@@ -1,238 +1,4929 @@
+// The CPI helpers #[program] generates take one argument per instruction argument, and several instructions
+// take more than clippy allows.
+#![cfg_attr(feature = "cpi", allow(clippy::too_many_arguments))]
+
 use anchor_lang::prelude::*;
-use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_lang::solana_program::{ hash::hash, instruction::{ AccountMeta, Instruction }, program::invoke };
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{ self, AssociatedToken };
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::TransferFeeConfig,
+        transfer_hook,
+        BaseStateWithExtensions,
+        StateWithExtensions,
+    },
+};
+use anchor_spl::token_interface::{ Mint, TokenAccount, TokenInterface };
+
+pub mod schedule;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+pub mod transitions;
+
+use schedule::Schedule;
+use transitions::GrantAction;
 
 declare_id!("GFdLg11UBR8ZeePW43ZyD1gY4z4UQ96LPa22YBgnn4z8");
+
+// Prepended to the seeds of every PDA. It is empty for the canonical deployment, which keeps its addresses
+// unchanged. A fork deployed next to it can build with e.g. `VESTING_SEED_NAMESPACE=f anchor build`, so one
+// toolchain can manage both without mixing up their accounts. The SDK reads it back from the IDL.
+#[constant]
+pub const SEED_NAMESPACE: &[u8] = match option_env!("VESTING_SEED_NAMESPACE") {
+    Some(namespace) => namespace.as_bytes(),
+    None => b"",
+};
+const _: () = assert!(SEED_NAMESPACE.len() <= 1, "VESTING_SEED_NAMESPACE must be a single byte");
+
+// Basis points are hundredths of a percent, so 10_000 bps is 100%.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+// Number of remaining accounts claim_all expects per grant.
+pub const CLAIM_ALL_GROUP_LEN: usize = 6;
+
+// Number of days of claim history kept by ClaimStats, which bounds its rolling windows.
+pub const CLAIM_STATS_WINDOW_DAYS: usize = 30;
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// Upper bound on the milestones of a custom schedule, this bounds the size of every EmployeeAccount.
+pub const MAX_MILESTONES: usize = 16;
+
+// Upper bound on the treasuries of a vesting account, this bounds the size of every VestingAccount.
+pub const MAX_TREASURIES: usize = 8;
+
+// Upper bound in bytes on a company name, see canonical_company_name for what a name may contain.
+pub const MAX_COMPANY_NAME_LEN: usize = 50;
+
+// Pyth's receiver program, the owner of the price update accounts claim price snapshots are read from.
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = anchor_lang::solana_program::pubkey!(
+    "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ"
+);
+
+// A price update published longer ago than this counts as stale, which claims handle according to the vesting
+// account's dependency_failure_policy.
+pub const MAX_PRICE_AGE_SECONDS: i64 = 60 * 60;
+
+// Upper bound in bytes on a beneficiary's communication pointer, enough for a CID or a short URI.
+pub const MAX_COMMUNICATION_POINTER_LEN: usize = 96;
+
+// How long a beneficiary has to accept a new grant before the grant admin can cancel it.
+pub const ACCEPTANCE_WINDOW_SECONDS: i64 = 30 * SECONDS_PER_DAY;
+
+// Stands in for the mint of grants paid in native SOL, in reservations and events. It can't be confused with a
+// wSOL treasury, whose mint is the native mint.
+pub const NATIVE_SOL_MINT: Pubkey = system_program::ID;
+
+// Hard cap on the protocol's claim fee, set_claim_fee can't go above it. Raising it takes a program upgrade.
+pub const MAX_CLAIM_FEE_BPS: u16 = 500;
+
+// Upper bound on the wallets of an AllowedDestinations, this bounds its size.
+pub const MAX_ALLOWED_DESTINATIONS: usize = 4;
+
+// Anchor's discriminator of `verify_destination(wallet: Pubkey, mint: Pubkey)`, the first 8 bytes of
+// sha256("global:verify_destination"). External allowlist programs implement this instruction, see
+// check_destination_allowed.
+pub const VERIFY_DESTINATION_DISCRIMINATOR: [u8; 8] = [19, 92, 202, 66, 3, 241, 79, 183];
+
+// VestingAccount and EmployeeAccount end in zeroed reserved bytes, so they keep a fixed size as fields are added.
+// A new fixed-size field goes right before `reserved`, and the reserved length shrinks by the field's size, which
+// keeps INIT_SPACE at VESTING_ACCOUNT_SPACE / EMPLOYEE_ACCOUNT_SPACE and lets existing accounts deserialize without
+// a realloc. Existing accounts read the new field from zeroed bytes, so zero has to mean its default (0, false,
+// None or the first enum variant). Only a field that doesn't fit in what is left needs a realloc migration.
+pub const VESTING_ACCOUNT_SPACE: usize = 1024;
+pub const VESTING_ACCOUNT_RESERVED_BYTES: usize = 5;
+pub const EMPLOYEE_ACCOUNT_SPACE: usize = 512;
+pub const EMPLOYEE_ACCOUNT_RESERVED_BYTES: usize = 79;
+
+// Layout versions of VestingAccount and EmployeeAccount, stored in their `version` field. Version 0 is every account
+// created before versioning: the same fields, but possibly allocated too small for the fields added since. A layout
+// change that zeroed bytes can't express bumps the version and adds a step to migrate_vesting_account or
+// migrate_employee_account, which bring older accounts up to date in place.
+pub const VESTING_ACCOUNT_VERSION: u8 = 1;
+pub const EMPLOYEE_ACCOUNT_VERSION: u8 = 1;
+
 #[program]
 pub mod vesting {
     use super::*;
+    // Only the instructions use these, so they leave with them when #[program] can't expand, e.g. in idl-build
+    // builds that can't find the program.
+    use anchor_lang::Discriminator;
+    use anchor_spl::token_interface;
 
     pub fn create_vesting_account(
         ctx: Context<CreateVestingAccount>,
-        company_name: String
-    ) -> Result<()> {
-        // The * operator is used to dereference an account, in this case its the account that the vesting_account variable points to.
-        // To modify an account, you need to dereference the account reference. 
-        // This dereference operator tells Rust that you want to work with the actual account data, not just the reference.
-        // So now you can update values saved to the account state of the vesting_account.
-        *ctx.accounts.vesting_account = VestingAccount {
-            owner: ctx.accounts.signer.key(),
-            mint: ctx.accounts.mint.key(),
-            treasury_token_account: ctx.accounts.treasury_token_account.key(),
-            company_name,
-            treasury_bump: ctx.bumps.treasury_token_account,
-            bump: ctx.bumps.vesting_account,
-            
+        company_name: String,
+        dependency_failure_policy: DependencyFailurePolicy
+    ) -> Result<()> {
+        let bumps = VestingAccountBumps {
+            creator_registry: ctx.bumps.creator_registry,
+            vesting_account: ctx.bumps.vesting_account,
+            treasury: ctx.bumps.treasury_token_account,
+            claim_stats: ctx.bumps.claim_stats,
+            company_name_record: ctx.bumps.company_name_record,
         };
+        init_vesting_account(
+            &ctx.accounts.signer,
+            &mut ctx.accounts.creator_registry,
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.claim_stats,
+            &mut ctx.accounts.company_name_record,
+            &ctx.accounts.config,
+            &ctx.accounts.creator_allowlist_entry,
+            &ctx.accounts.system_program,
+            ctx.accounts.mint.key(),
+            ctx.accounts.treasury_token_account.key(),
+            company_name,
+            dependency_failure_policy,
+            bumps
+        )?;
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_employee_vesting(
         ctx: Context<CreateEmployeeAccount>,
         start_time: i64,
         end_time: i64,
-        total_amount: i64,
-        cliff_time: i64
+        total_amount: u64,
+        cliff_time: i64,
+        schedule_kind: ScheduleKind,
+        vesting_interval_seconds: i64,
+        cliff_unlock_bps: u16
+    ) -> Result<()> {
+        validate_grant(start_time, end_time, cliff_time, total_amount)?;
+        validate_schedule_params(
+            schedule_kind,
+            end_time.saturating_sub(start_time),
+            vesting_interval_seconds,
+            cliff_unlock_bps
+        )?;
+
+        *ctx.accounts.employee_account = EmployeeAccount::new(
+            ctx.accounts.beneficiary.key(),
+            &ctx.accounts.vesting_account,
+            ctx.accounts.mint.key(),
+            ctx.bumps.employee_account,
+            Schedule {
+                kind: schedule_kind,
+                start_time,
+                end_time,
+                cliff_time,
+                total_amount,
+                vesting_interval_seconds,
+                cliff_unlock_bps,
+                milestones: &[],
+            }
+        )?;
+
+        ctx.accounts.vesting_account.reserve(
+            ctx.accounts.mint.key(),
+            total_amount,
+            ctx.accounts.treasury_token_account.amount
+        )?;
+        register_grant(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.grant_entry,
+            ctx.accounts.employee_account.key(),
+            ctx.accounts.employee_account.registry_index,
+            ctx.bumps.grant_entry
+        )?;
+        ctx.accounts.vesting_account.active_grant_count += 1;
+        emit_employee_vesting_created(&ctx.accounts.employee_account, None);
+
+        Ok(())
+    }
+
+    // Creates a pool grant, worth `share_bps` of everything the primary treasury has held rather than a fixed amount,
+    // so every deposit into the treasury grows all pool grants at once. Shares across the vesting account can't
+    // add up to more than 100%, and a primary treasury backing pool grants can't reserve for fixed grants.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_pool_employee_vesting(
+        ctx: Context<CreateEmployeeAccount>,
+        start_time: i64,
+        end_time: i64,
+        share_bps: u16,
+        cliff_time: i64,
+        schedule_kind: ScheduleKind,
+        vesting_interval_seconds: i64,
+        cliff_unlock_bps: u16
     ) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        let mint = ctx.accounts.mint.key();
+        if mint != vesting_account.mint {
+            return Err(ErrorCode::UnknownTreasury.into());
+        }
+        if vesting_account.treasuries.iter().any(|treasury| treasury.mint == mint && treasury.reserved_amount > 0) {
+            return Err(ErrorCode::PoolTreasuryInUse.into());
+        }
+        let pool_share_bps = vesting_account.pool_share_bps
+            .checked_add(share_bps)
+            .filter(|pool_share_bps| share_bps > 0 && *pool_share_bps as u64 <= BPS_DENOMINATOR)
+            .ok_or(ErrorCode::PoolOversubscribed)?;
+        vesting_account.pool_share_bps = pool_share_bps;
+
+        // The pool has to be funded first, a grant worth nothing would count as fully claimed. After a closed grant
+        // gave its share back, that means funded since.
+        let pool_base = vesting_account.pool_released_size;
+        let pool_size = vesting_account.pool_size(ctx.accounts.treasury_token_account.amount)?;
+        let total_amount = bps_of(pool_size.saturating_sub(pool_base), share_bps)?;
+        validate_grant(start_time, end_time, cliff_time, total_amount)?;
+        validate_schedule_params(
+            schedule_kind,
+            end_time.saturating_sub(start_time),
+            vesting_interval_seconds,
+            cliff_unlock_bps
+        )?;
+
         *ctx.accounts.employee_account = EmployeeAccount {
-            beneficiary: ctx.accounts.beneficiary.key(),
-            start_time,
-            end_time,
+            share_bps,
+            pool_base,
+            ..EmployeeAccount::new(
+                ctx.accounts.beneficiary.key(),
+                &ctx.accounts.vesting_account,
+                mint,
+                ctx.bumps.employee_account,
+                Schedule {
+                    kind: schedule_kind,
+                    start_time,
+                    end_time,
+                    cliff_time,
+                    total_amount,
+                    vesting_interval_seconds,
+                    cliff_unlock_bps,
+                    milestones: &[],
+                }
+            )?
+        };
+
+        register_grant(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.grant_entry,
+            ctx.accounts.employee_account.key(),
+            ctx.accounts.employee_account.registry_index,
+            ctx.bumps.grant_entry
+        )?;
+        ctx.accounts.vesting_account.active_grant_count += 1;
+        emit_employee_vesting_created(&ctx.accounts.employee_account, None);
+
+        Ok(())
+    }
+
+    // Creates a grant that follows an arbitrary unlock curve instead of a linear or periodic one.
+    // Each milestone unlocks tokens up to its cumulative share of total_amount once its timestamp has passed.
+    pub fn create_employee_vesting_with_milestones(
+        ctx: Context<CreateEmployeeAccount>,
+        start_time: i64,
+        total_amount: u64,
+        milestones: Vec<Milestone>
+    ) -> Result<()> {
+        validate_milestones(start_time, &milestones)?;
+
+        // Nothing can be claimed before the first milestone, and everything is unlocked at the last one.
+        let cliff_time = milestones[0].timestamp;
+        let end_time = milestones[milestones.len() - 1].timestamp;
+        validate_grant(start_time, end_time, cliff_time, total_amount)?;
+
+        *ctx.accounts.employee_account = EmployeeAccount::new(
+            ctx.accounts.beneficiary.key(),
+            &ctx.accounts.vesting_account,
+            ctx.accounts.mint.key(),
+            ctx.bumps.employee_account,
+            Schedule {
+                kind: ScheduleKind::Milestone,
+                start_time,
+                end_time,
+                cliff_time,
+                total_amount,
+                vesting_interval_seconds: 0,
+                cliff_unlock_bps: 0,
+                milestones: &milestones,
+            }
+        )?;
+
+        ctx.accounts.vesting_account.reserve(
+            ctx.accounts.mint.key(),
             total_amount,
-            total_withdrawn: 0,
-            cliff_time,
+            ctx.accounts.treasury_token_account.amount
+        )?;
+        register_grant(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.grant_entry,
+            ctx.accounts.employee_account.key(),
+            ctx.accounts.employee_account.registry_index,
+            ctx.bumps.grant_entry
+        )?;
+        ctx.accounts.vesting_account.active_grant_count += 1;
+        emit_employee_vesting_created(&ctx.accounts.employee_account, None);
+
+        Ok(())
+    }
+
+    // Creates a grant migrated from another vesting program, keeping what the employee already withdrew there.
+    // source_program and source_account identify the original grant and are recorded in the GrantImported event,
+    // so the migration can be audited against the source program's history.
+    #[allow(clippy::too_many_arguments)]
+    pub fn import_grant(
+        ctx: Context<CreateEmployeeAccount>,
+        start_time: i64,
+        end_time: i64,
+        total_amount: u64,
+        cliff_time: i64,
+        schedule_kind: ScheduleKind,
+        vesting_interval_seconds: i64,
+        cliff_unlock_bps: u16,
+        total_withdrawn: u64,
+        source_program: Pubkey,
+        source_account: Pubkey
+    ) -> Result<()> {
+        let config = load_config(&ctx.accounts.config)?;
+        require_feature(config.as_ref(), Feature::GrantImport)?;
+        validate_grant(start_time, end_time, cliff_time, total_amount)?;
+        validate_schedule_params(
+            schedule_kind,
+            end_time.saturating_sub(start_time),
+            vesting_interval_seconds,
+            cliff_unlock_bps
+        )?;
+        // A grant that was already fully withdrawn has nothing left to migrate and would start out completed.
+        if total_withdrawn >= total_amount {
+            return Err(ErrorCode::InvalidAmount.into());
+        }
+
+        *ctx.accounts.employee_account = EmployeeAccount {
+            total_withdrawn,
+            ..EmployeeAccount::new(
+                ctx.accounts.beneficiary.key(),
+                &ctx.accounts.vesting_account,
+                ctx.accounts.mint.key(),
+                ctx.bumps.employee_account,
+                Schedule {
+                    kind: schedule_kind,
+                    start_time,
+                    end_time,
+                    cliff_time,
+                    total_amount,
+                    vesting_interval_seconds,
+                    cliff_unlock_bps,
+                    milestones: &[],
+                }
+            )?
+        };
+
+        ctx.accounts.vesting_account.reserve(
+            ctx.accounts.mint.key(),
+            total_amount - total_withdrawn,
+            ctx.accounts.treasury_token_account.amount
+        )?;
+        register_grant(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.grant_entry,
+            ctx.accounts.employee_account.key(),
+            ctx.accounts.employee_account.registry_index,
+            ctx.bumps.grant_entry
+        )?;
+        ctx.accounts.vesting_account.active_grant_count += 1;
+        emit_employee_vesting_created(&ctx.accounts.employee_account, None);
+        emit!(GrantImported {
+            employee_account: ctx.accounts.employee_account.key(),
             vesting_account: ctx.accounts.vesting_account.key(),
-            bump: ctx.bumps.employee_account,
+            total_withdrawn,
+            source_program,
+            source_account,
+        });
+
+        Ok(())
+    }
+
+    // Token-2022 mints with a transfer hook need the hook's extra accounts, which are passed through remaining_accounts.
+    // `amount` claims only part of what is claimable, e.g. to spread claims over tax years, the rest stays in the
+    // treasury for later. None claims everything.
+    pub fn claim_tokens<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimTokens<'info>>,
+        // No longer used to derive the vesting account, kept so existing clients don't break.
+        _company_name: String,
+        amount: Option<u64>
+    ) -> Result<()> {
+        let destination = claim_destination_account(
+            &ctx.accounts.employee_account,
+            ctx.accounts.beneficiary.to_account_info(),
+            ctx.accounts.beneficiary.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.employee_token_account,
+            ctx.accounts.claim_destination.as_ref(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.associated_token_program,
+            &ctx.accounts.system_program
+        )?;
+        check_destination_allowed(
+            &ctx.accounts.vesting_account,
+            &ctx.accounts.allowed_destinations,
+            &destination,
+            ctx.accounts.mint.key(),
+            ctx.accounts.allowlist_program.as_deref(),
+            ctx.accounts.allowlist_record.as_deref()
+        )?;
+        claim_vested_tokens(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.employee_account,
+            &mut ctx.accounts.claim_stats,
+            &ctx.accounts.mint,
+            &ctx.accounts.treasury_token_account,
+            ctx.bumps.treasury_token_account,
+            destination,
+            &ctx.accounts.token_program,
+            ctx.accounts.price_update.as_deref(),
+            &ctx.accounts.config,
+            ctx.accounts.fee_token_account.as_ref(),
+            ctx.remaining_accounts,
+            amount,
+            current_time(ctx.accounts.time_override.as_deref())?
+        )
+    }
+
+    // Same as claim_tokens, signed by the claim delegate the beneficiary picked with set_claim_delegate instead of
+    // the beneficiary. Tokens still go to the beneficiary's associated token account, the delegate only pays the fees.
+    pub fn claim_tokens_as_delegate<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimTokensAsDelegate<'info>>
+    ) -> Result<()> {
+        let destination = claim_destination_account(
+            &ctx.accounts.employee_account,
+            ctx.accounts.delegate.to_account_info(),
+            ctx.accounts.beneficiary.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.employee_token_account,
+            ctx.accounts.claim_destination.as_ref(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.associated_token_program,
+            &ctx.accounts.system_program
+        )?;
+        check_destination_allowed(
+            &ctx.accounts.vesting_account,
+            &ctx.accounts.allowed_destinations,
+            &destination,
+            ctx.accounts.mint.key(),
+            ctx.accounts.allowlist_program.as_deref(),
+            ctx.accounts.allowlist_record.as_deref()
+        )?;
+        claim_vested_tokens(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.employee_account,
+            &mut ctx.accounts.claim_stats,
+            &ctx.accounts.mint,
+            &ctx.accounts.treasury_token_account,
+            ctx.bumps.treasury_token_account,
+            destination,
+            &ctx.accounts.token_program,
+            ctx.accounts.price_update.as_deref(),
+            &ctx.accounts.config,
+            ctx.accounts.fee_token_account.as_ref(),
+            ctx.remaining_accounts,
+            None,
+            current_time(ctx.accounts.time_override.as_deref())?
+        )
+    }
+
+    // Creates a company that vests native SOL. Its treasury is a system-owned PDA holding plain lamports, funded
+    // with an ordinary SOL transfer, so the employer doesn't have to wrap anything. Grants are created with
+    // create_sol_employee_vesting and claimed with claim_sol, NATIVE_SOL_MINT takes the place of the mint.
+    pub fn create_sol_vesting(
+        ctx: Context<CreateSolVestingAccount>,
+        company_name: String,
+        dependency_failure_policy: DependencyFailurePolicy
+    ) -> Result<()> {
+        let bumps = VestingAccountBumps {
+            creator_registry: ctx.bumps.creator_registry,
+            vesting_account: ctx.bumps.vesting_account,
+            treasury: ctx.bumps.sol_treasury,
+            claim_stats: ctx.bumps.claim_stats,
+            company_name_record: ctx.bumps.company_name_record,
         };
+        init_vesting_account(
+            &ctx.accounts.signer,
+            &mut ctx.accounts.creator_registry,
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.claim_stats,
+            &mut ctx.accounts.company_name_record,
+            &ctx.accounts.config,
+            &ctx.accounts.creator_allowlist_entry,
+            &ctx.accounts.system_program,
+            NATIVE_SOL_MINT,
+            ctx.accounts.sol_treasury.key(),
+            company_name,
+            dependency_failure_policy,
+            bumps
+        )?;
 
         Ok(())
     }
 
-    pub fn claim_tokens(ctx: Context<ClaimTokens>, _company_name: String) -> Result<()> {
-        // &mut is used to borrow data with the intent to modify it
-        let employee_account = &mut ctx.accounts.employee_account;
+    // Same as create_employee_vesting, for a grant paid in lamports out of the vesting account's SOL treasury.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_sol_employee_vesting(
+        ctx: Context<CreateSolEmployeeAccount>,
+        start_time: i64,
+        end_time: i64,
+        total_amount: u64,
+        cliff_time: i64,
+        schedule_kind: ScheduleKind,
+        vesting_interval_seconds: i64,
+        cliff_unlock_bps: u16
+    ) -> Result<()> {
+        validate_grant(start_time, end_time, cliff_time, total_amount)?;
+        validate_schedule_params(
+            schedule_kind,
+            end_time.saturating_sub(start_time),
+            vesting_interval_seconds,
+            cliff_unlock_bps
+        )?;
+
+        *ctx.accounts.employee_account = EmployeeAccount::new(
+            ctx.accounts.beneficiary.key(),
+            &ctx.accounts.vesting_account,
+            NATIVE_SOL_MINT,
+            ctx.bumps.employee_account,
+            Schedule {
+                kind: schedule_kind,
+                start_time,
+                end_time,
+                cliff_time,
+                total_amount,
+                vesting_interval_seconds,
+                cliff_unlock_bps,
+                milestones: &[],
+            }
+        )?;
+
+        ctx.accounts.vesting_account.reserve(
+            NATIVE_SOL_MINT,
+            total_amount,
+            sol_treasury_balance(&ctx.accounts.sol_treasury)?
+        )?;
+        register_grant(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.grant_entry,
+            ctx.accounts.employee_account.key(),
+            ctx.accounts.employee_account.registry_index,
+            ctx.bumps.grant_entry
+        )?;
+        ctx.accounts.vesting_account.active_grant_count += 1;
+        emit_employee_vesting_created(&ctx.accounts.employee_account, None);
 
-        let now = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    // The SOL counterpart of claim_tokens. The schedule math is the same, the lamports are moved out of the SOL
+    // treasury with a system program transfer signed by the treasury's seeds.
+    pub fn claim_sol(ctx: Context<ClaimSol>) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, vesting_account, GrantAction::Claim)?;
 
-        // Check if the current time is before the cliff time
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
         if now < employee_account.cliff_time {
             return Err(ErrorCode::ClaimNotAvailableYet.into());
         }
+        let (price, vested_until) = price_dependency(
+            vesting_account,
+            NATIVE_SOL_MINT,
+            ctx.accounts.price_update.as_deref(),
+            now
+        )?;
+        let claimable_amount = employee_account.claimable_amount(vested_until)?;
+        if claimable_amount == 0 {
+            return Err(ErrorCode::NothingToClaim.into());
+        }
+        let config = load_config(&ctx.accounts.config)?;
+        let protocol_fee = claim_fee(config.as_ref(), claimable_amount)?;
 
-        // Calculate the vested amount
-        // saturating_sub ensures that the subtraction does not go below zero, which can prevent underflow errors. 
-        let time_since_start = now.saturating_sub(employee_account.start_time);
-        let total_vesting_time = employee_account.end_time.saturating_sub(
-            employee_account.start_time
+        let vesting_account_key = vesting_account.key();
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[SEED_NAMESPACE, b"sol_treasury", vesting_account_key.as_ref(), &[vesting_account.treasury_bump]],
+        ];
+        if protocol_fee > 0 {
+            let fee_recipient = ctx.accounts.fee_recipient.as_ref().ok_or(ErrorCode::FeeAccountMissing)?;
+            check_fee_destination(config.as_ref(), fee_recipient.key(), NATIVE_SOL_MINT, NATIVE_SOL_MINT)?;
+            let fee_cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.sol_treasury.to_account_info(),
+                to: fee_recipient.to_account_info(),
+            };
+            let cpi_context = CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                fee_cpi_accounts,
+                signer_seeds
+            );
+            system_program::transfer(cpi_context, protocol_fee)?;
+        }
+        let transfer_cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.sol_treasury.to_account_info(),
+            to: ctx.accounts.beneficiary.to_account_info(),
+        };
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            transfer_cpi_accounts,
+            signer_seeds
         );
-        if total_vesting_time == 0 {
-            return Err(ErrorCode::InvalidVestingPeriod.into());
+        system_program::transfer(cpi_context, claimable_amount - protocol_fee)?;
+
+        employee_account.total_withdrawn = employee_account.total_withdrawn
+            .checked_add(claimable_amount)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+        vesting_account.release(NATIVE_SOL_MINT, claimable_amount)?;
+        ctx.accounts.claim_stats.record_claim(now, claimable_amount)?;
+
+        emit!(TokensClaimed {
+            employee_account: employee_account.key(),
+            beneficiary: employee_account.beneficiary,
+            mint: NATIVE_SOL_MINT,
+            amount: claimable_amount,
+            transfer_fee: 0,
+            protocol_fee,
+            remaining: employee_account.total_amount.saturating_sub(employee_account.total_withdrawn),
+            price,
+        });
+        Ok(())
+    }
+
+    // The beneficiary's signature here is the on-chain proof that they accepted the grant and its terms.
+    pub fn accept_grant(ctx: Context<AcceptGrant>) -> Result<()> {
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, &ctx.accounts.vesting_account, GrantAction::Accept)?;
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
+        employee_account.accepted_at = Some(now);
+
+        emit!(GrantAccepted {
+            employee_account: employee_account.key(),
+            beneficiary: employee_account.beneficiary,
+            accepted_at: now,
+        });
+
+        Ok(())
+    }
+
+    // Closes a grant the beneficiary never accepted once its acceptance deadline has passed, e.g. an offer
+    // that was declined or sent to a wrong wallet.
+    pub fn cancel_unaccepted_grant(ctx: Context<CancelUnacceptedGrant>) -> Result<()> {
+        transitions::guard(&ctx.accounts.employee_account, &ctx.accounts.vesting_account, GrantAction::CancelOffer)?;
+        if current_time(ctx.accounts.time_override.as_deref())? <= ctx.accounts.employee_account.acceptance_deadline {
+            return Err(ErrorCode::AcceptanceWindowOpen.into());
         }
-        let vested_amount = if now >= employee_account.end_time {
-            employee_account.total_amount
+
+        let employee_account = &ctx.accounts.employee_account;
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        if employee_account.share_bps > 0 {
+            // Nothing was claimed before acceptance, so the share can go to another grant without overdrawing the pool.
+            vesting_account.pool_share_bps = vesting_account.pool_share_bps.saturating_sub(employee_account.share_bps);
         } else {
-            // Perform a checked multiplication to handle possible overflow
-            match employee_account.total_amount.checked_mul(time_since_start) {
-                Some(product) => {
-                    // Safe to do the division after successful multiplication
-                    product / total_vesting_time
-                },
-                None => {
-                    // Handle overflow case, e.g., by logging or returning an error
-                    return Err(ErrorCode::CalculationOverflow.into());
-                }
+            vesting_account.release(
+                employee_account.mint,
+                employee_account.total_amount.saturating_sub(employee_account.total_withdrawn)
+            )?;
+        }
+        vesting_account.active_grant_count = vesting_account.active_grant_count.saturating_sub(1);
+
+        emit!(UnacceptedGrantCancelled {
+            employee_account: ctx.accounts.employee_account.key(),
+            vesting_account: vesting_account.key(),
+            beneficiary: ctx.accounts.employee_account.beneficiary,
+        });
+
+        Ok(())
+    }
+
+    // Opts a grant into claims by `delegate`, e.g. a scheduled auto-claim service or a custodian. None revokes it.
+    pub fn set_claim_delegate(ctx: Context<SetClaimDelegate>, delegate: Option<Pubkey>) -> Result<()> {
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, &ctx.accounts.vesting_account, GrantAction::SetClaimDelegate)?;
+        employee_account.claim_delegate = delegate;
+
+        emit!(ClaimDelegateSet {
+            employee_account: employee_account.key(),
+            beneficiary: employee_account.beneficiary,
+            delegate,
+        });
+
+        Ok(())
+    }
+
+    // Redirects the grant's claims to `destination`, a token account of the grant's mint that doesn't have to belong
+    // to the beneficiary, e.g. after their associated token account was handed to another owner. None sends claims
+    // to the beneficiary's associated token account again.
+    pub fn set_claim_destination(ctx: Context<SetClaimDestination>, destination: Option<Pubkey>) -> Result<()> {
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, &ctx.accounts.vesting_account, GrantAction::SetClaimDestination)?;
+        if let Some(destination) = destination {
+            let destination_token_account = ctx.accounts.destination_token_account
+                .as_ref()
+                .ok_or(ErrorCode::ClaimDestinationMissing)?;
+            if destination_token_account.key() != destination {
+                return Err(ErrorCode::InvalidClaimDestination.into());
             }
-        };
+            check_claim_destination(destination_token_account, employee_account.mint, None)?;
+        }
+        employee_account.claim_destination = destination;
 
-        //Calculate the amount that can be withdrawn
-        let claimable_amount = vested_amount.saturating_sub(employee_account.total_withdrawn);
-        
-        // Check if there is anything left to claim
-        if claimable_amount == 0 {
-            return Err(ErrorCode::NothingToClaim.into());
+        emit!(ClaimDestinationSet {
+            employee_account: employee_account.key(),
+            beneficiary: employee_account.beneficiary,
+            destination,
+        });
+
+        Ok(())
+    }
+
+    // Turns the destination gate on or off, for issuers that may only pay KYC-verified wallets. While it is on,
+    // claim_tokens and claim_tokens_as_delegate only pay into token accounts owned by a wallet the beneficiary's
+    // AllowedDestinations lets through, and claim_all skips the vesting account's grants. SOL claims always pay the
+    // beneficiary's wallet, so SOL vesting accounts can't turn it on.
+    pub fn set_require_allowed_destinations(
+        ctx: Context<UpdateVestingAccount>,
+        require_allowed_destinations: bool
+    ) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        if require_allowed_destinations && vesting_account.mint == NATIVE_SOL_MINT {
+            return Err(ErrorCode::DestinationGateUnsupported.into());
         }
+        vesting_account.require_allowed_destinations = require_allowed_destinations;
 
-        // Now we can transfer the tokens to the employee, this invloves a CPI call, which is a cross-program invocation.
-        let transfer_cpi_accounts = TransferChecked {
-            from: ctx.accounts.treasury_token_account.to_account_info(),
-            mint: ctx.accounts.mint.to_account_info(),
-            to: ctx.accounts.employee_token_account.to_account_info(),
-            authority: ctx.accounts.treasury_token_account.to_account_info(),
+        emit!(DestinationGateSet {
+            vesting_account: vesting_account.key(),
+            require_allowed_destinations,
+        });
+
+        Ok(())
+    }
+
+    // Registers the wallets a beneficiary's claims may be paid to, replacing the previous ones. Wallets that aren't
+    // listed are checked with `allowlist_program` when one is set, see check_destination_allowed.
+    pub fn set_allowed_destinations(
+        ctx: Context<SetAllowedDestinations>,
+        beneficiary: Pubkey,
+        wallets: Vec<Pubkey>,
+        allowlist_program: Option<Pubkey>
+    ) -> Result<()> {
+        if wallets.len() > MAX_ALLOWED_DESTINATIONS {
+            return Err(ErrorCode::TooManyAllowedDestinations.into());
+        }
+        *ctx.accounts.allowed_destinations = AllowedDestinations {
+            vesting_account: ctx.accounts.vesting_account.key(),
+            beneficiary,
+            wallets: wallets.clone(),
+            allowlist_program,
+            bump: ctx.bumps.allowed_destinations,
         };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
 
-        // When transferring tokens using the SPL Token program, the transfer instruction must be signed by the owner of the tokens or an authorized delegate.
-        // In our case, the authority is the treasury_token_account, which is the account that holds the tokens.
-        // So we need to define the seeds for this account to sign the transfer instruction.
+        emit!(AllowedDestinationsSet {
+            vesting_account: ctx.accounts.vesting_account.key(),
+            beneficiary,
+            wallets,
+            allowlist_program,
+        });
 
-        let signer_seeds: &[&[&[u8]]] = &[
-            &[
-                b"vesting_treasury",
-                ctx.accounts.vesting_account.company_name.as_ref(),
-                &[ctx.accounts.vesting_account.treasury_bump],
-            ],
-        ];
-        let cpi_context = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(
-            signer_seeds
-        );
+        Ok(())
+    }
+
+    // Closing the beneficiary's AllowedDestinations blocks their claims while the destination gate is on.
+    pub fn close_allowed_destinations(ctx: Context<CloseAllowedDestinations>, beneficiary: Pubkey) -> Result<()> {
+        emit!(AllowedDestinationsSet {
+            vesting_account: ctx.accounts.vesting_account.key(),
+            beneficiary,
+            wallets: Vec::new(),
+            allowlist_program: None,
+        });
 
-        // you're specifying that the CPI call should be signed by an account derived from the provided seeds. 
-        let decimals = ctx.accounts.mint.decimals;
-        token_interface::transfer_checked(cpi_context, claimable_amount as u64, decimals)?;
-        // update account state to reflect the amount that has been withdrawn
-        employee_account.total_withdrawn += claimable_amount;
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(company_name: String)]
-pub struct CreateVestingAccount<'info> {
-    #[account(mut)]
-    pub signer: Signer<'info>,
-    #[account(
-        init,
-        space = 8 + VestingAccount::INIT_SPACE,
-        payer = signer,
-        seeds = [company_name.as_ref()],
-        bump
-    )]
-    pub vesting_account: Account<'info, VestingAccount>,
-    pub mint: InterfaceAccount<'info, Mint>,
-    #[account(
-        init,
-        token::mint = mint,
-        token::authority = treasury_token_account,
-        payer = signer,
-        seeds = [b"vesting_treasury", company_name.as_bytes()],
-        bump
-    )]
-    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub system_program: Program<'info, System>,
-}
+    // Claims every grant passed in remaining_accounts in one transaction, as groups of
+    // [employee_account, vesting_account, mint, treasury_token_account, employee_token_account, claim_stats].
+    // Grants that are paused, not accepted yet, before their cliff or fully claimed are skipped instead of failing the batch.
+    // So are grants priced by a feed, unless the vesting account's dependency_failure_policy is TimeBased.
+    // The employee token accounts must already exist, and mints with a transfer hook have to use claim_tokens.
+    // A grant with a registered claim destination pays into it, so it has to be passed in place of the token account.
+    pub fn claim_all<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimAll<'info>>) -> Result<()> {
+        let remaining_accounts = ctx.remaining_accounts;
+        if remaining_accounts.is_empty() || !remaining_accounts.len().is_multiple_of(CLAIM_ALL_GROUP_LEN) {
+            return Err(ErrorCode::InvalidClaimAccounts.into());
+        }
 
-#[derive(Accounts)]
-pub struct CreateEmployeeAccount<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    pub beneficiary: SystemAccount<'info>,
-    #[account(has_one = owner)]
-    pub vesting_account: Account<'info, VestingAccount>,
-    #[account(
-        init,
-        space = 8 + EmployeeAccount::INIT_SPACE,
-        payer = owner,
-        seeds = [b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref()],
-        bump
-    )]
-    pub employee_account: Account<'info, EmployeeAccount>,
-    pub system_program: Program<'info, System>,
-}
+        let config = load_config(&ctx.accounts.config)?;
+        require_feature(config.as_ref(), Feature::ClaimAll)?;
+        // Groups have no room for fee accounts, so deployments charging a claim fee leave claims to claim_tokens.
+        if config.is_some_and(|config| config.claim_fee_bps > 0) {
+            return Err(ErrorCode::ClaimFeeUnsupported.into());
+        }
 
-#[derive(Accounts)]
-#[instruction(company_name: String)]
-// The has_one constraint is used within the #[account] attribute macro to assert that the specified field of a data structure 
-// (usually an account in this context) points to a specific account.
-pub struct ClaimTokens<'info> {
-    #[account(mut)]
-    pub beneficiary: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref()],
-        bump = employee_account.bump,
-        has_one = beneficiary,
-        has_one = vesting_account
-    )]
-    pub employee_account: Account<'info, EmployeeAccount>,
-    #[account(
-        mut,
-        seeds = [company_name.as_ref()],
-        bump = vesting_account.bump,
-        has_one = treasury_token_account,
-        has_one = mint
-    )]
-    pub vesting_account: Account<'info, VestingAccount>,
-    pub mint: InterfaceAccount<'info, Mint>,
-    #[account(mut)]
-    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
-    #[account(
-        init_if_needed,
-        payer = beneficiary,
-        associated_token::mint = mint,
-        associated_token::authority = beneficiary,
-        associated_token::token_program = token_program
-    )]
-    pub employee_token_account: InterfaceAccount<'info, TokenAccount>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
-}
+        let beneficiary = ctx.accounts.beneficiary.key();
+        let token_program = ctx.accounts.token_program.key();
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
 
-#[account]
-#[derive(InitSpace, Debug)]
-pub struct VestingAccount {
-    pub owner: Pubkey,
-    pub mint: Pubkey,
-    pub treasury_token_account: Pubkey,
-    #[max_len(50)]
+        for group in remaining_accounts.chunks(CLAIM_ALL_GROUP_LEN) {
+            let [employee_info, vesting_info, mint_info, treasury_info, destination_info, claim_stats_info] = group else {
+                return Err(ErrorCode::InvalidClaimAccounts.into());
+            };
+
+            // Deserializing through Account and InterfaceAccount checks the owners and discriminators,
+            // the remaining checks mirror the has_one constraints of ClaimTokens.
+            let mut employee_account = Account::<EmployeeAccount>::try_from(employee_info)?;
+            let mut vesting_account = Account::<VestingAccount>::try_from(vesting_info)?;
+            let mint = InterfaceAccount::<Mint>::try_from(mint_info)?;
+            let destination = InterfaceAccount::<TokenAccount>::try_from(destination_info)?;
+            let mut claim_stats = Account::<ClaimStats>::try_from(claim_stats_info)?;
+            let vesting_account_key = vesting_account.key();
+            let mint_key = mint.key();
+            let seeds = treasury_signer_seeds(&vesting_account, &vesting_account_key, &mint_key, &[]);
+            let (treasury, treasury_bump) = Pubkey::find_program_address(&seeds[..seeds.len() - 1], &crate::ID);
+            if employee_account.beneficiary != beneficiary
+                || claim_stats.vesting_account != vesting_account.key()
+                || employee_account.vesting_account != vesting_account.key()
+                || employee_account.mint != mint.key()
+                || treasury != treasury_info.key()
+                || *mint_info.owner != token_program
+            {
+                return Err(ErrorCode::InvalidClaimAccounts.into());
+            }
+            match employee_account.claim_destination {
+                Some(registered) if registered != destination.key() => {
+                    return Err(ErrorCode::InvalidClaimDestination.into());
+                }
+                Some(_) => check_claim_destination(&destination, mint.key(), None)?,
+                None => check_claim_destination(&destination, mint.key(), Some(beneficiary))?,
+            }
+
+            // claim_all passes no transfer hook accounts, so grants of mints with a hook are left to claim_tokens.
+            if !is_transfer_hook_available(mint_info, &[])? {
+                continue;
+            }
+            let Some(claimable_amount) = claim_all_amount(&employee_account, &vesting_account, now)? else {
+                continue;
+            };
+
+            let transfer_fee = calculate_transfer_fee(mint_info, claimable_amount)?;
+            transfer_from_treasury(
+                &token_program,
+                &vesting_account,
+                treasury_info.clone(),
+                treasury_bump,
+                mint_info.clone(),
+                destination_info.clone(),
+                &[],
+                claimable_amount,
+                mint.decimals
+            )?;
+            employee_account.total_withdrawn = employee_account.total_withdrawn
+                .checked_add(claimable_amount)
+                .ok_or(ErrorCode::CalculationOverflow)?;
+            vesting_account.release(employee_account.mint, claimable_amount)?;
+            claim_stats.record_claim(now, claimable_amount)?;
+            // Accounts loaded by hand aren't written back automatically like the ones in the Accounts struct.
+            // Writing them back before the next group is loaded also keeps grants sharing a company consistent.
+            employee_account.exit(&crate::ID)?;
+            vesting_account.exit(&crate::ID)?;
+            claim_stats.exit(&crate::ID)?;
+
+            emit!(TokensClaimed {
+                employee_account: employee_account.key(),
+                beneficiary,
+                mint: employee_account.mint,
+                amount: claimable_amount,
+                transfer_fee,
+                protocol_fee: 0,
+                remaining: employee_account.total_amount.saturating_sub(employee_account.total_withdrawn),
+                // Groups have no room for price update accounts, claim_tokens records prices.
+                price: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Read-only view of a grant at `timestamp`, or at the current time when None. Clients simulate this
+    // and read the return data instead of reimplementing the vesting math, so they always match claim_tokens.
+    pub fn get_claimable_amount(ctx: Context<GetClaimableAmount>, timestamp: Option<i64>) -> Result<ClaimableAmount> {
+        let mut employee_account = (*ctx.accounts.employee_account).clone();
+        refresh_pool_grant(
+            &mut employee_account,
+            &ctx.accounts.vesting_account,
+            ctx.accounts.treasury_token_account.as_ref()
+        )?;
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp,
+            None => current_time(ctx.accounts.time_override.as_deref())?,
+        };
+
+        Ok(ClaimableAmount {
+            timestamp,
+            vested_amount: employee_account.vested_amount(timestamp)?,
+            withdrawn_amount: employee_account.total_withdrawn,
+            claimable_amount: employee_account.claimable_amount(timestamp)?,
+        })
+    }
+
+    // Records what the grant has vested and not withdrawn yet into its VotingPower account, so governance can count
+    // it before it is claimed. Grants that haven't been accepted have no power. Anyone can crank it, the snapshot
+    // is keyed by the grant's registry index so it follows the grant across beneficiary reassignments.
+    pub fn snapshot_vested_amount(ctx: Context<SnapshotVestedAmount>) -> Result<()> {
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
+        let slot = Clock::get()?.slot;
+        let employee_account_key = ctx.accounts.employee_account.key();
+        let mut employee_account = (*ctx.accounts.employee_account).clone();
+        refresh_pool_grant(
+            &mut employee_account,
+            &ctx.accounts.vesting_account,
+            ctx.accounts.treasury_token_account.as_ref()
+        )?;
+        let amount = match employee_account.accepted_at {
+            Some(_) => employee_account.claimable_amount(now)?,
+            None => 0,
+        };
+
+        *ctx.accounts.voting_power = VotingPower {
+            vesting_account: employee_account.vesting_account,
+            registry_index: employee_account.registry_index,
+            employee_account: employee_account_key,
+            beneficiary: employee_account.beneficiary,
+            mint: employee_account.mint,
+            amount,
+            timestamp: now,
+            slot,
+            bump: ctx.bumps.voting_power,
+        };
+
+        emit!(VestedAmountSnapshotted {
+            employee_account: employee_account_key,
+            vesting_account: employee_account.vesting_account,
+            beneficiary: employee_account.beneficiary,
+            amount,
+            slot,
+        });
+
+        Ok(())
+    }
+
+    // Fails once `deadline` has passed. Claim-and-swap transactions start with it, so a transaction held back by
+    // a validator or relayer can't land later, when the quote it was built from no longer holds.
+    pub fn check_deadline(ctx: Context<CheckDeadline>, deadline: i64) -> Result<()> {
+        if ctx.accounts.clock.unix_timestamp > deadline {
+            return Err(ErrorCode::DeadlineExceeded.into());
+        }
+        Ok(())
+    }
+
+    // Ownership moves in two steps so a mistyped key can't take over grant administration: the owner proposes
+    // a new owner here, and nothing changes until that key signs accept_ownership. None cancels a pending proposal.
+    pub fn propose_new_owner(ctx: Context<UpdateVestingAccount>, new_owner: Option<Pubkey>) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        vesting_account.pending_owner = new_owner;
+
+        emit!(OwnershipTransferProposed {
+            vesting_account: vesting_account.key(),
+            owner: vesting_account.owner,
+            pending_owner: new_owner,
+        });
+
+        Ok(())
+    }
+
+    pub fn accept_ownership(ctx: Context<AcceptOwnership>) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        let previous_owner = vesting_account.owner;
+        vesting_account.owner = ctx.accounts.pending_owner.key();
+        vesting_account.pending_owner = None;
+
+        emit!(OwnershipTransferred {
+            vesting_account: vesting_account.key(),
+            previous_owner,
+            new_owner: vesting_account.owner,
+        });
+
+        Ok(())
+    }
+
+    // Halts all claims of the company during an incident, e.g. a compromised mint or a legal hold.
+    pub fn set_paused(ctx: Context<UpdateVestingAccount>, paused: bool) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        vesting_account.is_paused = paused;
+
+        emit!(VestingPauseSet {
+            vesting_account: vesting_account.key(),
+            is_paused: paused,
+        });
+
+        Ok(())
+    }
+
+    // Hands one of the administration roles to another key, e.g. an HR multisig for grant_admin. The roles
+    // stay in place when ownership moves, the new owner reassigns them if needed.
+    pub fn set_role(ctx: Context<UpdateVestingAccount>, role: VestingRole, key: Pubkey) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        let slot = match role {
+            VestingRole::GrantAdmin => &mut vesting_account.grant_admin,
+            VestingRole::RevokeAdmin => &mut vesting_account.revoke_admin,
+            VestingRole::TreasuryAdmin => &mut vesting_account.treasury_admin,
+        };
+        let previous = *slot;
+        *slot = key;
+
+        emit!(RoleAssigned {
+            vesting_account: vesting_account.key(),
+            role,
+            previous,
+            key,
+        });
+
+        Ok(())
+    }
+
+    // Lets the owner move grants to a new wallet on behalf of employees who lost access to theirs.
+    // Off by default, since it gives the owner control over where vested tokens go.
+    pub fn set_owner_can_reassign_beneficiary(
+        ctx: Context<UpdateVestingAccount>,
+        allowed: bool
+    ) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        vesting_account.owner_can_reassign_beneficiary = allowed;
+
+        emit!(BeneficiaryReassignmentPolicySet {
+            vesting_account: vesting_account.key(),
+            owner_can_reassign_beneficiary: allowed,
+        });
+
+        Ok(())
+    }
+
+    // Changes the company name shown to beneficiaries. Only the CompanyNameRecord is seeded by the name, the
+    // vesting account and its treasuries keep their addresses. The record of the new name is created and the one of
+    // the previous name closed, unless both names have the same canonical form.
+    pub fn rename_company(ctx: Context<RenameCompany>, company_name: String) -> Result<()> {
+        let canonical_name = canonical_company_name(&company_name)?;
+        let vesting_account_key = ctx.accounts.vesting_account.key();
+        if ctx.accounts.vesting_account.legacy_seeds {
+            return Err(ErrorCode::LegacyCompanyName.into());
+        }
+        let company_name_record = &mut ctx.accounts.company_name_record;
+        if company_name_record.vesting_account == Pubkey::default() {
+            company_name_record.vesting_account = vesting_account_key;
+            company_name_record.bump = ctx.bumps.company_name_record;
+        } else if company_name_record.vesting_account != vesting_account_key {
+            return Err(ErrorCode::CompanyNameTaken.into());
+        }
+        if ctx.accounts.previous_company_name_record.key() != company_name_record.key() {
+            close_company_name_record(
+                &ctx.accounts.previous_company_name_record,
+                vesting_account_key,
+                &ctx.accounts.owner
+            )?;
+        }
+
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        let previous_name = std::mem::replace(&mut vesting_account.company_name, company_name);
+        vesting_account.canonical_name = canonical_name;
+
+        emit!(CompanyRenamed {
+            vesting_account: vesting_account.key(),
+            previous_name,
+            company_name: vesting_account.company_name.clone(),
+        });
+
+        Ok(())
+    }
+
+    // Sets how long execute_clawback has to wait after a grant is revoked. Grants that are already revoked keep
+    // the delay they were revoked with.
+    pub fn set_clawback_delay(ctx: Context<UpdateVestingAccount>, clawback_delay_seconds: i64) -> Result<()> {
+        if clawback_delay_seconds < 0 {
+            return Err(ErrorCode::InvalidClawbackDelay.into());
+        }
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        vesting_account.clawback_delay_seconds = clawback_delay_seconds;
+
+        emit!(ClawbackDelaySet {
+            vesting_account: vesting_account.key(),
+            clawback_delay_seconds,
+        });
+
+        Ok(())
+    }
+
+    // The employee account PDA is derived from the beneficiary, so reassigning it moves the grant into a new PDA
+    // and closes the old one within the same instruction. The authority pays for the new account and gets the old rent back.
+    pub fn reassign_beneficiary(ctx: Context<ReassignBeneficiary>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let vesting_account = &ctx.accounts.vesting_account;
+        let old_employee_account = &ctx.accounts.employee_account;
+        transitions::guard(old_employee_account, vesting_account, GrantAction::ReassignBeneficiary)?;
+        let config = load_config(&ctx.accounts.config)?;
+        check_reassignment_authority(authority, old_employee_account, vesting_account, config.as_ref())?;
+
+        let previous_beneficiary = old_employee_account.beneficiary;
+        *ctx.accounts.new_employee_account = EmployeeAccount {
+            beneficiary: ctx.accounts.new_beneficiary.key(),
+            // The delegate and the destination were picked by the previous wallet, the new one has to opt in again.
+            claim_delegate: None,
+            claim_destination: None,
+            bump: ctx.bumps.new_employee_account,
+            ..(**old_employee_account).clone()
+        };
+        ctx.accounts.grant_entry.employee_account = ctx.accounts.new_employee_account.key();
+
+        emit!(BeneficiaryReassigned {
+            vesting_account: vesting_account.key(),
+            previous_employee_account: old_employee_account.key(),
+            new_employee_account: ctx.accounts.new_employee_account.key(),
+            previous_beneficiary,
+            new_beneficiary: ctx.accounts.new_beneficiary.key(),
+        });
+
+        Ok(())
+    }
+
+    // Lets a beneficiary publish where notifications about their grants should go. The pointer is opaque to the
+    // program, typically the content hash or URI of an encrypted contact blob only the notification service can read.
+    // Indexers link it to grants through the beneficiary key. An empty pointer opts out of notifications.
+    pub fn set_communication_pointer(ctx: Context<SetCommunicationPointer>, pointer: Vec<u8>) -> Result<()> {
+        if pointer.len() > MAX_COMMUNICATION_POINTER_LEN {
+            return Err(ErrorCode::CommunicationPointerTooLong.into());
+        }
+
+        let beneficiary_registry = &mut ctx.accounts.beneficiary_registry;
+        beneficiary_registry.beneficiary = ctx.accounts.beneficiary.key();
+        beneficiary_registry.communication_pointer = pointer;
+        beneficiary_registry.bump = ctx.bumps.beneficiary_registry;
+
+        emit!(CommunicationPointerSet {
+            beneficiary: beneficiary_registry.beneficiary,
+            pointer: beneficiary_registry.communication_pointer.clone(),
+        });
+
+        Ok(())
+    }
+
+    // Adds tokens to a grant, e.g. back pay or a raise: `amount` moves from the grant admin into the treasury and whatever
+    // arrives after transfer fees is added to total_amount and reserved for the grant. Transfer hook accounts go
+    // through remaining_accounts, like in claim_tokens.
+    pub fn top_up_grant<'info>(ctx: Context<'_, '_, '_, 'info, TopUpGrant<'info>>, amount: u64) -> Result<()> {
+        transitions::guard(&ctx.accounts.employee_account, &ctx.accounts.vesting_account, GrantAction::TopUp)?;
+        if amount == 0 {
+            return Err(ErrorCode::InvalidAmount.into());
+        }
+        let transfer_fee = calculate_transfer_fee(&ctx.accounts.mint.to_account_info(), amount)?;
+        spl_token_2022::onchain::invoke_transfer_checked(
+            ctx.accounts.token_program.key,
+            ctx.accounts.source_token_account.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.treasury_token_account.to_account_info(),
+            ctx.accounts.grant_admin.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            ctx.accounts.mint.decimals,
+            &[]
+        )?;
+        ctx.accounts.treasury_token_account.reload()?;
+
+        let received = amount.saturating_sub(transfer_fee);
+        let employee_account = &mut ctx.accounts.employee_account;
+        ctx.accounts.vesting_account.reserve(
+            employee_account.mint,
+            received,
+            ctx.accounts.treasury_token_account.amount
+        )?;
+        employee_account.total_amount = employee_account.total_amount
+            .checked_add(received)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+
+        emit!(GrantToppedUp {
+            employee_account: employee_account.key(),
+            vesting_account: employee_account.vesting_account,
+            amount: received,
+            transfer_fee,
+            total_amount: employee_account.total_amount,
+        });
+
+        Ok(())
+    }
+
+    // Amends a grant in place so total_withdrawn is kept, instead of closing and recreating it. The grant admin can move
+    // end_time, total_amount and cliff_time, as long as nothing already vested becomes locked again.
+    pub fn update_employee_vesting(
+        ctx: Context<UpdateEmployeeAccount>,
+        end_time: i64,
+        total_amount: u64,
+        cliff_time: i64
+    ) -> Result<()> {
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, &ctx.accounts.vesting_account, GrantAction::Amend)?;
+
+        if end_time < now {
+            return Err(ErrorCode::EndTimeInPast.into());
+        }
+        validate_grant(employee_account.start_time, end_time, cliff_time, total_amount)?;
+        // Milestone grants derive their cliff and end from the milestones, so only the amount can change.
+        if employee_account.schedule_kind == ScheduleKind::Milestone {
+            if end_time != employee_account.end_time || cliff_time != employee_account.cliff_time {
+                return Err(ErrorCode::InvalidMilestones.into());
+            }
+        } else {
+            validate_schedule_params(
+                employee_account.schedule_kind,
+                end_time.saturating_sub(employee_account.start_time),
+                employee_account.vesting_interval_seconds,
+                employee_account.cliff_unlock_bps
+            )?;
+        }
+
+        // The reservation follows total_amount, so increasing it needs enough unreserved tokens in the treasury.
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        if total_amount > employee_account.total_amount {
+            vesting_account.reserve(
+                employee_account.mint,
+                total_amount - employee_account.total_amount,
+                ctx.accounts.treasury_token_account.amount
+            )?;
+        } else {
+            vesting_account.release(employee_account.mint, employee_account.total_amount - total_amount)?;
+        }
+
+        let vested_before = employee_account.vested_amount(now)?;
+        let previous_end_time = employee_account.end_time;
+        let previous_total_amount = employee_account.total_amount;
+        let previous_cliff_time = employee_account.cliff_time;
+        employee_account.end_time = end_time;
+        employee_account.total_amount = total_amount;
+        employee_account.cliff_time = cliff_time;
+        // This also guarantees total_amount never drops below total_withdrawn, which is at most vested_before.
+        if employee_account.vested_amount(now)? < vested_before {
+            return Err(ErrorCode::VestedAmountReduced.into());
+        }
+
+        emit!(EmployeeVestingUpdated {
+            employee_account: employee_account.key(),
+            vesting_account: employee_account.vesting_account,
+            beneficiary: employee_account.beneficiary,
+            previous_end_time,
+            previous_total_amount,
+            previous_cliff_time,
+            end_time,
+            total_amount,
+            cliff_time,
+        });
+
+        Ok(())
+    }
+
+    // Stops a grant from vesting any further, e.g. when the employee leaves. What vested so far stays claimable,
+    // the rest is only returned to the treasury by execute_clawback after the clawback delay.
+    pub fn revoke_grant(ctx: Context<RevokeGrant>) -> Result<()> {
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
+        let vesting_account = &ctx.accounts.vesting_account;
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, vesting_account, GrantAction::Revoke)?;
+        let clawback_available_at = employee_account.revoke(now, vesting_account.clawback_delay_seconds);
+
+        emit!(GrantRevoked {
+            employee_account: employee_account.key(),
+            vesting_account: vesting_account.key(),
+            beneficiary: employee_account.beneficiary,
+            revoked_at: now,
+            clawback_available_at,
+            unvested_amount: employee_account.total_amount.saturating_sub(employee_account.vested_amount(now)?),
+        });
+
+        Ok(())
+    }
+
+    // Undoes a revocation before the clawback was executed, e.g. after a dispute was resolved in the beneficiary's
+    // favor. The grant vests as if it had never been revoked.
+    pub fn rescind_revocation(ctx: Context<RevokeGrant>) -> Result<()> {
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, &ctx.accounts.vesting_account, GrantAction::RescindRevocation)?;
+        employee_account.revoked_at = None;
+        employee_account.clawback_available_at = None;
+
+        emit!(RevocationRescinded {
+            employee_account: employee_account.key(),
+            vesting_account: employee_account.vesting_account,
+        });
+
+        Ok(())
+    }
+
+    // Returns the unvested part of a revoked grant to the treasury once the clawback delay has passed. The tokens
+    // never leave the treasury, so this shrinks the grant to what had vested and releases the rest of the
+    // reservation. Anyone can crank it.
+    pub fn execute_clawback(ctx: Context<ExecuteClawback>) -> Result<()> {
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, vesting_account, GrantAction::ExecuteClawback)?;
+        let clawed_back_amount = employee_account.claw_back(now)?;
+        vesting_account.release(employee_account.mint, clawed_back_amount)?;
+
+        emit!(ClawbackExecuted {
+            employee_account: employee_account.key(),
+            vesting_account: vesting_account.key(),
+            beneficiary: employee_account.beneficiary,
+            amount: clawed_back_amount,
+            total_amount: employee_account.total_amount,
+        });
+
+        Ok(())
+    }
+
+    // Moves a grant to another vesting program in one atomic step, with the consent of both the treasury admin and
+    // the beneficiary. What has vested but not been withdrawn is paid to the beneficiary first, only the unvested rest
+    // goes from the treasury to `destination_token_account`, the other program's escrow, and the grant is closed here.
+    // The escrow has to belong to the destination program's `[b"grant_import", external_reference]` PDA, so the
+    // tokens can't be sent anywhere else. The other program's own instruction is then invoked with `instruction_data`
+    // and remaining_accounts, so it can register the grant (e.g. from the GrantExported event fields). Mints with a
+    // transfer hook aren't supported, since remaining_accounts are reserved for that instruction.
+    pub fn export_grant<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExportGrant<'info>>,
+        external_reference: Pubkey,
+        instruction_data: Vec<u8>
+    ) -> Result<()> {
+        let config = load_config(&ctx.accounts.config)?;
+        require_feature(config.as_ref(), Feature::GrantExport)?;
+        let employee_account = &ctx.accounts.employee_account;
+        let vesting_account = &ctx.accounts.vesting_account;
+        transitions::guard(employee_account, vesting_account, GrantAction::Export)?;
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
+        let vested_amount = employee_account.vested_amount(now)?;
+        let remaining_amount = employee_account.total_amount.saturating_sub(employee_account.total_withdrawn);
+        let paid_amount = vested_amount.saturating_sub(employee_account.total_withdrawn);
+        let amount = remaining_amount.saturating_sub(paid_amount);
+
+        if remaining_amount > 0 && !is_transfer_hook_available(&ctx.accounts.mint.to_account_info(), &[])? {
+            return Err(ErrorCode::DependencyUnavailable.into());
+        }
+        if paid_amount > 0 {
+            transfer_from_treasury(
+                ctx.accounts.token_program.key,
+                vesting_account,
+                ctx.accounts.treasury_token_account.to_account_info(),
+                ctx.bumps.treasury_token_account,
+                ctx.accounts.mint.to_account_info(),
+                ctx.accounts.beneficiary_token_account.to_account_info(),
+                &[],
+                paid_amount,
+                ctx.accounts.mint.decimals
+            )?;
+        }
+        if amount > 0 {
+            transfer_from_treasury(
+                ctx.accounts.token_program.key,
+                vesting_account,
+                ctx.accounts.treasury_token_account.to_account_info(),
+                ctx.bumps.treasury_token_account,
+                ctx.accounts.mint.to_account_info(),
+                ctx.accounts.destination_token_account.to_account_info(),
+                &[],
+                amount,
+                ctx.accounts.mint.decimals
+            )?;
+        }
+
+        // No signer seeds here, the other program only gets the signatures of the outer transaction.
+        let instruction = Instruction {
+            program_id: ctx.accounts.destination_program.key(),
+            accounts: ctx.remaining_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.key(),
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: instruction_data,
+        };
+        invoke(&instruction, ctx.remaining_accounts)?;
+
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        vesting_account.release(employee_account.mint, remaining_amount)?;
+        vesting_account.active_grant_count = vesting_account.active_grant_count.saturating_sub(1);
+
+        emit!(GrantExported {
+            employee_account: employee_account.key(),
+            vesting_account: vesting_account.key(),
+            beneficiary: employee_account.beneficiary,
+            amount,
+            paid_amount,
+            total_withdrawn: employee_account.total_withdrawn.saturating_add(paid_amount),
+            vested_amount,
+            destination_program: ctx.accounts.destination_program.key(),
+            destination_token_account: ctx.accounts.destination_token_account.key(),
+            external_reference,
+        });
+
+        Ok(())
+    }
+
+    // Opens a treasury for another mint, so the company can grant e.g. a governance token and a stablecoin bonus
+    // from the same vesting account. Grants pick their mint at creation.
+    pub fn add_treasury(ctx: Context<AddTreasury>) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        vesting_account.add_treasury(ctx.accounts.mint.key())?;
+
+        emit!(TreasuryAdded {
+            vesting_account: vesting_account.key(),
+            mint: ctx.accounts.mint.key(),
+            treasury_token_account: ctx.accounts.treasury_token_account.key(),
+        });
+
+        Ok(())
+    }
+
+    // Closes an empty additional treasury, the primary one is closed by close_vesting_account. Grants in its mint
+    // can't be claimed until the treasury is added again.
+    pub fn close_treasury(ctx: Context<CloseTreasury>) -> Result<()> {
+        let vesting_account_key = ctx.accounts.vesting_account.key();
+        let mint_key = ctx.accounts.mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                SEED_NAMESPACE,
+                b"vesting_treasury",
+                vesting_account_key.as_ref(),
+                mint_key.as_ref(),
+                &[ctx.bumps.treasury_token_account],
+            ],
+        ];
+        let close_cpi_accounts = token_interface::CloseAccount {
+            account: ctx.accounts.treasury_token_account.to_account_info(),
+            destination: ctx.accounts.treasury_admin.to_account_info(),
+            authority: ctx.accounts.treasury_token_account.to_account_info(),
+        };
+        let cpi_context = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            close_cpi_accounts
+        ).with_signer(signer_seeds);
+        token_interface::close_account(cpi_context)?;
+
+        // An empty treasury has nothing reserved, otherwise it would have been over-committed.
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        vesting_account.treasuries.retain(|treasury| treasury.mint != mint_key);
+
+        emit!(TreasuryClosed {
+            vesting_account: vesting_account_key,
+            mint: mint_key,
+            treasury_token_account: ctx.accounts.treasury_token_account.key(),
+        });
+
+        Ok(())
+    }
+
+    // Sets the Pyth price update account claims of `mint` read their price snapshot from, None stops recording prices.
+    // Once set, claims need a fresh update from it or fall back to the vesting account's dependency_failure_policy.
+    pub fn set_price_feed(ctx: Context<SetPriceFeed>, mint: Pubkey, price_feed: Option<Pubkey>) -> Result<()> {
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        let treasury = vesting_account.treasuries
+            .iter_mut()
+            .find(|treasury| treasury.mint == mint)
+            .ok_or(ErrorCode::UnknownTreasury)?;
+        treasury.price_feed = price_feed;
+
+        emit!(PriceFeedSet {
+            vesting_account: vesting_account.key(),
+            mint,
+            price_feed,
+        });
+
+        Ok(())
+    }
+
+    // A grant can only be closed once it has been fully claimed, the rent goes back to the grant admin.
+    pub fn close_employee_account(ctx: Context<CloseEmployeeAccount>) -> Result<()> {
+        let employee_account = &mut ctx.accounts.employee_account;
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        // A pool grant only counts as fully claimed once it claimed its share of every deposit so far.
+        refresh_pool_grant(employee_account, vesting_account, ctx.accounts.treasury_token_account.as_ref())?;
+        transitions::guard(employee_account, vesting_account, GrantAction::Close)?;
+        if employee_account.share_bps > 0 {
+            // The share goes back to the pool, but only for what the pool gains from here on, see pool_released_size.
+            vesting_account.pool_share_bps = vesting_account.pool_share_bps.saturating_sub(employee_account.share_bps);
+            vesting_account.pool_released_size = current_pool_size(
+                vesting_account,
+                ctx.accounts.treasury_token_account.as_ref()
+            )?;
+        }
+        vesting_account.active_grant_count = vesting_account.active_grant_count.saturating_sub(1);
+
+        emit!(EmployeeAccountClosed {
+            employee_account: ctx.accounts.employee_account.key(),
+            vesting_account: vesting_account.key(),
+            beneficiary: ctx.accounts.employee_account.beneficiary,
+        });
+
+        Ok(())
+    }
+
+    // A company can be closed once all of its grants and additional treasuries are closed and its primary treasury is empty.
+    // This closes the primary treasury token account too, so the rent of both accounts goes back to the owner.
+    pub fn close_vesting_account(ctx: Context<CloseVestingAccount>) -> Result<()> {
+        let close_cpi_accounts = token_interface::CloseAccount {
+            account: ctx.accounts.treasury_token_account.to_account_info(),
+            destination: ctx.accounts.owner.to_account_info(),
+            authority: ctx.accounts.treasury_token_account.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+
+        // The treasury is its own authority, so it has to sign the close with its seeds, just like in claim_tokens.
+        let vesting_account_key = ctx.accounts.vesting_account.key();
+        let treasury_bump = [ctx.accounts.vesting_account.treasury_bump];
+        let seeds = treasury_signer_seeds(
+            &ctx.accounts.vesting_account,
+            &vesting_account_key,
+            &ctx.accounts.vesting_account.mint,
+            &treasury_bump
+        );
+        let signer_seeds: &[&[&[u8]]] = &[&seeds];
+        let cpi_context = CpiContext::new(cpi_program, close_cpi_accounts).with_signer(
+            signer_seeds
+        );
+        token_interface::close_account(cpi_context)?;
+        close_company_name_record(
+            &ctx.accounts.company_name_record,
+            vesting_account_key,
+            &ctx.accounts.owner
+        )?;
+
+        emit!(VestingAccountClosed {
+            vesting_account: ctx.accounts.vesting_account.key(),
+            owner: ctx.accounts.owner.key(),
+            refunded_deposit_lamports: ctx.accounts.vesting_account.deposit_lamports,
+        });
+
+        Ok(())
+    }
+
+    // The SOL counterpart of close_vesting_account. Whatever is left in the SOL treasury, unreserved deposits and the
+    // rent-exempt minimum alike, goes back to the owner along with the vesting account's rent and deposit.
+    pub fn close_sol_vesting(ctx: Context<CloseSolVestingAccount>) -> Result<()> {
+        let vesting_account_key = ctx.accounts.vesting_account.key();
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[
+                SEED_NAMESPACE,
+                b"sol_treasury",
+                vesting_account_key.as_ref(),
+                &[ctx.accounts.vesting_account.treasury_bump],
+            ],
+        ];
+        let drained_lamports = ctx.accounts.sol_treasury.lamports();
+        if drained_lamports > 0 {
+            let transfer_cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.sol_treasury.to_account_info(),
+                to: ctx.accounts.owner.to_account_info(),
+            };
+            let cpi_context = CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                transfer_cpi_accounts,
+                signer_seeds
+            );
+            system_program::transfer(cpi_context, drained_lamports)?;
+        }
+        close_company_name_record(
+            &ctx.accounts.company_name_record,
+            vesting_account_key,
+            &ctx.accounts.owner
+        )?;
+
+        emit!(VestingAccountClosed {
+            vesting_account: vesting_account_key,
+            owner: ctx.accounts.owner.key(),
+            refunded_deposit_lamports: ctx.accounts.vesting_account.deposit_lamports,
+        });
+
+        Ok(())
+    }
+
+    // The program config can only be initialized once, and only by the program's upgrade authority.
+    // This keeps anyone else from front-running the deployment and naming themselves protocol admin.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, admin: Pubkey) -> Result<()> {
+        *ctx.accounts.config = Config {
+            admin,
+            template_count: 0,
+            permissioned_creation: false,
+            creation_deposit_lamports: 0,
+            enabled_features: 0,
+            fee_recipient: admin,
+            claim_fee_bps: 0,
+            bump: ctx.bumps.config,
+        };
+
+        emit!(ConfigInitialized { admin });
+
+        Ok(())
+    }
+
+    // Hosted deployments can restrict company creation to allowlisted creators to keep spam companies out.
+    pub fn set_permissioned_creation(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.config.permissioned_creation = enabled;
+
+        emit!(PermissionedCreationSet { enabled });
+
+        Ok(())
+    }
+
+    // Risky subsystems are off until the deployment's admin turns them on, so conservative deployments
+    // can run with a minimal attack surface.
+    pub fn set_feature_enabled(ctx: Context<UpdateConfig>, feature: Feature, enabled: bool) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        if enabled {
+            config.enabled_features |= feature.mask();
+        } else {
+            config.enabled_features &= !feature.mask();
+        }
+
+        emit!(FeatureToggled { feature, enabled });
+
+        Ok(())
+    }
+
+    // Only applies to companies created after the change, existing deposits keep their original amount.
+    pub fn set_creation_deposit(ctx: Context<UpdateConfig>, lamports: u64) -> Result<()> {
+        ctx.accounts.config.creation_deposit_lamports = lamports;
+
+        emit!(CreationDepositSet { lamports });
+
+        Ok(())
+    }
+
+    // Hosted deployments can charge a share of every claim. The fee only applies to claims made after the change.
+    pub fn set_claim_fee(ctx: Context<UpdateConfig>, fee_recipient: Pubkey, claim_fee_bps: u16) -> Result<()> {
+        ctx.accounts.config.set_claim_fee(fee_recipient, claim_fee_bps)?;
+
+        emit!(ClaimFeeSet { fee_recipient, claim_fee_bps });
+
+        Ok(())
+    }
+
+    pub fn add_allowed_creator(ctx: Context<AddAllowedCreator>, creator: Pubkey) -> Result<()> {
+        *ctx.accounts.creator_allowlist_entry = CreatorAllowlistEntry {
+            creator,
+            bump: ctx.bumps.creator_allowlist_entry,
+        };
+
+        emit!(CreatorAllowlistUpdated { creator, allowed: true });
+
+        Ok(())
+    }
+
+    // Removing a creator closes its allowlist entry, companies it already created are not affected.
+    pub fn remove_allowed_creator(_ctx: Context<RemoveAllowedCreator>, creator: Pubkey) -> Result<()> {
+        emit!(CreatorAllowlistUpdated { creator, allowed: false });
+
+        Ok(())
+    }
+
+    pub fn create_schedule_template(
+        ctx: Context<CreateScheduleTemplate>,
+        name: String,
+        cliff_offset_seconds: i64,
+        duration_seconds: i64,
+        schedule_kind: ScheduleKind,
+        vesting_interval_seconds: i64,
+        cliff_unlock_bps: u16
+    ) -> Result<()> {
+        if duration_seconds <= 0 || cliff_offset_seconds < 0 || cliff_offset_seconds > duration_seconds {
+            return Err(ErrorCode::InvalidTemplate.into());
+        }
+        validate_schedule_params(
+            schedule_kind,
+            duration_seconds,
+            vesting_interval_seconds,
+            cliff_unlock_bps
+        )?;
+
+        let config = &mut ctx.accounts.config;
+        *ctx.accounts.schedule_template = ScheduleTemplate {
+            id: config.template_count,
+            name,
+            cliff_offset_seconds,
+            duration_seconds,
+            schedule_kind,
+            vesting_interval_seconds,
+            cliff_unlock_bps,
+            is_active: true,
+            bump: ctx.bumps.schedule_template,
+        };
+        // Template ids are sequential, so clients can list the registry by walking 0..template_count.
+        config.template_count += 1;
+
+        let schedule_template = &ctx.accounts.schedule_template;
+        emit!(ScheduleTemplateCreated {
+            schedule_template: schedule_template.key(),
+            id: schedule_template.id,
+            name: schedule_template.name.clone(),
+        });
+
+        Ok(())
+    }
+
+    // Retired templates stay readable so existing grants can still point at them, but can't be used for new grants.
+    pub fn set_schedule_template_active(
+        ctx: Context<SetScheduleTemplateActive>,
+        _template_id: u64,
+        is_active: bool
+    ) -> Result<()> {
+        let schedule_template = &mut ctx.accounts.schedule_template;
+        schedule_template.is_active = is_active;
+
+        emit!(ScheduleTemplateStatusChanged {
+            schedule_template: schedule_template.key(),
+            id: schedule_template.id,
+            is_active,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_employee_vesting_from_template(
+        ctx: Context<CreateEmployeeAccountFromTemplate>,
+        _template_id: u64,
+        start_time: i64,
+        total_amount: u64
+    ) -> Result<()> {
+        let template = &ctx.accounts.schedule_template;
+        if !template.is_active {
+            return Err(ErrorCode::TemplateInactive.into());
+        }
+
+        // Templates store offsets relative to the grant start, so we turn them into absolute timestamps here.
+        let cliff_time = start_time
+            .checked_add(template.cliff_offset_seconds)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+        let end_time = start_time
+            .checked_add(template.duration_seconds)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+        validate_grant(start_time, end_time, cliff_time, total_amount)?;
+
+        *ctx.accounts.employee_account = EmployeeAccount::new(
+            ctx.accounts.beneficiary.key(),
+            &ctx.accounts.vesting_account,
+            ctx.accounts.mint.key(),
+            ctx.bumps.employee_account,
+            Schedule {
+                kind: template.schedule_kind,
+                start_time,
+                end_time,
+                cliff_time,
+                total_amount,
+                vesting_interval_seconds: template.vesting_interval_seconds,
+                cliff_unlock_bps: template.cliff_unlock_bps,
+                milestones: &[],
+            }
+        )?;
+
+        ctx.accounts.vesting_account.reserve(
+            ctx.accounts.mint.key(),
+            total_amount,
+            ctx.accounts.treasury_token_account.amount
+        )?;
+        register_grant(
+            &mut ctx.accounts.vesting_account,
+            &mut ctx.accounts.grant_entry,
+            ctx.accounts.employee_account.key(),
+            ctx.accounts.employee_account.registry_index,
+            ctx.bumps.grant_entry
+        )?;
+        ctx.accounts.vesting_account.active_grant_count += 1;
+        emit_employee_vesting_created(&ctx.accounts.employee_account, Some(template.id));
+
+        Ok(())
+    }
+
+    // Brings a vesting account created with an older layout up to VESTING_ACCOUNT_VERSION. Anyone can call it, the
+    // payer only covers the rent of the bytes the account grows by. Accounts with the original layout go through
+    // migrate_legacy_vesting_account instead.
+    pub fn migrate_vesting_account(ctx: Context<MigrateVestingAccount>) -> Result<()> {
+        let config = load_config(&ctx.accounts.config)?;
+        require_feature(config.as_ref(), Feature::AccountMigration)?;
+        let info = ctx.accounts.vesting_account.to_account_info();
+        if info.data_len() == 8 + LegacyVestingAccount::INIT_SPACE {
+            return Err(ErrorCode::LegacyAccountLayout.into());
+        }
+        grow_account(&info, VESTING_ACCOUNT_SPACE, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        let mut vesting_account = VestingAccount::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        let from_version = vesting_account.version;
+        if from_version >= VESTING_ACCOUNT_VERSION {
+            return Err(ErrorCode::AccountAlreadyMigrated.into());
+        }
+
+        // Version 0 accounts may predate canonical_name. Names that don't validate stay without one, like
+        // they do until their owner calls rename_company.
+        if vesting_account.canonical_name.is_empty() {
+            vesting_account.canonical_name = canonical_company_name(&vesting_account.company_name).unwrap_or_default();
+        }
+        vesting_account.version = VESTING_ACCOUNT_VERSION;
+        vesting_account.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        emit!(AccountMigrated { account: info.key(), from_version, to_version: VESTING_ACCOUNT_VERSION });
+
+        Ok(())
+    }
+
+    // Same as migrate_vesting_account, for grants.
+    pub fn migrate_employee_account(ctx: Context<MigrateEmployeeAccount>) -> Result<()> {
+        let config = load_config(&ctx.accounts.config)?;
+        require_feature(config.as_ref(), Feature::AccountMigration)?;
+        let info = ctx.accounts.employee_account.to_account_info();
+        if info.data_len() == 8 + LegacyEmployeeAccount::INIT_SPACE {
+            return Err(ErrorCode::LegacyAccountLayout.into());
+        }
+        grow_account(&info, EMPLOYEE_ACCOUNT_SPACE, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        let mut employee_account = EmployeeAccount::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        let from_version = employee_account.version;
+        if from_version >= EMPLOYEE_ACCOUNT_VERSION {
+            return Err(ErrorCode::AccountAlreadyMigrated.into());
+        }
+
+        // Version 0 has the same fields, the zeroed bytes grow_account added read as their defaults.
+        employee_account.version = EMPLOYEE_ACCOUNT_VERSION;
+        employee_account.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        emit!(AccountMigrated { account: info.key(), from_version, to_version: EMPLOYEE_ACCOUNT_VERSION });
+
+        Ok(())
+    }
+
+    // Rewrites a vesting account created by the original program, see LegacyVestingAccount. It keeps its
+    // `[company_name]` address and treasury, which is why it is marked with legacy_seeds. It also gets the
+    // ClaimStats the original program didn't have, which close_vesting_account closes along with it.
+    pub fn migrate_legacy_vesting_account(ctx: Context<MigrateLegacyVestingAccount>) -> Result<()> {
+        let config = load_config(&ctx.accounts.config)?;
+        require_feature(config.as_ref(), Feature::AccountMigration)?;
+        let info = ctx.accounts.vesting_account.to_account_info();
+        let legacy: LegacyVestingAccount = decode_legacy_account(&info.try_borrow_data()?, &VestingAccount::DISCRIMINATOR)?;
+        grow_account(&info, VESTING_ACCOUNT_SPACE, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        legacy.into_current().try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        *ctx.accounts.claim_stats = ClaimStats {
+            vesting_account: info.key(),
+            total_claimed: 0,
+            claim_count: 0,
+            last_claim_day: 0,
+            daily_claimed: [0; CLAIM_STATS_WINDOW_DAYS],
+            bump: ctx.bumps.claim_stats,
+        };
+
+        emit!(AccountMigrated { account: info.key(), from_version: 0, to_version: VESTING_ACCOUNT_VERSION });
+
+        Ok(())
+    }
+
+    // Rewrites a grant created by the original program, see LegacyEmployeeAccount. Its vesting account has to be
+    // migrated first, the grant takes its mint from there. It is added to the grant registry like a new grant.
+    pub fn migrate_legacy_employee_account(ctx: Context<MigrateLegacyEmployeeAccount>) -> Result<()> {
+        let config = load_config(&ctx.accounts.config)?;
+        require_feature(config.as_ref(), Feature::AccountMigration)?;
+        let info = ctx.accounts.employee_account.to_account_info();
+        let legacy: LegacyEmployeeAccount = decode_legacy_account(&info.try_borrow_data()?, &EmployeeAccount::DISCRIMINATOR)?;
+        let vesting_account = &mut ctx.accounts.vesting_account;
+        if legacy.vesting_account != vesting_account.key() || !vesting_account.legacy_seeds {
+            return Err(ErrorCode::NotLegacyAccount.into());
+        }
+        let employee_account = legacy.into_current(vesting_account.mint, vesting_account.grant_count)?;
+        grow_account(&info, EMPLOYEE_ACCOUNT_SPACE, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        employee_account.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        register_grant(
+            vesting_account,
+            &mut ctx.accounts.grant_entry,
+            info.key(),
+            employee_account.registry_index,
+            ctx.bumps.grant_entry
+        )?;
+        vesting_account.active_grant_count += 1;
+
+        emit!(AccountMigrated { account: info.key(), from_version: 0, to_version: EMPLOYEE_ACCOUNT_VERSION });
+
+        Ok(())
+    }
+
+    // Claims a grant migrated by migrate_legacy_employee_account. Its vesting account kept the original
+    // `[company_name]` address and treasury, so the transfer is signed with the treasury's original seeds. Claims
+    // work the way the original program's did: no protocol fee, no price snapshot and no transfer hook accounts.
+    pub fn claim_legacy_tokens(ctx: Context<ClaimLegacyTokens>) -> Result<()> {
+        let now = current_time(ctx.accounts.time_override.as_deref())?;
+        let vesting_account = &ctx.accounts.vesting_account;
+        let employee_account = &mut ctx.accounts.employee_account;
+        transitions::guard(employee_account, vesting_account, GrantAction::Claim)?;
+        if now < employee_account.cliff_time {
+            return Err(ErrorCode::ClaimNotAvailableYet.into());
+        }
+        let claimable_amount = employee_account.claimable_amount(now)?;
+        if claimable_amount == 0 {
+            return Err(ErrorCode::NothingToClaim.into());
+        }
+
+        let transfer_cpi_accounts = token_interface::TransferChecked {
+            from: ctx.accounts.treasury_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.employee_token_account.to_account_info(),
+            authority: ctx.accounts.treasury_token_account.to_account_info(),
+        };
+        let vesting_account_key = vesting_account.key();
+        let treasury_bump = [vesting_account.treasury_bump];
+        let seeds = treasury_signer_seeds(vesting_account, &vesting_account_key, &vesting_account.mint, &treasury_bump);
+        let signer_seeds: &[&[&[u8]]] = &[&seeds];
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            transfer_cpi_accounts,
+            signer_seeds
+        );
+        token_interface::transfer_checked(cpi_context, claimable_amount, ctx.accounts.mint.decimals)?;
+        employee_account.total_withdrawn = employee_account.total_withdrawn
+            .checked_add(claimable_amount)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+
+        emit!(TokensClaimed {
+            employee_account: employee_account.key(),
+            beneficiary: employee_account.beneficiary,
+            mint: employee_account.mint,
+            amount: claimable_amount,
+            transfer_fee: calculate_transfer_fee(&ctx.accounts.mint.to_account_info(), claimable_amount)?,
+            protocol_fee: 0,
+            remaining: employee_account.total_amount.saturating_sub(employee_account.total_withdrawn),
+            price: None,
+        });
+
+        Ok(())
+    }
+
+    // Pins the time claims vest at, None goes back to the Clock sysvar. Only works in test-clock builds, where
+    // anyone can call it, so such a build must never be deployed outside a local validator. #[program] can't leave
+    // out an instruction by cfg, so other builds keep it and fail with TestClockDisabled.
+    pub fn set_time_override(ctx: Context<SetTimeOverride>, unix_timestamp: Option<i64>) -> Result<()> {
+        if !cfg!(feature = "test-clock") {
+            return Err(ErrorCode::TestClockDisabled.into());
+        }
+        let time_override = &mut ctx.accounts.time_override;
+        time_override.unix_timestamp = unix_timestamp;
+        time_override.bump = ctx.bumps.time_override;
+
+        emit!(TimeOverrideSet { unix_timestamp });
+
+        Ok(())
+    }
+}
+
+// The time claims vest at. test-clock builds take it from the TimeOverride account when one is passed and set, so
+// tests on a local validator can move through a schedule without waiting for it. Every other build uses the Clock
+// sysvar and ignores the account.
+fn current_time(time_override: Option<&AccountInfo>) -> Result<i64> {
+    if !cfg!(feature = "test-clock") {
+        return Ok(Clock::get()?.unix_timestamp);
+    }
+    if let Some(time_override) = time_override {
+        let (address, _) = Pubkey::find_program_address(&[SEED_NAMESPACE, b"time_override"], &crate::ID);
+        if time_override.key() != address {
+            return Err(anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+        }
+        if is_initialized(time_override) {
+            let time_override = TimeOverride::try_deserialize(&mut &time_override.try_borrow_data()?[..])?;
+            if let Some(unix_timestamp) = time_override.unix_timestamp {
+                return Ok(unix_timestamp);
+            }
+        }
+    }
+    Ok(Clock::get()?.unix_timestamp)
+}
+
+// Shared by create_vesting_account and create_sol_vesting, which only differ in the mint and treasury: checks the
+// name and the creator, takes the creation deposit, numbers the vesting account in the creator's registry and
+// writes it along with its ClaimStats.
+#[allow(clippy::too_many_arguments)]
+fn init_vesting_account<'info>(
+    signer: &Signer<'info>,
+    creator_registry: &mut Account<'info, CreatorRegistry>,
+    vesting_account: &mut Account<'info, VestingAccount>,
+    claim_stats: &mut Account<'info, ClaimStats>,
+    company_name_record: &mut Account<'info, CompanyNameRecord>,
+    config: &AccountInfo<'info>,
+    creator_allowlist_entry: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    mint: Pubkey,
+    treasury: Pubkey,
+    company_name: String,
+    dependency_failure_policy: DependencyFailurePolicy,
+    bumps: VestingAccountBumps
+) -> Result<()> {
+    let canonical_name = canonical_company_name(&company_name)?;
+    let config = load_config(config)?;
+    check_creator_allowed(config.as_ref(), creator_allowlist_entry)?;
+
+    // The deposit is held in the vesting account itself, so it is refunded along with the rent
+    // when the company is closed through close_vesting_account.
+    let deposit_lamports = config.map_or(0, |config| config.creation_deposit_lamports);
+    if deposit_lamports > 0 {
+        let transfer_cpi_accounts = system_program::Transfer {
+            from: signer.to_account_info(),
+            to: vesting_account.to_account_info(),
+        };
+        let cpi_context = CpiContext::new(
+            system_program.to_account_info(),
+            transfer_cpi_accounts
+        );
+        system_program::transfer(cpi_context, deposit_lamports)?;
+    }
+
+    // Vesting accounts are numbered per creator, so a company can run several pools (e.g. one per mint)
+    // and nobody else can squat its company name.
+    let index = creator_registry.vesting_account_count;
+    creator_registry.creator = signer.key();
+    creator_registry.vesting_account_count += 1;
+    creator_registry.bump = bumps.creator_registry;
+
+    // The * operator is used to dereference an account, in this case its the account that the vesting_account variable points to.
+    // To modify an account, you need to dereference the account reference. 
+    // This dereference operator tells Rust that you want to work with the actual account data, not just the reference.
+    // So now you can update values saved to the account state of the vesting_account.
+    **vesting_account = VestingAccount {
+        owner: signer.key(),
+        creator: signer.key(),
+        grant_admin: signer.key(),
+        revoke_admin: signer.key(),
+        treasury_admin: signer.key(),
+        index,
+        mint,
+        treasury_token_account: treasury,
+        company_name,
+        treasury_bump: bumps.treasury,
+        active_grant_count: 0,
+        grant_count: 0,
+        treasuries: vec![TreasuryReserve { mint, reserved_amount: 0, price_feed: None }],
+        deposit_lamports,
+        pending_owner: None,
+        owner_can_reassign_beneficiary: false,
+        is_paused: false,
+        clawback_delay_seconds: 0,
+        dependency_failure_policy,
+        bump: bumps.vesting_account,
+        canonical_name,
+        pool_share_bps: 0,
+        pool_withdrawn: 0,
+        require_allowed_destinations: false,
+        version: VESTING_ACCOUNT_VERSION,
+        legacy_seeds: false,
+        pool_released_size: 0,
+        reserved: [0; VESTING_ACCOUNT_RESERVED_BYTES],
+    };
+
+    **claim_stats = ClaimStats {
+        vesting_account: vesting_account.key(),
+        total_claimed: 0,
+        claim_count: 0,
+        last_claim_day: 0,
+        daily_claimed: [0; CLAIM_STATS_WINDOW_DAYS],
+        bump: bumps.claim_stats,
+    };
+
+    **company_name_record = CompanyNameRecord {
+        vesting_account: vesting_account.key(),
+        bump: bumps.company_name_record,
+    };
+
+    emit!(VestingAccountCreated {
+        vesting_account: vesting_account.key(),
+        owner: vesting_account.owner,
+        mint: vesting_account.mint,
+        index,
+        company_name: vesting_account.company_name.clone(),
+        deposit_lamports,
+        dependency_failure_policy,
+    });
+
+    Ok(())
+}
+
+// The bumps init_vesting_account stores, from the caller's Context.
+struct VestingAccountBumps {
+    creator_registry: u8,
+    vesting_account: u8,
+    treasury: u8,
+    claim_stats: u8,
+    company_name_record: u8,
+}
+
+// Pool size of a vesting account, read from its primary treasury. Instructions that handle pool grants take the
+// treasury as an optional account, it is only required for pool grants.
+fn current_pool_size(
+    vesting_account: &VestingAccount,
+    treasury_token_account: Option<&InterfaceAccount<TokenAccount>>
+) -> Result<u64> {
+    let treasury_token_account = treasury_token_account
+        .filter(|treasury_token_account| treasury_token_account.key() == vesting_account.treasury_token_account)
+        .ok_or(ErrorCode::PoolTreasuryMissing)?;
+    vesting_account.pool_size(treasury_token_account.amount)
+}
+
+// Brings a pool grant's total_amount up to date before its status or amounts are read, see refresh_pool_amount.
+fn refresh_pool_grant(
+    employee_account: &mut EmployeeAccount,
+    vesting_account: &VestingAccount,
+    treasury_token_account: Option<&InterfaceAccount<TokenAccount>>
+) -> Result<()> {
+    if employee_account.share_bps > 0 {
+        employee_account.refresh_pool_amount(current_pool_size(vesting_account, treasury_token_account)?)?;
+    }
+    Ok(())
+}
+
+// Self-deployments never need to initialize the config, in which case None is returned.
+fn load_config(config: &AccountInfo) -> Result<Option<Config>> {
+    if !is_initialized(config) {
+        return Ok(None);
+    }
+    Ok(Some(Config::try_deserialize(&mut &config.try_borrow_data()?[..])?))
+}
+
+// Shared by claim_tokens and claim_tokens_as_delegate, whose account constraints already tie every account to the grant.
+#[allow(clippy::too_many_arguments)]
+fn claim_vested_tokens<'info>(
+    vesting_account: &mut Account<'info, VestingAccount>,
+    employee_account: &mut Account<'info, EmployeeAccount>,
+    claim_stats: &mut Account<'info, ClaimStats>,
+    mint: &InterfaceAccount<'info, Mint>,
+    treasury_token_account: &InterfaceAccount<'info, TokenAccount>,
+    treasury_bump: u8,
+    destination: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    price_update: Option<&AccountInfo<'info>>,
+    config: &AccountInfo<'info>,
+    fee_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    additional_accounts: &[AccountInfo<'info>],
+    requested_amount: Option<u64>,
+    now: i64
+) -> Result<()> {
+    employee_account.refresh_pool_amount(vesting_account.pool_size(treasury_token_account.amount)?)?;
+    // Pausing only blocks claims, vesting keeps accruing so employees can claim everything once resumed.
+    transitions::guard(employee_account, vesting_account, GrantAction::Claim)?;
+
+    // Check if the current time is before the cliff time
+    if now < employee_account.cliff_time {
+        return Err(ErrorCode::ClaimNotAvailableYet.into());
+    }
+
+    let (price, vested_until) = price_dependency(vesting_account, employee_account.mint, price_update, now)?;
+
+    // Calculate the amount that has vested according to the grant's schedule kind and not been withdrawn yet
+    let claimable_amount = employee_account.claimable_amount(vested_until)?;
+    
+    // Check if there is anything left to claim
+    if claimable_amount == 0 {
+        return Err(ErrorCode::NothingToClaim.into());
+    }
+    let amount = claim_amount(claimable_amount, requested_amount)?;
+
+    if !is_transfer_hook_available(&mint.to_account_info(), additional_accounts)? {
+        return Err(ErrorCode::DependencyUnavailable.into());
+    }
+
+    // The protocol fee is taken out of the claim, the beneficiary gets the rest.
+    let config = load_config(config)?;
+    let protocol_fee = claim_fee(config.as_ref(), amount)?;
+    let beneficiary_amount = amount - protocol_fee;
+
+    // With the TransferFee extension the fee is withheld from what the employee receives, the full
+    // amount still leaves the treasury and is what counts towards total_withdrawn.
+    let transfer_fee = calculate_transfer_fee(
+        &mint.to_account_info(),
+        beneficiary_amount
+    )?;
+
+    if protocol_fee > 0 {
+        let fee_token_account = fee_token_account.ok_or(ErrorCode::FeeAccountMissing)?;
+        check_fee_destination(config.as_ref(), fee_token_account.owner, fee_token_account.mint, mint.key())?;
+        transfer_from_treasury(
+            token_program.key,
+            vesting_account,
+            treasury_token_account.to_account_info(),
+            treasury_bump,
+            mint.to_account_info(),
+            fee_token_account.to_account_info(),
+            additional_accounts,
+            protocol_fee,
+            mint.decimals
+        )?;
+    }
+
+    // Now we can transfer the tokens to the employee, this invloves a CPI call, which is a cross-program invocation.
+    transfer_from_treasury(
+        token_program.key,
+        vesting_account,
+        treasury_token_account.to_account_info(),
+        treasury_bump,
+        mint.to_account_info(),
+        destination,
+        additional_accounts,
+        beneficiary_amount,
+        mint.decimals
+    )?;
+    // update account state to reflect the amount that has been withdrawn
+    employee_account.total_withdrawn = employee_account.total_withdrawn
+        .checked_add(amount)
+        .ok_or(ErrorCode::CalculationOverflow)?;
+    if employee_account.share_bps > 0 {
+        vesting_account.pool_withdrawn = vesting_account.pool_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+    } else {
+        vesting_account.release(employee_account.mint, amount)?;
+    }
+    claim_stats.record_claim(now, amount)?;
+
+    emit!(TokensClaimed {
+        employee_account: employee_account.key(),
+        beneficiary: employee_account.beneficiary,
+        mint: employee_account.mint,
+        amount,
+        transfer_fee,
+        protocol_fee,
+        remaining: employee_account.total_amount.saturating_sub(employee_account.total_withdrawn),
+        price,
+    });
+    Ok(())
+}
+
+// Returns the token account a claim pays into: the grant's registered claim destination when there is one, the
+// beneficiary's associated token account otherwise. A closed associated token account is re-created at the payer's
+// expense. One that changed owner or was frozen fails here with an error naming the problem, instead of somewhere
+// inside the token program.
+#[allow(clippy::too_many_arguments)]
+fn claim_destination_account<'info>(
+    employee_account: &EmployeeAccount,
+    payer: AccountInfo<'info>,
+    beneficiary: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    associated_token_account: &UncheckedAccount<'info>,
+    claim_destination: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Interface<'info, TokenInterface>,
+    associated_token_program: &Program<'info, AssociatedToken>,
+    system_program: &Program<'info, System>
+) -> Result<AccountInfo<'info>> {
+    if let Some(registered) = employee_account.claim_destination {
+        let destination = claim_destination.ok_or(ErrorCode::ClaimDestinationMissing)?;
+        if destination.key() != registered {
+            return Err(ErrorCode::InvalidClaimDestination.into());
+        }
+        check_claim_destination(destination, mint.key(), None)?;
+        return Ok(destination.to_account_info());
+    }
+
+    let beneficiary_key = beneficiary.key();
+    if associated_token_account.data_is_empty() {
+        let create_cpi_accounts = associated_token::Create {
+            payer,
+            associated_token: associated_token_account.to_account_info(),
+            authority: beneficiary,
+            mint: mint.to_account_info(),
+            system_program: system_program.to_account_info(),
+            token_program: token_program.to_account_info(),
+        };
+        associated_token::create(
+            CpiContext::new(associated_token_program.to_account_info(), create_cpi_accounts)
+        )?;
+    }
+    let destination = TokenAccount::try_deserialize(&mut &associated_token_account.try_borrow_data()?[..])?;
+    check_claim_destination(&destination, mint.key(), Some(beneficiary_key))?;
+
+    Ok(associated_token_account.to_account_info())
+}
+
+// Fails with a precise error when claims can't be paid into `destination`: it holds another mint, it has been
+// frozen, or, when `owner` is given, it no longer belongs to that owner.
+fn check_claim_destination(destination: &TokenAccount, mint: Pubkey, owner: Option<Pubkey>) -> Result<()> {
+    if destination.mint != mint {
+        return Err(ErrorCode::ClaimDestinationWrongMint.into());
+    }
+    if owner.is_some_and(|owner| destination.owner != owner) {
+        return Err(ErrorCode::TokenAccountReassigned.into());
+    }
+    if destination.is_frozen() {
+        return Err(ErrorCode::ClaimDestinationFrozen.into());
+    }
+
+    Ok(())
+}
+
+// While the vesting account requires allowed destinations, fails unless the wallet owning `destination` is listed in
+// the beneficiary's AllowedDestinations or accepted by its allowlist program. The program is called with
+// `allowlist_record` as its only account and VERIFY_DESTINATION_DISCRIMINATOR followed by the wallet and the mint as
+// data, and rejects the wallet by failing, which fails the claim.
+fn check_destination_allowed<'info>(
+    vesting_account: &VestingAccount,
+    allowed_destinations: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    mint: Pubkey,
+    allowlist_program: Option<&AccountInfo<'info>>,
+    allowlist_record: Option<&AccountInfo<'info>>
+) -> Result<()> {
+    if !vesting_account.require_allowed_destinations {
+        return Ok(());
+    }
+    // The address is already derived from the beneficiary, so an existing account is theirs.
+    let allowed_destinations = if is_initialized(allowed_destinations) {
+        Some(AllowedDestinations::try_deserialize(&mut &allowed_destinations.try_borrow_data()?[..])?)
+    } else {
+        None
+    };
+    let wallet = TokenAccount::try_deserialize(&mut &destination.try_borrow_data()?[..])?.owner;
+    let Some(program_id) = allowlist_program_to_ask(allowed_destinations.as_ref(), wallet)? else {
+        return Ok(());
+    };
+    let (Some(allowlist_program), Some(allowlist_record)) = (allowlist_program, allowlist_record) else {
+        return Err(ErrorCode::AllowlistProgramMissing.into());
+    };
+    if allowlist_program.key() != program_id {
+        return Err(ErrorCode::AllowlistProgramMissing.into());
+    }
+
+    let mut data = VERIFY_DESTINATION_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(wallet.as_ref());
+    data.extend_from_slice(mint.as_ref());
+    // No signer seeds and a read-only account, the allowlist program can only answer.
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(allowlist_record.key(), false)],
+        data,
+    };
+    invoke(&instruction, &[allowlist_record.clone(), allowlist_program.clone()])?;
+
+    Ok(())
+}
+
+// The part of check_destination_allowed that needs no CPI: None when `wallet` is listed, the allowlist program to
+// ask about it otherwise. Fails when the beneficiary has no AllowedDestinations or nothing to ask.
+fn allowlist_program_to_ask(allowed_destinations: Option<&AllowedDestinations>, wallet: Pubkey) -> Result<Option<Pubkey>> {
+    let allowed_destinations = allowed_destinations.ok_or(ErrorCode::DestinationNotAllowed)?;
+    if allowed_destinations.wallets.contains(&wallet) {
+        return Ok(None);
+    }
+    allowed_destinations.allowlist_program.map(Some).ok_or_else(|| ErrorCode::DestinationNotAllowed.into())
+}
+
+// What a claim of `requested_amount` takes out of `claimable_amount`, everything when None.
+fn claim_amount(claimable_amount: u64, requested_amount: Option<u64>) -> Result<u64> {
+    match requested_amount {
+        None => Ok(claimable_amount),
+        Some(0) => Err(ErrorCode::InvalidAmount.into()),
+        Some(amount) if amount > claimable_amount => Err(ErrorCode::ClaimAmountExceedsClaimable.into()),
+        Some(amount) => Ok(amount),
+    }
+}
+
+// What claim_all pays out for a grant at `now`, None for grants it skips instead of failing the whole batch: those
+// claim_tokens would reject, pool grants, whose amount claim_tokens refreshes from the treasury balance first, and
+// grants of vesting accounts with the destination gate on, whose AllowedDestinations groups have no room for.
+fn claim_all_amount(employee_account: &EmployeeAccount, vesting_account: &VestingAccount, now: i64) -> Result<Option<u64>> {
+    if transitions::guard(employee_account, vesting_account, GrantAction::Claim).is_err()
+        || employee_account.share_bps > 0
+        || vesting_account.require_allowed_destinations
+        || now < employee_account.cliff_time
+    {
+        return Ok(None);
+    }
+    // Without a price update, grants priced by a feed only go through under the TimeBased policy.
+    let Ok((_, vested_until)) = price_dependency(vesting_account, employee_account.mint, None, now) else {
+        return Ok(None);
+    };
+    let claimable_amount = employee_account.claimable_amount(vested_until)?;
+    Ok(Some(claimable_amount).filter(|amount| *amount > 0))
+}
+
+// Applies the vesting account's dependency_failure_policy to the price feed of `mint`'s treasury, the one input
+// claims read from outside the program. Without a configured feed there is nothing to depend on. With one, an
+// update that is missing, can't be read or is older than MAX_PRICE_AGE_SECONDS counts as a failure. Returns the
+// price to record with the claim and the time the claim may vest up to.
+fn price_dependency(
+    vesting_account: &VestingAccount,
+    mint: Pubkey,
+    price_update: Option<&AccountInfo>,
+    now: i64
+) -> Result<(Option<PriceSnapshot>, i64)> {
+    let price_feed = vesting_account.treasuries
+        .iter()
+        .find(|treasury| treasury.mint == mint)
+        .and_then(|treasury| treasury.price_feed);
+    let Some(price_feed) = price_feed else {
+        return Ok((None, now));
+    };
+    let price = match price_update {
+        Some(price_update) if price_update.key() != price_feed => return Err(ErrorCode::InvalidPriceFeed.into()),
+        Some(price_update) => read_price_update(price_update).ok(),
+        None => None,
+    };
+    if let Some(price) = price.filter(|price| now.saturating_sub(price.publish_time) <= MAX_PRICE_AGE_SECONDS) {
+        return Ok((Some(price), now));
+    }
+    match (vesting_account.dependency_failure_policy, price) {
+        // Vesting stops at the last price the feed published, until it recovers.
+        (DependencyFailurePolicy::VestedOnly, Some(stale)) => Ok((None, stale.publish_time.min(now))),
+        (DependencyFailurePolicy::TimeBased, _) => Ok((None, now)),
+        _ => Err(ErrorCode::DependencyUnavailable.into()),
+    }
+}
+
+// Parses a PriceUpdateV2 account of Pyth's receiver program by hand, so the program doesn't need the Pyth SDK.
+// Layout: discriminator, write authority, verification level, then the price message starting with the feed id.
+// Only fully verified updates are accepted.
+fn read_price_update(price_update: &AccountInfo) -> Result<PriceSnapshot> {
+    if price_update.owner != &PYTH_RECEIVER_PROGRAM_ID {
+        return Err(ErrorCode::InvalidPriceFeed.into());
+    }
+    let data = price_update.try_borrow_data()?;
+    const VERIFICATION_LEVEL_OFFSET: usize = 8 + 32;
+    const VERIFICATION_LEVEL_FULL: u8 = 1;
+    if data.get(VERIFICATION_LEVEL_OFFSET) != Some(&VERIFICATION_LEVEL_FULL) {
+        return Err(ErrorCode::InvalidPriceFeed.into());
+    }
+    // price: i64, conf: u64, exponent: i32, publish_time: i64
+    let message = data
+        .get(VERIFICATION_LEVEL_OFFSET + 1 + 32..VERIFICATION_LEVEL_OFFSET + 1 + 32 + 28)
+        .ok_or(ErrorCode::InvalidPriceFeed)?;
+    Ok(PriceSnapshot {
+        price: i64::from_le_bytes(message[0..8].try_into().unwrap()),
+        expo: i32::from_le_bytes(message[16..20].try_into().unwrap()),
+        publish_time: i64::from_le_bytes(message[20..28].try_into().unwrap()),
+    })
+}
+
+// Transfers `amount` out of a vesting account's treasury.
+// When transferring tokens using the SPL Token program, the transfer instruction must be signed by the owner of the tokens or an authorized delegate.
+// In our case, the authority is the treasury_token_account, which is the account that holds the tokens,
+// so we sign with the seeds of this account.
+// invoke_transfer_checked works like token_interface::transfer_checked, but also resolves the extra
+// accounts of a transfer hook from `additional_accounts` and adds them to the instruction.
+#[allow(clippy::too_many_arguments)]
+fn transfer_from_treasury<'info>(
+    token_program: &Pubkey,
+    vesting_account: &Account<'info, VestingAccount>,
+    treasury_token_account: AccountInfo<'info>,
+    treasury_bump: u8,
+    mint: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    additional_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    decimals: u8
+) -> Result<()> {
+    let vesting_account_key = vesting_account.key();
+    let mint_key = mint.key();
+    let treasury_bump = [treasury_bump];
+    let seeds = treasury_signer_seeds(vesting_account, &vesting_account_key, &mint_key, &treasury_bump);
+    let signer_seeds: &[&[&[u8]]] = &[&seeds];
+    spl_token_2022::onchain::invoke_transfer_checked(
+        token_program,
+        treasury_token_account.clone(),
+        mint,
+        destination,
+        treasury_token_account,
+        additional_accounts,
+        amount,
+        decimals,
+        signer_seeds
+    )?;
+
+    Ok(())
+}
+
+// Seeds the treasury of `vesting_account` for `mint` signs with. The primary treasury of a vesting account migrated
+// from the original program kept its `[b"vesting_treasury", company_name]` address, see legacy_seeds.
+fn treasury_signer_seeds<'a>(
+    vesting_account: &'a VestingAccount,
+    vesting_account_key: &'a Pubkey,
+    mint: &'a Pubkey,
+    bump: &'a [u8]
+) -> Vec<&'a [u8]> {
+    if vesting_account.legacy_seeds && *mint == vesting_account.mint {
+        vec![b"vesting_treasury", vesting_account.company_name.as_bytes(), bump]
+    } else {
+        vec![SEED_NAMESPACE, b"vesting_treasury", vesting_account_key.as_ref(), mint.as_ref(), bump]
+    }
+}
+
+// Returns the fee a Token-2022 mint with the TransferFee extension withholds from a transfer of `amount`,
+// and 0 for every other mint.
+fn calculate_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(0);
+    }
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => transfer_fee_config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or_else(|| ErrorCode::CalculationOverflow.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+// Returns false if the mint has a Token-2022 transfer hook whose program isn't among `additional_accounts`
+// or isn't executable anymore, in which case the token program would reject the transfer.
+fn is_transfer_hook_available(mint: &AccountInfo, additional_accounts: &[AccountInfo]) -> Result<bool> {
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(true);
+    }
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    let Some(hook_program_id) = transfer_hook::get_program_id(&mint_state) else {
+        return Ok(true);
+    };
+
+    Ok(additional_accounts
+        .iter()
+        .any(|account| account.key == &hook_program_id && account.executable))
+}
+
+// Lamports of a SOL treasury that can back grants. The treasury has to keep its rent-exempt minimum, the system
+// program rejects a transfer that would leave it below.
+fn sol_treasury_balance(sol_treasury: &AccountInfo) -> Result<u64> {
+    Ok(sol_treasury.lamports().saturating_sub(Rent::get()?.minimum_balance(0)))
+}
+
+// Company names are shown to beneficiaries when they accept and claim grants, so they are limited to ASCII that
+// can't hide lookalikes: letters, digits, a few punctuation marks and single spaces between words. Returns the
+// canonical form, the lowercased name.
+fn canonical_company_name(company_name: &str) -> Result<String> {
+    let is_allowed = |c: char| c.is_ascii_alphanumeric() || " -_.,&'()".contains(c);
+    if company_name.is_empty()
+        || company_name.len() > MAX_COMPANY_NAME_LEN
+        || !company_name.chars().all(is_allowed)
+        || company_name.starts_with(' ')
+        || company_name.ends_with(' ')
+        || company_name.contains("  ")
+    {
+        return Err(ErrorCode::InvalidCompanyName.into());
+    }
+    Ok(company_name.to_ascii_lowercase())
+}
+
+// Seed of the CompanyNameRecord of `company_name`. Names can be longer than a seed, so the seed is the hash of the
+// canonical form.
+fn company_name_seed(company_name: &str) -> [u8; 32] {
+    hash(company_name.to_ascii_lowercase().as_bytes()).to_bytes()
+}
+
+// Closes `record` to `destination` if it is the CompanyNameRecord of `vesting_account`. Companies created before
+// names were reserved have no record to close, and their name may since have been reserved by another company.
+fn close_company_name_record<'info>(
+    record: &AccountInfo<'info>,
+    vesting_account: Pubkey,
+    destination: &AccountInfo<'info>
+) -> Result<()> {
+    if record.owner != &crate::ID || record.data_is_empty() {
+        return Ok(());
+    }
+    let company_name_record = CompanyNameRecord::try_deserialize(&mut &record.try_borrow_data()?[..])?;
+    if company_name_record.vesting_account != vesting_account {
+        return Ok(());
+    }
+    // What the close constraint does, for an account that may not exist.
+    destination.add_lamports(record.lamports())?;
+    record.sub_lamports(record.lamports())?;
+    record.assign(&system_program::ID);
+    record.realloc(0, false)?;
+    Ok(())
+}
+
+// Fails unless a protocol fee in `mint` goes to the config's fee recipient. `fee_owner` and `fee_mint` describe the
+// account it is paid into, for SOL claims the fee recipient's wallet itself and NATIVE_SOL_MINT.
+fn check_fee_destination(config: Option<&Config>, fee_owner: Pubkey, fee_mint: Pubkey, mint: Pubkey) -> Result<()> {
+    if config.map(|config| config.fee_recipient) != Some(fee_owner) || fee_mint != mint {
+        return Err(ErrorCode::InvalidFeeAccount.into());
+    }
+    Ok(())
+}
+
+// Protocol fee owed on a claim of `amount`, nothing on deployments without a config.
+fn claim_fee(config: Option<&Config>, amount: u64) -> Result<u64> {
+    match config {
+        Some(config) => bps_of(amount, config.claim_fee_bps),
+        None => Ok(0),
+    }
+}
+
+// Features are disabled unless the config exists and the admin enabled them.
+fn require_feature(config: Option<&Config>, feature: Feature) -> Result<()> {
+    if config.is_none_or(|config| config.enabled_features & feature.mask() == 0) {
+        return Err(ErrorCode::FeatureDisabled.into());
+    }
+
+    Ok(())
+}
+
+// The beneficiary can always move their grant to a new wallet. The owner only can when both their vesting account
+// and the deployment allow it.
+fn check_reassignment_authority(
+    authority: Pubkey,
+    employee_account: &EmployeeAccount,
+    vesting_account: &VestingAccount,
+    config: Option<&Config>
+) -> Result<()> {
+    if authority == employee_account.beneficiary {
+        return Ok(());
+    }
+    if authority != vesting_account.owner || !vesting_account.owner_can_reassign_beneficiary {
+        return Err(ErrorCode::Unauthorized.into());
+    }
+    require_feature(config, Feature::OwnerBeneficiaryReassignment)
+}
+
+// Company creation is permissionless unless the config exists and has permissioned creation turned on.
+fn check_creator_allowed(config: Option<&Config>, creator_allowlist_entry: &AccountInfo) -> Result<()> {
+    if !config.is_some_and(|config| config.permissioned_creation) {
+        return Ok(());
+    }
+    // The entry's address is already derived from the signer, so it only has to exist.
+    if !is_initialized(creator_allowlist_entry) {
+        return Err(ErrorCode::CreatorNotAllowed.into());
+    }
+
+    Ok(())
+}
+
+// Returns true if the account has been created by this program and not closed since.
+fn is_initialized(account: &AccountInfo) -> bool {
+    account.owner == &crate::ID && !account.data_is_empty()
+}
+
+// Grows an account of an older layout to its current size, `space` plus the discriminator, topping up its rent
+// from `payer`. The new bytes are zeroed, so fields the old layout didn't have read as their defaults.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    space: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>
+) -> Result<()> {
+    let len = 8 + space;
+    if account.data_len() >= len {
+        return Ok(());
+    }
+    let lamports = Rent::get()?.minimum_balance(len).saturating_sub(account.lamports());
+    if lamports > 0 {
+        let transfer_cpi_accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        system_program::transfer(
+            CpiContext::new(system_program.to_account_info(), transfer_cpi_accounts),
+            lamports
+        )?;
+    }
+    account.realloc(len, true)?;
+
+    Ok(())
+}
+
+// Adds a new grant to its vesting account's registry, at the index it was created with.
+fn register_grant(
+    vesting_account: &mut Account<VestingAccount>,
+    grant_entry: &mut Account<GrantRegistryEntry>,
+    employee_account: Pubkey,
+    registry_index: u64,
+    bump: u8
+) -> Result<()> {
+    **grant_entry = GrantRegistryEntry {
+        vesting_account: vesting_account.key(),
+        index: registry_index,
+        employee_account,
+        bump,
+    };
+    vesting_account.grant_count = vesting_account.grant_count
+        .checked_add(1)
+        .ok_or(ErrorCode::CalculationOverflow)?;
+
+    Ok(())
+}
+
+// Every new grant gets the same acceptance window, counted from its creation.
+fn acceptance_deadline() -> Result<i64> {
+    Clock::get()?
+        .unix_timestamp
+        .checked_add(ACCEPTANCE_WINDOW_SECONDS)
+        .ok_or_else(|| ErrorCode::CalculationOverflow.into())
+}
+
+// Shared by every instruction that creates a grant, template_id is set when the grant was created from a template.
+fn emit_employee_vesting_created(employee_account: &Account<EmployeeAccount>, template_id: Option<u64>) {
+    emit!(EmployeeVestingCreated {
+        employee_account: employee_account.key(),
+        vesting_account: employee_account.vesting_account,
+        beneficiary: employee_account.beneficiary,
+        total_amount: employee_account.total_amount,
+        start_time: employee_account.start_time,
+        end_time: employee_account.end_time,
+        cliff_time: employee_account.cliff_time,
+        schedule_kind: employee_account.schedule_kind,
+        template_id,
+        share_bps: employee_account.share_bps,
+    });
+}
+
+// Rejects grants whose amount or timestamps make no sense, so they fail at creation rather than at claim time.
+fn validate_grant(start_time: i64, end_time: i64, cliff_time: i64, total_amount: u64) -> Result<()> {
+    if total_amount == 0 {
+        return Err(ErrorCode::InvalidAmount.into());
+    }
+    if end_time <= start_time {
+        return Err(ErrorCode::InvalidSchedule.into());
+    }
+    if cliff_time < start_time || cliff_time > end_time {
+        return Err(ErrorCode::InvalidSchedule.into());
+    }
+
+    Ok(())
+}
+
+// Checks the schedule parameters that are relative to the vesting duration, shared by direct and template-based grants.
+fn validate_schedule_params(
+    schedule_kind: ScheduleKind,
+    duration: i64,
+    vesting_interval_seconds: i64,
+    cliff_unlock_bps: u16
+) -> Result<()> {
+    // Milestone schedules can only be created through create_employee_vesting_with_milestones.
+    if schedule_kind == ScheduleKind::Milestone {
+        return Err(ErrorCode::InvalidMilestones.into());
+    }
+    // A periodic schedule needs a positive interval, otherwise there is no way to count tranches.
+    if schedule_kind == ScheduleKind::Periodic && vesting_interval_seconds <= 0 {
+        return Err(ErrorCode::InvalidVestingInterval.into());
+    }
+    // An interval longer than the whole schedule would never complete a single tranche before end_time.
+    if schedule_kind == ScheduleKind::Periodic && vesting_interval_seconds > duration {
+        return Err(ErrorCode::InvalidVestingInterval.into());
+    }
+    if cliff_unlock_bps as u64 > BPS_DENOMINATOR {
+        return Err(ErrorCode::InvalidCliffUnlock.into());
+    }
+    // A lockup unlocks everything at end_time, so there is no cliff portion to unlock earlier.
+    if schedule_kind == ScheduleKind::Lockup && cliff_unlock_bps > 0 {
+        return Err(ErrorCode::InvalidCliffUnlock.into());
+    }
+
+    Ok(())
+}
+
+// Milestones must be ordered by time, start no earlier than start_time, never decrease and end at 100%.
+fn validate_milestones(start_time: i64, milestones: &[Milestone]) -> Result<()> {
+    if milestones.is_empty() || milestones.len() > MAX_MILESTONES {
+        return Err(ErrorCode::InvalidMilestones.into());
+    }
+    if milestones[0].timestamp < start_time {
+        return Err(ErrorCode::InvalidMilestones.into());
+    }
+    for pair in milestones.windows(2) {
+        if pair[1].timestamp <= pair[0].timestamp || pair[1].cumulative_bps < pair[0].cumulative_bps {
+            return Err(ErrorCode::InvalidMilestones.into());
+        }
+    }
+    if milestones[milestones.len() - 1].cumulative_bps as u64 != BPS_DENOMINATOR {
+        return Err(ErrorCode::InvalidMilestones.into());
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(company_name: String)]
+pub struct CreateVestingAccount<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 8 + CreatorRegistry::INIT_SPACE,
+        payer = signer,
+        seeds = [SEED_NAMESPACE, b"creator_registry", signer.key().as_ref()],
+        bump
+    )]
+    pub creator_registry: Account<'info, CreatorRegistry>,
+    #[account(
+        init,
+        space = 8 + VestingAccount::INIT_SPACE,
+        payer = signer,
+        seeds = [
+            SEED_NAMESPACE,
+            b"vesting_account",
+            signer.key().as_ref(),
+            creator_registry.vesting_account_count.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        token::mint = mint,
+        token::authority = treasury_token_account,
+        payer = signer,
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        init,
+        space = 8 + ClaimStats::INIT_SPACE,
+        payer = signer,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    // Reserves the company name, so no other company can be created under it or renamed to it.
+    #[account(
+        init,
+        space = 8 + CompanyNameRecord::INIT_SPACE,
+        payer = signer,
+        seeds = [SEED_NAMESPACE, b"company_name", company_name_seed(&company_name).as_ref()],
+        bump
+    )]
+    pub company_name_record: Account<'info, CompanyNameRecord>,
+    /// CHECK: The signer's allowlist entry, which only has to exist when creation is permissioned.
+    #[account(seeds = [SEED_NAMESPACE, b"creator_allowlist", signer.key().as_ref()], bump)]
+    pub creator_allowlist_entry: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateEmployeeAccount<'info> {
+    #[account(mut)]
+    pub grant_admin: Signer<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    #[account(mut, has_one = grant_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    // Only checked to exist, so grants can only be created in mints the vesting account has a treasury for.
+    #[account(
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        space = 8 + EmployeeAccount::INIT_SPACE,
+        payer = grant_admin,
+        seeds = [SEED_NAMESPACE, b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        init,
+        space = 8 + GrantRegistryEntry::INIT_SPACE,
+        payer = grant_admin,
+        seeds = [SEED_NAMESPACE, b"grant_entry", vesting_account.key().as_ref(), vesting_account.grant_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub grant_entry: Account<'info, GrantRegistryEntry>,
+    // Only read by import_grant, which has to be enabled with Feature::GrantImport.
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(company_name: String)]
+pub struct CreateSolVestingAccount<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 8 + CreatorRegistry::INIT_SPACE,
+        payer = signer,
+        seeds = [SEED_NAMESPACE, b"creator_registry", signer.key().as_ref()],
+        bump
+    )]
+    pub creator_registry: Account<'info, CreatorRegistry>,
+    #[account(
+        init,
+        space = 8 + VestingAccount::INIT_SPACE,
+        payer = signer,
+        seeds = [
+            SEED_NAMESPACE,
+            b"vesting_account",
+            signer.key().as_ref(),
+            creator_registry.vesting_account_count.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    // A plain system account, so there is nothing to initialize. It comes into existence with the first transfer to it.
+    #[account(seeds = [SEED_NAMESPACE, b"sol_treasury", vesting_account.key().as_ref()], bump)]
+    pub sol_treasury: SystemAccount<'info>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        init,
+        space = 8 + ClaimStats::INIT_SPACE,
+        payer = signer,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    // Reserves the company name, so no other company can be created under it or renamed to it.
+    #[account(
+        init,
+        space = 8 + CompanyNameRecord::INIT_SPACE,
+        payer = signer,
+        seeds = [SEED_NAMESPACE, b"company_name", company_name_seed(&company_name).as_ref()],
+        bump
+    )]
+    pub company_name_record: Account<'info, CompanyNameRecord>,
+    /// CHECK: The signer's allowlist entry, which only has to exist when creation is permissioned.
+    #[account(seeds = [SEED_NAMESPACE, b"creator_allowlist", signer.key().as_ref()], bump)]
+    pub creator_allowlist_entry: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateSolEmployeeAccount<'info> {
+    #[account(mut)]
+    pub grant_admin: Signer<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    #[account(mut, has_one = grant_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(
+        seeds = [SEED_NAMESPACE, b"sol_treasury", vesting_account.key().as_ref()],
+        bump = vesting_account.treasury_bump
+    )]
+    pub sol_treasury: SystemAccount<'info>,
+    #[account(
+        init,
+        space = 8 + EmployeeAccount::INIT_SPACE,
+        payer = grant_admin,
+        seeds = [SEED_NAMESPACE, b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref(), NATIVE_SOL_MINT.as_ref()],
+        bump
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        init,
+        space = 8 + GrantRegistryEntry::INIT_SPACE,
+        payer = grant_admin,
+        seeds = [SEED_NAMESPACE, b"grant_entry", vesting_account.key().as_ref(), vesting_account.grant_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub grant_entry: Account<'info, GrantRegistryEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimSol<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref(), NATIVE_SOL_MINT.as_ref()],
+        bump = employee_account.bump,
+        has_one = beneficiary,
+        has_one = vesting_account,
+        constraint = employee_account.mint == NATIVE_SOL_MINT @ ErrorCode::NotASolGrant
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_account", vesting_account.creator.as_ref(), vesting_account.index.to_le_bytes().as_ref()],
+        bump = vesting_account.bump
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"sol_treasury", vesting_account.key().as_ref()],
+        bump = vesting_account.treasury_bump
+    )]
+    pub sol_treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump = claim_stats.bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: Pyth price update account of SOL, checked against the treasury's price_feed in price_dependency.
+    pub price_update: Option<UncheckedAccount<'info>>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    // The config's fee recipient, required while the config charges a claim fee.
+    #[account(mut)]
+    pub fee_recipient: Option<SystemAccount<'info>>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+// The has_one constraint is used within the #[account] attribute macro to assert that the specified field of a data structure 
+// (usually an account in this context) points to a specific account.
+pub struct ClaimTokens<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump = employee_account.bump,
+        has_one = beneficiary,
+        has_one = vesting_account,
+        has_one = mint
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_account", vesting_account.creator.as_ref(), vesting_account.index.to_le_bytes().as_ref()],
+        bump = vesting_account.bump
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: The beneficiary's associated token account. It may have been closed or handed to another owner since
+    /// the last claim, so it is checked, and re-created when needed, in claim_destination_account.
+    #[account(
+        mut,
+        seeds = [beneficiary.key().as_ref(), token_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = associated_token_program.key(),
+        bump
+    )]
+    pub employee_token_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump = claim_stats.bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: Pyth price update account of the mint, checked against the treasury's price_feed in price_dependency.
+    pub price_update: Option<UncheckedAccount<'info>>,
+    // Required when the beneficiary registered a claim destination with set_claim_destination.
+    #[account(mut)]
+    pub claim_destination: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    // Token account of the config's fee recipient for the mint, required while the config charges a claim fee.
+    #[account(mut)]
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+    /// CHECK: The beneficiary's AllowedDestinations, which may not exist. Only read while the vesting account
+    /// requires allowed destinations, in check_destination_allowed.
+    #[account(
+        seeds = [SEED_NAMESPACE, b"allowed_destinations", vesting_account.key().as_ref(), beneficiary.key().as_ref()],
+        bump
+    )]
+    pub allowed_destinations: UncheckedAccount<'info>,
+    /// CHECK: The allowlist program registered in allowed_destinations, checked in check_destination_allowed.
+    pub allowlist_program: Option<UncheckedAccount<'info>>,
+    /// CHECK: Only passed on to the allowlist program, which checks it itself.
+    pub allowlist_record: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimTokensAsDelegate<'info> {
+    #[account(mut)]
+    pub delegate: Signer<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump = employee_account.bump,
+        has_one = beneficiary,
+        has_one = vesting_account,
+        has_one = mint,
+        constraint = employee_account.claim_delegate == Some(delegate.key()) @ ErrorCode::NotClaimDelegate
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_account", vesting_account.creator.as_ref(), vesting_account.index.to_le_bytes().as_ref()],
+        bump = vesting_account.bump
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: The beneficiary's associated token account. It may have been closed or handed to another owner since
+    /// the last claim, so it is checked, and re-created when needed, in claim_destination_account.
+    #[account(
+        mut,
+        seeds = [beneficiary.key().as_ref(), token_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = associated_token_program.key(),
+        bump
+    )]
+    pub employee_token_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump = claim_stats.bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: Pyth price update account of the mint, checked against the treasury's price_feed in price_dependency.
+    pub price_update: Option<UncheckedAccount<'info>>,
+    // Required when the beneficiary registered a claim destination with set_claim_destination.
+    #[account(mut)]
+    pub claim_destination: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    // Token account of the config's fee recipient for the mint, required while the config charges a claim fee.
+    #[account(mut)]
+    pub fee_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The beneficiary's AllowedDestinations, which may not exist. Only read while the vesting account
+    /// requires allowed destinations, in check_destination_allowed.
+    #[account(
+        seeds = [SEED_NAMESPACE, b"allowed_destinations", vesting_account.key().as_ref(), beneficiary.key().as_ref()],
+        bump
+    )]
+    pub allowed_destinations: UncheckedAccount<'info>,
+    /// CHECK: The allowlist program registered in allowed_destinations, checked in check_destination_allowed.
+    pub allowlist_program: Option<UncheckedAccount<'info>>,
+    /// CHECK: Only passed on to the allowlist program, which checks it itself.
+    pub allowlist_record: Option<UncheckedAccount<'info>>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptGrant<'info> {
+    pub beneficiary: Signer<'info>,
+    #[account(mut, has_one = beneficiary, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    // Only read, for the grant's status in transitions::guard.
+    pub vesting_account: Account<'info, VestingAccount>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelUnacceptedGrant<'info> {
+    #[account(mut)]
+    pub grant_admin: Signer<'info>,
+    #[account(mut, has_one = grant_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(mut, close = grant_admin, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct SetClaimDelegate<'info> {
+    pub beneficiary: Signer<'info>,
+    #[account(mut, has_one = beneficiary, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    // Only read, for the grant's status in transitions::guard.
+    pub vesting_account: Account<'info, VestingAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetClaimDestination<'info> {
+    pub beneficiary: Signer<'info>,
+    #[account(mut, has_one = beneficiary, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    // Only read, for the grant's status in transitions::guard.
+    pub vesting_account: Account<'info, VestingAccount>,
+    // The new destination, only needed when one is set.
+    pub destination_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey)]
+pub struct SetAllowedDestinations<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(has_one = owner)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(
+        init_if_needed,
+        space = 8 + AllowedDestinations::INIT_SPACE,
+        payer = owner,
+        seeds = [SEED_NAMESPACE, b"allowed_destinations", vesting_account.key().as_ref(), beneficiary.as_ref()],
+        bump
+    )]
+    pub allowed_destinations: Account<'info, AllowedDestinations>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey)]
+pub struct CloseAllowedDestinations<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(has_one = owner)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [SEED_NAMESPACE, b"allowed_destinations", vesting_account.key().as_ref(), beneficiary.as_ref()],
+        bump = allowed_destinations.bump
+    )]
+    pub allowed_destinations: Account<'info, AllowedDestinations>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVestingAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: A vesting account that may be too small to deserialize yet. Deserialized, which checks its
+    /// discriminator, once grow_account has brought it to its current size.
+    #[account(mut, owner = crate::ID)]
+    pub vesting_account: UncheckedAccount<'info>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateEmployeeAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: A grant that may be too small to deserialize yet. Deserialized, which checks its discriminator, once
+    /// grow_account has brought it to its current size.
+    #[account(mut, owner = crate::ID)]
+    pub employee_account: UncheckedAccount<'info>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyVestingAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: A vesting account with the original layout, decoded by decode_legacy_account, which checks its
+    /// discriminator and size.
+    #[account(mut, owner = crate::ID)]
+    pub vesting_account: UncheckedAccount<'info>,
+    #[account(
+        init,
+        space = 8 + ClaimStats::INIT_SPACE,
+        payer = payer,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyEmployeeAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: A grant with the original layout, decoded by decode_legacy_account, which checks its discriminator
+    /// and size.
+    #[account(mut, owner = crate::ID)]
+    pub employee_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(
+        init,
+        space = 8 + GrantRegistryEntry::INIT_SPACE,
+        payer = payer,
+        seeds = [SEED_NAMESPACE, b"grant_entry", vesting_account.key().as_ref(), vesting_account.grant_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub grant_entry: Account<'info, GrantRegistryEntry>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// The original program's seeds, without SEED_NAMESPACE, which it predates.
+#[derive(Accounts)]
+pub struct ClaimLegacyTokens<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref()],
+        bump = employee_account.bump,
+        has_one = beneficiary,
+        has_one = vesting_account,
+        has_one = mint
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        seeds = [vesting_account.company_name.as_bytes()],
+        bump = vesting_account.bump,
+        constraint = vesting_account.legacy_seeds @ ErrorCode::NotLegacyAccount
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"vesting_treasury", vesting_account.company_name.as_bytes()],
+        bump = vesting_account.treasury_bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program
+    )]
+    pub employee_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAll<'info> {
+    pub beneficiary: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct GetClaimableAmount<'info> {
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    // The vesting account's primary treasury, required for pool grants, whose amount is computed from its balance.
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct SnapshotVestedAmount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        init_if_needed,
+        space = 8 + VotingPower::INIT_SPACE,
+        payer = payer,
+        seeds = [SEED_NAMESPACE, b"voting_power", vesting_account.key().as_ref(), employee_account.registry_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub voting_power: Account<'info, VotingPower>,
+    // The vesting account's primary treasury, required for pool grants, whose amount is computed from its balance.
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CheckDeadline<'info> {
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetTimeOverride<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 8 + TimeOverride::INIT_SPACE,
+        payer = payer,
+        seeds = [SEED_NAMESPACE, b"time_override"],
+        bump
+    )]
+    pub time_override: Account<'info, TimeOverride>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwnership<'info> {
+    pub pending_owner: Signer<'info>,
+    #[account(
+        mut,
+        constraint = vesting_account.pending_owner == Some(pending_owner.key()) @ ErrorCode::NotPendingOwner
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVestingAccount<'info> {
+    pub owner: Signer<'info>,
+    #[account(mut, has_one = owner)]
+    pub vesting_account: Account<'info, VestingAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(company_name: String)]
+pub struct RenameCompany<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut, has_one = owner)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    // Already initialized when only the case of the name changes.
+    #[account(
+        init_if_needed,
+        space = 8 + CompanyNameRecord::INIT_SPACE,
+        payer = owner,
+        seeds = [SEED_NAMESPACE, b"company_name", company_name_seed(&company_name).as_ref()],
+        bump
+    )]
+    pub company_name_record: Account<'info, CompanyNameRecord>,
+    /// CHECK: The record of the current name, closed in the handler with close_company_name_record.
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"company_name", company_name_seed(&vesting_account.company_name).as_ref()],
+        bump
+    )]
+    pub previous_company_name_record: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReassignBeneficiary<'info> {
+    // Either the current beneficiary or, if the vesting account allows it, the owner. Checked in the handler.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub vesting_account: Account<'info, VestingAccount>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            SEED_NAMESPACE,
+            b"employee_vesting",
+            employee_account.beneficiary.as_ref(),
+            vesting_account.key().as_ref(),
+            employee_account.mint.as_ref(),
+        ],
+        bump = employee_account.bump,
+        has_one = vesting_account
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    pub new_beneficiary: SystemAccount<'info>,
+    #[account(
+        init,
+        space = 8 + EmployeeAccount::INIT_SPACE,
+        payer = authority,
+        seeds = [
+            SEED_NAMESPACE,
+            b"employee_vesting",
+            new_beneficiary.key().as_ref(),
+            vesting_account.key().as_ref(),
+            employee_account.mint.as_ref(),
+        ],
+        bump
+    )]
+    pub new_employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"grant_entry", vesting_account.key().as_ref(), employee_account.registry_index.to_le_bytes().as_ref()],
+        bump = grant_entry.bump
+    )]
+    pub grant_entry: Account<'info, GrantRegistryEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetCommunicationPointer<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 8 + BeneficiaryRegistry::INIT_SPACE,
+        payer = beneficiary,
+        seeds = [SEED_NAMESPACE, b"beneficiary_registry", beneficiary.key().as_ref()],
+        bump
+    )]
+    pub beneficiary_registry: Account<'info, BeneficiaryRegistry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpGrant<'info> {
+    pub grant_admin: Signer<'info>,
+    #[account(mut, has_one = grant_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(mut, has_one = vesting_account, has_one = mint)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = grant_admin,
+        token::token_program = token_program
+    )]
+    pub source_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeGrant<'info> {
+    pub revoke_admin: Signer<'info>,
+    #[account(has_one = revoke_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(mut, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteClawback<'info> {
+    #[account(mut)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(mut, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateEmployeeAccount<'info> {
+    pub grant_admin: Signer<'info>,
+    #[account(mut, has_one = grant_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(mut, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), employee_account.mint.as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(external_reference: Pubkey)]
+pub struct ExportGrant<'info> {
+    #[account(mut)]
+    pub treasury_admin: Signer<'info>,
+    // Moving a grant changes who holds the beneficiary's tokens, so the beneficiary has to agree to it.
+    pub beneficiary: Signer<'info>,
+    #[account(mut, has_one = treasury_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(mut, close = treasury_admin, has_one = vesting_account, has_one = mint, has_one = beneficiary)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    // Receives the vested but unclaimed tokens.
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = beneficiary,
+        token::token_program = token_program
+    )]
+    pub beneficiary_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+        constraint = destination_token_account.owner == Pubkey::find_program_address(
+            &[b"grant_import", external_reference.as_ref()],
+            destination_program.key
+        ).0 @ ErrorCode::InvalidExportDestination
+    )]
+    pub destination_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: The vesting program the grant moves to, only used as the target of the CPI.
+    #[account(
+        executable,
+        constraint = destination_program.key() != crate::ID @ ErrorCode::InvalidExportProgram
+    )]
+    pub destination_program: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: The program config, which may not be initialized on self-deployments. Read with load_config.
+    #[account(seeds = [SEED_NAMESPACE, b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: The TimeOverride account, only read by test-clock builds, see current_time.
+    pub time_override: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct AddTreasury<'info> {
+    #[account(mut)]
+    pub treasury_admin: Signer<'info>,
+    #[account(mut, has_one = treasury_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        token::mint = mint,
+        token::authority = treasury_token_account,
+        payer = treasury_admin,
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTreasury<'info> {
+    #[account(mut)]
+    pub treasury_admin: Signer<'info>,
+    #[account(
+        mut,
+        has_one = treasury_admin,
+        constraint = vesting_account.mint != mint.key() @ ErrorCode::PrimaryTreasury
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump,
+        constraint = treasury_token_account.amount == 0 @ ErrorCode::TreasuryNotEmpty
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetPriceFeed<'info> {
+    pub treasury_admin: Signer<'info>,
+    #[account(mut, has_one = treasury_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CloseEmployeeAccount<'info> {
+    #[account(mut)]
+    pub grant_admin: Signer<'info>,
+    #[account(mut, has_one = grant_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    // The close constraint transfers the account's lamports to grant_admin and zeroes its data at the end of the instruction.
+    #[account(mut, close = grant_admin, has_one = vesting_account)]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    // The vesting account's primary treasury, required for pool grants, whose amount is computed from its balance.
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseVestingAccount<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        has_one = treasury_token_account,
+        constraint = vesting_account.active_grant_count == 0 @ ErrorCode::ActiveGrantsRemaining,
+        constraint = vesting_account.treasuries.len() == 1 @ ErrorCode::TreasuriesRemaining
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(
+        mut,
+        constraint = treasury_token_account.amount == 0 @ ErrorCode::TreasuryNotEmpty
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump = claim_stats.bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    /// CHECK: The company name's record, closed in the handler with close_company_name_record.
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"company_name", company_name_seed(&vesting_account.company_name).as_ref()],
+        bump
+    )]
+    pub company_name_record: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSolVestingAccount<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        constraint = vesting_account.mint == NATIVE_SOL_MINT @ ErrorCode::NotASolVestingAccount,
+        constraint = vesting_account.active_grant_count == 0 @ ErrorCode::ActiveGrantsRemaining,
+        constraint = vesting_account.treasuries.len() == 1 @ ErrorCode::TreasuriesRemaining
+    )]
+    pub vesting_account: Account<'info, VestingAccount>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"sol_treasury", vesting_account.key().as_ref()],
+        bump = vesting_account.treasury_bump
+    )]
+    pub sol_treasury: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [SEED_NAMESPACE, b"claim_stats", vesting_account.key().as_ref()],
+        bump = claim_stats.bump
+    )]
+    pub claim_stats: Account<'info, ClaimStats>,
+    /// CHECK: The company name's record, closed in the handler with close_company_name_record.
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"company_name", company_name_seed(&vesting_account.company_name).as_ref()],
+        bump
+    )]
+    pub company_name_record: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        space = 8 + Config::INIT_SPACE,
+        payer = authority,
+        seeds = [SEED_NAMESPACE, b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    // The program data account stores the upgrade authority of this program.
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Vesting>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+#[instruction(creator: Pubkey)]
+pub struct AddAllowedCreator<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [SEED_NAMESPACE, b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        space = 8 + CreatorAllowlistEntry::INIT_SPACE,
+        payer = admin,
+        seeds = [SEED_NAMESPACE, b"creator_allowlist", creator.as_ref()],
+        bump
+    )]
+    pub creator_allowlist_entry: Account<'info, CreatorAllowlistEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(creator: Pubkey)]
+pub struct RemoveAllowedCreator<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [SEED_NAMESPACE, b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [SEED_NAMESPACE, b"creator_allowlist", creator.as_ref()],
+        bump = creator_allowlist_entry.bump
+    )]
+    pub creator_allowlist_entry: Account<'info, CreatorAllowlistEntry>,
+}
+
+#[derive(Accounts)]
+pub struct CreateScheduleTemplate<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        space = 8 + ScheduleTemplate::INIT_SPACE,
+        payer = admin,
+        seeds = [SEED_NAMESPACE, b"schedule_template", config.template_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub schedule_template: Account<'info, ScheduleTemplate>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(template_id: u64)]
+pub struct SetScheduleTemplateActive<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [SEED_NAMESPACE, b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [SEED_NAMESPACE, b"schedule_template", template_id.to_le_bytes().as_ref()],
+        bump = schedule_template.bump
+    )]
+    pub schedule_template: Account<'info, ScheduleTemplate>,
+}
+
+#[derive(Accounts)]
+#[instruction(template_id: u64)]
+pub struct CreateEmployeeAccountFromTemplate<'info> {
+    #[account(mut)]
+    pub grant_admin: Signer<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    #[account(mut, has_one = grant_admin)]
+    pub vesting_account: Account<'info, VestingAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    // Only checked to exist, so grants can only be created in mints the vesting account has a treasury for.
+    #[account(
+        seeds = [SEED_NAMESPACE, b"vesting_treasury", vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [SEED_NAMESPACE, b"schedule_template", template_id.to_le_bytes().as_ref()],
+        bump = schedule_template.bump
+    )]
+    pub schedule_template: Account<'info, ScheduleTemplate>,
+    #[account(
+        init,
+        space = 8 + EmployeeAccount::INIT_SPACE,
+        payer = grant_admin,
+        seeds = [SEED_NAMESPACE, b"employee_vesting", beneficiary.key().as_ref(), vesting_account.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub employee_account: Account<'info, EmployeeAccount>,
+    #[account(
+        init,
+        space = 8 + GrantRegistryEntry::INIT_SPACE,
+        payer = grant_admin,
+        seeds = [SEED_NAMESPACE, b"grant_entry", vesting_account.key().as_ref(), vesting_account.grant_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub grant_entry: Account<'info, GrantRegistryEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct VestingAccount {
+    pub owner: Pubkey,
+    // The key that created the vesting account, together with index it seeds the PDA and never changes.
+    pub creator: Pubkey,
+    // Roles for day-to-day administration, all set to the creator at first and reassigned by the owner with
+    // set_role. The owner keeps ownership, pausing, policies and closing the vesting account.
+    // Creates, amends, tops up, cancels and closes grants.
+    pub grant_admin: Pubkey,
+    // Revokes grants and rescinds revocations.
+    pub revoke_admin: Pubkey,
+    // Adds and closes treasuries and exports grants out of them.
+    pub treasury_admin: Pubkey,
+    pub index: u64,
+    // The primary mint and its treasury, created along with the vesting account. Treasuries for other mints
+    // are added with add_treasury, all of them are seeded by the vesting account and their mint.
+    pub mint: Pubkey,
+    pub treasury_token_account: Pubkey,
+    // The name shown to beneficiaries, as the owner typed it.
+    #[max_len(MAX_COMPANY_NAME_LEN)]
+    pub company_name: String,
+    pub treasury_bump: u8,
+    // Number of employee accounts that haven't been closed yet, the vesting account can only be closed at zero.
+    pub active_grant_count: u64,
+    // Number of grants ever created, also the index the next grant's GrantRegistryEntry gets. Closed grants keep
+    // their entries, so this never goes down.
+    pub grant_count: u64,
+    // One entry per open treasury, the primary one first. The vesting account can only be closed with just the primary left.
+    #[max_len(MAX_TREASURIES)]
+    pub treasuries: Vec<TreasuryReserve>,
+    // Lamports deposited at creation on top of rent, refunded when the vesting account is closed.
+    pub deposit_lamports: u64,
+    // Set by propose_new_owner, becomes the owner once it signs accept_ownership.
+    pub pending_owner: Option<Pubkey>,
+    // Whether the owner may reassign grants to new beneficiary wallets, beneficiaries can always reassign their own.
+    // Only takes effect on deployments that enabled Feature::OwnerBeneficiaryReassignment.
+    pub owner_can_reassign_beneficiary: bool,
+    // While set, claim_tokens fails with VestingPaused.
+    pub is_paused: bool,
+    // Time between revoke_grant and the earliest execute_clawback, which gives the beneficiary time to dispute.
+    // Each revocation snapshots it into the grant's clawback_available_at.
+    pub clawback_delay_seconds: i64,
+    // Chosen at creation and never changed, so the company can't tighten it once grants depend on it.
+    pub dependency_failure_policy: DependencyFailurePolicy,
+    pub bump: u8,
+    // company_name lowercased, so clients and indexers can tell that "Acme" and "acme" name the same company. It is
+    // reserved by a CompanyNameRecord, so no other company goes by it.
+    // Empty on companies created before names were validated, until their owner calls rename_company.
+    #[max_len(MAX_COMPANY_NAME_LEN)]
+    pub canonical_name: String,
+    // Shares of the primary treasury handed out to pool grants so far, in basis points. Never above 100%, which
+    // keeps the pool solvent. Cancelled offers and closed grants give their share back.
+    pub pool_share_bps: u16,
+    // Total claimed by pool grants, so their shares keep applying to everything the pool has held.
+    pub pool_withdrawn: u64,
+    // When set, token claims only pay into wallets the beneficiary's AllowedDestinations lets through.
+    pub require_allowed_destinations: bool,
+    // Layout version, see VESTING_ACCOUNT_VERSION.
+    pub version: u8,
+    // Set on vesting accounts migrated from the original layout, which keep their `[company_name]` address and
+    // `[b"vesting_treasury", company_name]` treasury. Their company name can't change, it seeds both. Handlers sign
+    // for that treasury with treasury_signer_seeds, contexts that check the current treasury seeds reject it, so
+    // their grants are claimed with claim_legacy_tokens or claim_all.
+    pub legacy_seeds: bool,
+    // Pool size when a closed pool grant last gave its share back. Shares handed out after that only count what the
+    // pool gains from then on, so a new grant can't claim what the closed one already withdrew.
+    pub pool_released_size: u64,
+    // Headroom for new fields, see VESTING_ACCOUNT_RESERVED_BYTES.
+    pub reserved: [u8; VESTING_ACCOUNT_RESERVED_BYTES],
+}
+
+impl VestingAccount {
+    // Reserves `amount` of `mint` for a grant. Fails if the treasury doesn't hold enough tokens that aren't
+    // reserved for other grants yet, so the company can never promise more than it deposited.
+    pub fn reserve(&mut self, mint: Pubkey, amount: u64, treasury_balance: u64) -> Result<()> {
+        // Once pool grants own shares of the primary treasury, all of it belongs to the pool.
+        if mint == self.mint && self.pool_share_bps > 0 {
+            return Err(ErrorCode::PoolTreasuryInUse.into());
+        }
+        let treasury = self.treasury_reserve_mut(mint)?;
+        let reserved_amount = treasury.reserved_amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+        if reserved_amount > treasury_balance {
+            return Err(ErrorCode::TreasuryOvercommitted.into());
+        }
+        treasury.reserved_amount = reserved_amount;
+        Ok(())
+    }
+
+    // Starts tracking reservations for a new treasury. Each mint gets one, including the primary mint of a legacy
+    // account, whose treasury lives at an address add_treasury doesn't derive.
+    pub fn add_treasury(&mut self, mint: Pubkey) -> Result<()> {
+        if self.treasuries.iter().any(|treasury| treasury.mint == mint) {
+            return Err(ErrorCode::TreasuryAlreadyExists.into());
+        }
+        if self.treasuries.len() >= MAX_TREASURIES {
+            return Err(ErrorCode::TooManyTreasuries.into());
+        }
+        self.treasuries.push(TreasuryReserve { mint, reserved_amount: 0, price_feed: None });
+        Ok(())
+    }
+
+    // Gives back a reservation once its tokens left the treasury or the grant no longer needs them.
+    pub fn release(&mut self, mint: Pubkey, amount: u64) -> Result<()> {
+        let treasury = self.treasury_reserve_mut(mint)?;
+        treasury.reserved_amount = treasury.reserved_amount.saturating_sub(amount);
+        Ok(())
+    }
+
+    // Everything the pool has held: what the primary treasury holds now plus what pool grants already claimed.
+    // Deposits make it grow, claims leave it unchanged.
+    pub fn pool_size(&self, treasury_balance: u64) -> Result<u64> {
+        treasury_balance.checked_add(self.pool_withdrawn).ok_or_else(|| ErrorCode::CalculationOverflow.into())
+    }
+
+    fn treasury_reserve_mut(&mut self, mint: Pubkey) -> Result<&mut TreasuryReserve> {
+        self.treasuries
+            .iter_mut()
+            .find(|treasury| treasury.mint == mint)
+            .ok_or_else(|| ErrorCode::UnknownTreasury.into())
+    }
+}
+
+// Amount of a treasury's balance that is promised to grants and not withdrawn yet.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct TreasuryReserve {
+    pub mint: Pubkey,
+    pub reserved_amount: u64,
+    // Pyth price update account of the mint, claims that pass it record the price in TokensClaimed.
+    pub price_feed: Option<Pubkey>,
+}
+
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct EmployeeAccount {
+    pub beneficiary: Pubkey,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub total_amount: u64,
+    pub total_withdrawn: u64,
+    pub cliff_time: i64,
+    pub schedule_kind: ScheduleKind,
+    // Length of one tranche for periodic schedules, ignored for linear schedules.
+    pub vesting_interval_seconds: i64,
+    // Share of total_amount, in basis points, that unlocks all at once at cliff_time.
+    pub cliff_unlock_bps: u16,
+    // Unlock curve of milestone schedules, empty for every other schedule kind.
+    #[max_len(MAX_MILESTONES)]
+    pub milestones: Vec<Milestone>,
+    // Key allowed to call claim_tokens_as_delegate for this grant, set by the beneficiary.
+    pub claim_delegate: Option<Pubkey>,
+    // Token account claims are paid into instead of the beneficiary's associated token account, set by the
+    // beneficiary with set_claim_destination.
+    pub claim_destination: Option<Pubkey>,
+    // When the beneficiary signed accept_grant, nothing can be claimed before that.
+    pub accepted_at: Option<i64>,
+    // After this, a grant that still hasn't been accepted can be cancelled with cancel_unaccepted_grant.
+    pub acceptance_deadline: i64,
+    // When the revoke admin revoked the grant, vesting stops there. The unvested rest goes back to the treasury
+    // through execute_clawback once the vesting account's clawback delay has passed.
+    pub revoked_at: Option<i64>,
+    // Set by execute_clawback, total_amount then only holds what had vested when the grant was revoked.
+    pub clawed_back: bool,
+    // Mint the grant pays out in, claims are paid from the vesting account's treasury for this mint.
+    pub mint: Pubkey,
+    pub vesting_account: Pubkey,
+    // Index of the grant's GrantRegistryEntry.
+    pub registry_index: u64,
+    pub bump: u8,
+    // For pool grants, the share of the vesting account's pool the grant is worth, in basis points. total_amount
+    // then holds that share of the pool as of the last claim. 0 for grants of a fixed amount.
+    pub share_bps: u16,
+    // Layout version, see EMPLOYEE_ACCOUNT_VERSION.
+    pub version: u8,
+    // When execute_clawback may run, fixed by revoke_grant so a later set_clawback_delay doesn't move it. None
+    // while the grant isn't revoked.
+    pub clawback_available_at: Option<i64>,
+    // For pool grants, the pool size the share counts from, the vesting account's pool_released_size when the
+    // grant was created.
+    pub pool_base: u64,
+    // Headroom for new fields, see EMPLOYEE_ACCOUNT_RESERVED_BYTES.
+    pub reserved: [u8; EMPLOYEE_ACCOUNT_RESERVED_BYTES],
+}
+
+impl EmployeeAccount {
+    // A grant of `vesting_account` that hasn't been accepted and has nothing withdrawn yet. It gets the next
+    // registry index, register_grant then adds it to the registry.
+    pub fn new(
+        beneficiary: Pubkey,
+        vesting_account: &Account<VestingAccount>,
+        mint: Pubkey,
+        bump: u8,
+        schedule: Schedule
+    ) -> Result<Self> {
+        Ok(EmployeeAccount {
+            beneficiary,
+            start_time: schedule.start_time,
+            end_time: schedule.end_time,
+            total_amount: schedule.total_amount,
+            total_withdrawn: 0,
+            cliff_time: schedule.cliff_time,
+            schedule_kind: schedule.kind,
+            vesting_interval_seconds: schedule.vesting_interval_seconds,
+            cliff_unlock_bps: schedule.cliff_unlock_bps,
+            milestones: schedule.milestones.to_vec(),
+            claim_delegate: None,
+            claim_destination: None,
+            accepted_at: None,
+            acceptance_deadline: acceptance_deadline()?,
+            revoked_at: None,
+            clawed_back: false,
+            mint,
+            vesting_account: vesting_account.key(),
+            registry_index: vesting_account.grant_count,
+            bump,
+            share_bps: 0,
+            version: EMPLOYEE_ACCOUNT_VERSION,
+            clawback_available_at: None,
+            pool_base: 0,
+            reserved: [0; EMPLOYEE_ACCOUNT_RESERVED_BYTES],
+        })
+    }
+
+    // Returns the amount that has vested at `now` and hasn't been withdrawn yet.
+    pub fn claimable_amount(&self, now: i64) -> Result<u64> {
+        Ok(self.vested_amount(now)?.saturating_sub(self.total_withdrawn))
+    }
+
+    // Returns the total amount vested at `now`, including anything that has already been withdrawn.
+    pub fn vested_amount(&self, now: i64) -> Result<u64> {
+        if self.clawed_back {
+            return Ok(self.total_amount);
+        }
+        // A revoked grant stops vesting at the revocation, even while the clawback is still pending.
+        let now = self.revoked_at.map_or(now, |revoked_at| now.min(revoked_at));
+        schedule::vested_amount(&self.schedule(), now)
+    }
+
+    // Stops the grant from vesting at `now`. Returns when its unvested rest can be clawed back, which is fixed by the
+    // clawback delay at revocation.
+    pub fn revoke(&mut self, now: i64, clawback_delay_seconds: i64) -> i64 {
+        let clawback_available_at = now.saturating_add(clawback_delay_seconds);
+        self.revoked_at = Some(now);
+        self.clawback_available_at = Some(clawback_available_at);
+        clawback_available_at
+    }
+
+    // Shrinks a revoked grant to what had vested at its revocation, once the clawback delay has passed. Returns the
+    // amount taken back.
+    pub fn claw_back(&mut self, now: i64) -> Result<u64> {
+        let revoked_at = self.revoked_at.ok_or(ErrorCode::GrantNotRevoked)?;
+        let clawback_available_at = self.clawback_available_at.ok_or(ErrorCode::GrantNotRevoked)?;
+        if now < clawback_available_at {
+            return Err(ErrorCode::ClawbackDelayNotElapsed.into());
+        }
+
+        let vested_amount = self.vested_amount(revoked_at)?;
+        let clawed_back_amount = self.total_amount.saturating_sub(vested_amount);
+        self.total_amount = vested_amount;
+        self.clawed_back = true;
+        Ok(clawed_back_amount)
+    }
+
+    // Updates a pool grant's total_amount to its share of the pool, before anything is computed from it.
+    pub fn refresh_pool_amount(&mut self, pool_size: u64) -> Result<()> {
+        if self.share_bps > 0 {
+            self.total_amount = bps_of(pool_size.saturating_sub(self.pool_base), self.share_bps)?;
+        }
+        Ok(())
+    }
+
+    // The grant's unlock curve, for the pure vesting math in the schedule module.
+    pub fn schedule(&self) -> Schedule<'_> {
+        Schedule {
+            kind: self.schedule_kind,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            cliff_time: self.cliff_time,
+            total_amount: self.total_amount,
+            vesting_interval_seconds: self.vesting_interval_seconds,
+            cliff_unlock_bps: self.cliff_unlock_bps,
+            milestones: &self.milestones,
+        }
+    }
+}
+
+// The layouts the program shipped with, before any of the fields above existed. Accounts created back then are
+// serialized exactly like this, under the VestingAccount and EmployeeAccount discriminators, and live at their
+// original addresses: `[company_name]` for vesting accounts and `[b"employee_vesting", beneficiary, vesting_account]`
+// for grants. migrate_legacy_vesting_account and migrate_legacy_employee_account rewrite them in the current layout.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Debug)]
+pub struct LegacyVestingAccount {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub treasury_token_account: Pubkey,
+    #[max_len(MAX_COMPANY_NAME_LEN)]
     pub company_name: String,
     pub treasury_bump: u8,
     pub bump: u8,
 }
 
+impl LegacyVestingAccount {
+    // The owner gets every role, and nothing is reserved since the original program didn't track reservations.
+    pub fn into_current(self) -> VestingAccount {
+        // Names that don't validate are kept as they are, they seed the account's address.
+        let canonical_name = canonical_company_name(&self.company_name).unwrap_or_default();
+        VestingAccount {
+            owner: self.owner,
+            creator: self.owner,
+            grant_admin: self.owner,
+            revoke_admin: self.owner,
+            treasury_admin: self.owner,
+            index: 0,
+            mint: self.mint,
+            treasury_token_account: self.treasury_token_account,
+            company_name: self.company_name,
+            treasury_bump: self.treasury_bump,
+            active_grant_count: 0,
+            grant_count: 0,
+            treasuries: vec![TreasuryReserve { mint: self.mint, reserved_amount: 0, price_feed: None }],
+            deposit_lamports: 0,
+            pending_owner: None,
+            owner_can_reassign_beneficiary: false,
+            is_paused: false,
+            clawback_delay_seconds: 0,
+            dependency_failure_policy: DependencyFailurePolicy::BlockClaims,
+            bump: self.bump,
+            canonical_name,
+            pool_share_bps: 0,
+            pool_withdrawn: 0,
+            require_allowed_destinations: false,
+            version: VESTING_ACCOUNT_VERSION,
+            legacy_seeds: true,
+            pool_released_size: 0,
+            reserved: [0; VESTING_ACCOUNT_RESERVED_BYTES],
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Debug)]
+pub struct LegacyEmployeeAccount {
+    pub beneficiary: Pubkey,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub total_amount: i64,
+    pub total_withdrawn: i64,
+    pub cliff_time: i64,
+    pub vesting_account: Pubkey,
+    pub bump: u8,
+}
+
+impl LegacyEmployeeAccount {
+    // Original grants vested linearly with nothing unlocking at the cliff and needed no acceptance, so they count
+    // as accepted from their start. `registry_index` is the vesting account's next grant registry index.
+    pub fn into_current(self, mint: Pubkey, registry_index: u64) -> Result<EmployeeAccount> {
+        Ok(EmployeeAccount {
+            beneficiary: self.beneficiary,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            total_amount: to_u64(self.total_amount)?,
+            total_withdrawn: to_u64(self.total_withdrawn)?,
+            cliff_time: self.cliff_time,
+            schedule_kind: ScheduleKind::Linear,
+            vesting_interval_seconds: 0,
+            cliff_unlock_bps: 0,
+            milestones: Vec::new(),
+            claim_delegate: None,
+            claim_destination: None,
+            accepted_at: Some(self.start_time),
+            acceptance_deadline: self.start_time,
+            revoked_at: None,
+            clawed_back: false,
+            mint,
+            vesting_account: self.vesting_account,
+            registry_index,
+            bump: self.bump,
+            share_bps: 0,
+            version: EMPLOYEE_ACCOUNT_VERSION,
+            clawback_available_at: None,
+            pool_base: 0,
+            reserved: [0; EMPLOYEE_ACCOUNT_RESERVED_BYTES],
+        })
+    }
+}
+
+// Decodes an account written with one of the original layouts, which were allocated at exactly their INIT_SPACE.
+fn decode_legacy_account<T: AnchorDeserialize + Space>(data: &[u8], discriminator: &[u8]) -> Result<T> {
+    if data.len() != 8 + T::INIT_SPACE || &data[..8] != discriminator {
+        return Err(ErrorCode::NotLegacyAccount.into());
+    }
+    T::deserialize(&mut &data[8..]).map_err(|_| ErrorCode::NotLegacyAccount.into())
+}
+
+// Returns `amount * numerator / denominator` rounded down. Multiplying in u128 means large grants with
+// long schedules can't overflow before the division.
+fn pro_rata(amount: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    let result = (amount as u128) * (numerator as u128) / (denominator as u128);
+    u64::try_from(result).map_err(|_| ErrorCode::CalculationOverflow.into())
+}
+
+// Returns `bps` basis points of `amount`, rounded down.
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    amount
+        .checked_mul(bps as u64)
+        .map(|product| product / BPS_DENOMINATOR)
+        .ok_or_else(|| ErrorCode::CalculationOverflow.into())
+}
+
+// Converts a non-negative duration or timestamp difference to u64.
+fn to_u64(value: i64) -> Result<u64> {
+    u64::try_from(value).map_err(|_| ErrorCode::CalculationOverflow.into())
+}
+
+// What claims do when an external dependency of the vesting account is stale or erroring.
+// A Token-2022 transfer hook is enforced by the token program itself, so claims of a mint whose hook program
+// is unavailable are always blocked. The policy applies to the treasury price feeds, see price_dependency.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum DependencyFailurePolicy {
+    // Claims fail with DependencyUnavailable until the dependency recovers.
+    BlockClaims,
+    // Only amounts that vested while the dependency was last healthy can be claimed.
+    VestedOnly,
+    // Vesting falls back to the schedule's time-based math, ignoring the dependency.
+    TimeBased,
+}
+
+// Return data of get_claimable_amount. Amounts are in the mint's base units.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ClaimableAmount {
+    pub timestamp: i64,
+    pub vested_amount: u64,
+    pub withdrawn_amount: u64,
+    pub claimable_amount: u64,
+}
+
+// Oracle price of one whole token, the price is price * 10^expo.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceSnapshot {
+    pub price: i64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VestingRole {
+    GrantAdmin,
+    RevokeAdmin,
+    TreasuryAdmin,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum ScheduleKind {
+    // Tokens vest continuously between start_time and end_time.
+    Linear,
+    // Tokens vest in equal tranches every vesting_interval_seconds, the final tranche trues up to total_amount.
+    Periodic,
+    // Tokens vest following the grant's milestones, each one unlocking up to its cumulative share.
+    Milestone,
+    // Nothing vests before end_time, then everything unlocks at once. The usual model for investor and advisor lockups.
+    Lockup,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct Milestone {
+    pub timestamp: i64,
+    // Share of total_amount, in basis points, unlocked once timestamp has passed, including earlier milestones.
+    pub cumulative_bps: u16,
+}
+
+// The program config is a singleton holding protocol-level settings, managed by the protocol admin.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct Config {
+    pub admin: Pubkey,
+    // Number of schedule templates created so far, also the id of the next template.
+    pub template_count: u64,
+    // When set, only creators with a CreatorAllowlistEntry can create vesting accounts.
+    pub permissioned_creation: bool,
+    // Refundable deposit charged to new companies, 0 disables it.
+    pub creation_deposit_lamports: u64,
+    // Bitmask of the features enabled on this deployment, see Feature::mask.
+    pub enabled_features: u64,
+    // Wallet claim fees are paid to, into its token accounts for token grants.
+    pub fee_recipient: Pubkey,
+    // Share of every claim, in basis points, paid to fee_recipient. At most MAX_CLAIM_FEE_BPS, 0 disables it.
+    pub claim_fee_bps: u16,
+    pub bump: u8,
+}
+
+impl Config {
+    pub fn set_claim_fee(&mut self, fee_recipient: Pubkey, claim_fee_bps: u16) -> Result<()> {
+        if claim_fee_bps > MAX_CLAIM_FEE_BPS {
+            return Err(ErrorCode::ClaimFeeTooHigh.into());
+        }
+        self.fee_recipient = fee_recipient;
+        self.claim_fee_bps = claim_fee_bps;
+        Ok(())
+    }
+}
+
+// High-risk subsystems that each deployment's admin has to enable explicitly with set_feature_enabled.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum Feature {
+    // Lets owners reassign grants to new beneficiary wallets, when their vesting account allows it too.
+    OwnerBeneficiaryReassignment,
+    // export_grant, which hands a grant's unvested tokens to another program.
+    GrantExport,
+    // import_grant, which creates grants with withdrawals that happened in another program.
+    GrantImport,
+    // The migrate_* instructions, which rewrite accounts in place.
+    AccountMigration,
+    // claim_all, which reads every account it pays out from remaining_accounts.
+    ClaimAll,
+}
+
+impl Feature {
+    pub fn mask(self) -> u64 {
+        1 << (self as u8)
+    }
+}
+
+// Reserves a company name for a vesting account, at a PDA of the name's canonical form, so no two companies have
+// names that only differ in case. Created along with the vesting account and moved by rename_company.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct CompanyNameRecord {
+    pub vesting_account: Pubkey,
+    pub bump: u8,
+}
+
+// Counts the vesting accounts created by a key, the count is the index of its next vesting account.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct CreatorRegistry {
+    pub creator: Pubkey,
+    pub vesting_account_count: u64,
+    pub bump: u8,
+}
+
+// Lists the grants of a vesting account by index, from 0 to its grant_count, so clients and other programs can
+// page through a company's grants without a getProgramAccounts scan. The entry outlives its grant: once the grant
+// is closed, employee_account points to an account that no longer exists.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct GrantRegistryEntry {
+    pub vesting_account: Pubkey,
+    pub index: u64,
+    // Follows the grant when its beneficiary is reassigned.
+    pub employee_account: Pubkey,
+    pub bump: u8,
+}
+
+// Vested and unclaimed tokens of one grant as of its last snapshot_vested_amount, for governance plugins (e.g. a
+// Realms voter weight plugin) to count as the beneficiary's voting power. Only this program writes it, so the power
+// can't be transferred apart from the grant. Snapshots aren't refreshed by claims or cleared when the grant is
+// closed, consumers should require a recent slot and check that employee_account still belongs to beneficiary.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct VotingPower {
+    pub vesting_account: Pubkey,
+    pub registry_index: u64,
+    pub employee_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+    pub bump: u8,
+}
+
+// The time test-clock builds vest claims at, see current_time.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct TimeOverride {
+    pub unix_timestamp: Option<i64>,
+    pub bump: u8,
+}
+
+// Per-wallet settings of a beneficiary, shared by all of its grants.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct BeneficiaryRegistry {
+    pub beneficiary: Pubkey,
+    #[max_len(MAX_COMMUNICATION_POINTER_LEN)]
+    pub communication_pointer: Vec<u8>,
+    pub bump: u8,
+}
+
+// Marks a creator as allowed to create vesting accounts while permissioned creation is on.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct CreatorAllowlistEntry {
+    pub creator: Pubkey,
+    pub bump: u8,
+}
+
+// The wallets a beneficiary's claims may be paid to while the vesting account requires allowed destinations.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct AllowedDestinations {
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    #[max_len(MAX_ALLOWED_DESTINATIONS)]
+    pub wallets: Vec<Pubkey>,
+    // Asked about wallets that aren't listed, e.g. a KYC registry. None allows the listed wallets only.
+    pub allowlist_program: Option<Pubkey>,
+    pub bump: u8,
+}
+
+// Rolling claim metrics of a vesting account. daily_claimed is a ring buffer indexed by unix day,
+// holding the amount claimed on each of the last CLAIM_STATS_WINDOW_DAYS days.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct ClaimStats {
+    pub vesting_account: Pubkey,
+    pub total_claimed: u64,
+    pub claim_count: u64,
+    pub last_claim_day: i64,
+    pub daily_claimed: [u64; CLAIM_STATS_WINDOW_DAYS],
+    pub bump: u8,
+}
+
+impl ClaimStats {
+    pub fn record_claim(&mut self, now: i64, amount: u64) -> Result<()> {
+        let today = now.div_euclid(SECONDS_PER_DAY);
+        // Clear the buckets of the days without claims since the last one, all of them if it's outside the window.
+        let days_passed = today.saturating_sub(self.last_claim_day).min(CLAIM_STATS_WINDOW_DAYS as i64);
+        for day in (today - days_passed + 1)..=today {
+            self.daily_claimed[Self::bucket(day)] = 0;
+        }
+        self.last_claim_day = self.last_claim_day.max(today);
+
+        let bucket = &mut self.daily_claimed[Self::bucket(today)];
+        *bucket = bucket.checked_add(amount).ok_or(ErrorCode::CalculationOverflow)?;
+        self.total_claimed = self.total_claimed
+            .checked_add(amount)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+        self.claim_count = self.claim_count
+            .checked_add(1)
+            .ok_or(ErrorCode::CalculationOverflow)?;
+        Ok(())
+    }
+
+    // Returns the amount claimed during the last `days` days, today included. `days` is capped to the window.
+    pub fn claimed_in_last_days(&self, now: i64, days: usize) -> u64 {
+        let today = now.div_euclid(SECONDS_PER_DAY);
+        let days = days.min(CLAIM_STATS_WINDOW_DAYS) as i64;
+        ((today - days + 1)..=today)
+            .filter(|day| *day <= self.last_claim_day && self.last_claim_day - day < CLAIM_STATS_WINDOW_DAYS as i64)
+            .map(|day| self.daily_claimed[Self::bucket(day)])
+            .sum()
+    }
+
+    fn bucket(day: i64) -> usize {
+        day.rem_euclid(CLAIM_STATS_WINDOW_DAYS as i64) as usize
+    }
+}
+
+// A publicly shareable schedule curated by the protocol admin. Times are offsets from the grant's start_time.
 #[account]
 #[derive(InitSpace, Debug)]
-pub struct EmployeeAccount {
+pub struct ScheduleTemplate {
+    pub id: u64,
+    #[max_len(50)]
+    pub name: String,
+    pub cliff_offset_seconds: i64,
+    pub duration_seconds: i64,
+    pub schedule_kind: ScheduleKind,
+    pub vesting_interval_seconds: i64,
+    pub cliff_unlock_bps: u16,
+    pub is_active: bool,
+    pub bump: u8,
+}
+
+// Events are emitted on every state change so indexers can follow the program through its logs.
+#[event]
+pub struct VestingAccountCreated {
+    pub vesting_account: Pubkey,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub index: u64,
+    pub company_name: String,
+    pub deposit_lamports: u64,
+    pub dependency_failure_policy: DependencyFailurePolicy,
+}
+
+#[event]
+pub struct CompanyRenamed {
+    pub vesting_account: Pubkey,
+    pub previous_name: String,
+    pub company_name: String,
+}
+
+#[event]
+pub struct EmployeeVestingCreated {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
     pub beneficiary: Pubkey,
+    pub total_amount: u64,
     pub start_time: i64,
     pub end_time: i64,
-    pub total_amount: i64,
-    pub total_withdrawn: i64,
     pub cliff_time: i64,
+    pub schedule_kind: ScheduleKind,
+    pub template_id: Option<u64>,
+    // Non-zero for pool grants, total_amount is then the share of the pool at creation.
+    pub share_bps: u16,
+}
+
+#[event]
+pub struct GrantToppedUp {
+    pub employee_account: Pubkey,
     pub vesting_account: Pubkey,
-    pub bump: u8,
+    // Amount that reached the treasury and was added to the grant, after transfer fees.
+    pub amount: u64,
+    pub transfer_fee: u64,
+    pub total_amount: u64,
+}
+
+#[event]
+pub struct TreasuryAdded {
+    pub vesting_account: Pubkey,
+    pub mint: Pubkey,
+    pub treasury_token_account: Pubkey,
+}
+
+#[event]
+pub struct PriceFeedSet {
+    pub vesting_account: Pubkey,
+    pub mint: Pubkey,
+    pub price_feed: Option<Pubkey>,
+}
+
+#[event]
+pub struct TreasuryClosed {
+    pub vesting_account: Pubkey,
+    pub mint: Pubkey,
+    pub treasury_token_account: Pubkey,
+}
+
+#[event]
+pub struct GrantExported {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    // Amount moved to destination_token_account, the part of the grant that hadn't vested yet.
+    pub amount: u64,
+    // Vested but unclaimed amount paid to the beneficiary before the export, included in total_withdrawn.
+    pub paid_amount: u64,
+    pub total_withdrawn: u64,
+    pub vested_amount: u64,
+    pub destination_program: Pubkey,
+    pub destination_token_account: Pubkey,
+    // The grant's identifier in the destination program, e.g. the address of its grant account.
+    pub external_reference: Pubkey,
+}
+
+#[event]
+pub struct GrantAccepted {
+    pub employee_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub accepted_at: i64,
+}
+
+#[event]
+pub struct UnacceptedGrantCancelled {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+}
+
+#[event]
+pub struct ClaimDelegateSet {
+    pub employee_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub delegate: Option<Pubkey>,
+}
+
+#[event]
+pub struct ClaimDestinationSet {
+    pub employee_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub destination: Option<Pubkey>,
+}
+
+#[event]
+pub struct DestinationGateSet {
+    pub vesting_account: Pubkey,
+    pub require_allowed_destinations: bool,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+// Closing a beneficiary's AllowedDestinations is emitted as an empty allowlist.
+#[event]
+pub struct AllowedDestinationsSet {
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub wallets: Vec<Pubkey>,
+    pub allowlist_program: Option<Pubkey>,
+}
+
+#[event]
+pub struct VestedAmountSnapshotted {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct GrantImported {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    // Amount already withdrawn from the source program, which the grant starts from.
+    pub total_withdrawn: u64,
+    pub source_program: Pubkey,
+    pub source_account: Pubkey,
+}
+
+#[event]
+pub struct TokensClaimed {
+    pub employee_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub mint: Pubkey,
+    // Amount that left the treasury, the beneficiary receives amount - protocol_fee - transfer_fee.
+    pub amount: u64,
+    pub transfer_fee: u64,
+    // Share of amount paid to the config's fee recipient.
+    pub protocol_fee: u64,
+    // Amount of the grant that has not been claimed yet, whether it has vested or not.
+    pub remaining: u64,
+    // Price of the mint at claim time, recorded when the treasury has a price feed and the claim passed it.
+    pub price: Option<PriceSnapshot>,
+}
+
+#[event]
+pub struct OwnershipTransferProposed {
+    pub vesting_account: Pubkey,
+    pub owner: Pubkey,
+    pub pending_owner: Option<Pubkey>,
+}
+
+#[event]
+pub struct OwnershipTransferred {
+    pub vesting_account: Pubkey,
+    pub previous_owner: Pubkey,
+    pub new_owner: Pubkey,
+}
+
+#[event]
+pub struct VestingPauseSet {
+    pub vesting_account: Pubkey,
+    pub is_paused: bool,
+}
+
+#[event]
+pub struct BeneficiaryReassignmentPolicySet {
+    pub vesting_account: Pubkey,
+    pub owner_can_reassign_beneficiary: bool,
+}
+
+#[event]
+pub struct RoleAssigned {
+    pub vesting_account: Pubkey,
+    pub role: VestingRole,
+    pub previous: Pubkey,
+    pub key: Pubkey,
+}
+
+#[event]
+pub struct ClawbackDelaySet {
+    pub vesting_account: Pubkey,
+    pub clawback_delay_seconds: i64,
+}
+
+#[event]
+pub struct BeneficiaryReassigned {
+    pub vesting_account: Pubkey,
+    pub previous_employee_account: Pubkey,
+    pub new_employee_account: Pubkey,
+    pub previous_beneficiary: Pubkey,
+    pub new_beneficiary: Pubkey,
+}
+
+#[event]
+pub struct CommunicationPointerSet {
+    pub beneficiary: Pubkey,
+    pub pointer: Vec<u8>,
+}
+
+#[event]
+pub struct EmployeeVestingUpdated {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub previous_end_time: i64,
+    pub previous_total_amount: u64,
+    pub previous_cliff_time: i64,
+    pub end_time: i64,
+    pub total_amount: u64,
+    pub cliff_time: i64,
+}
+
+#[event]
+pub struct GrantRevoked {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    pub revoked_at: i64,
+    // Earliest time execute_clawback can be called, the end of the beneficiary's dispute window.
+    pub clawback_available_at: i64,
+    pub unvested_amount: u64,
+}
+
+#[event]
+pub struct RevocationRescinded {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+}
+
+#[event]
+pub struct ClawbackExecuted {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+    // Unvested amount returned to the treasury.
+    pub amount: u64,
+    pub total_amount: u64,
+}
+
+#[event]
+pub struct EmployeeAccountClosed {
+    pub employee_account: Pubkey,
+    pub vesting_account: Pubkey,
+    pub beneficiary: Pubkey,
+}
+
+#[event]
+pub struct VestingAccountClosed {
+    pub vesting_account: Pubkey,
+    pub owner: Pubkey,
+    pub refunded_deposit_lamports: u64,
+}
+
+#[event]
+pub struct ConfigInitialized {
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct PermissionedCreationSet {
+    pub enabled: bool,
+}
+
+#[event]
+pub struct FeatureToggled {
+    pub feature: Feature,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct CreationDepositSet {
+    pub lamports: u64,
+}
+
+#[event]
+pub struct TimeOverrideSet {
+    pub unix_timestamp: Option<i64>,
+}
+
+#[event]
+pub struct ClaimFeeSet {
+    pub fee_recipient: Pubkey,
+    pub claim_fee_bps: u16,
+}
+
+#[event]
+pub struct CreatorAllowlistUpdated {
+    pub creator: Pubkey,
+    pub allowed: bool,
+}
+
+#[event]
+pub struct ScheduleTemplateCreated {
+    pub schedule_template: Pubkey,
+    pub id: u64,
+    pub name: String,
+}
+
+#[event]
+pub struct ScheduleTemplateStatusChanged {
+    pub schedule_template: Pubkey,
+    pub id: u64,
+    pub is_active: bool,
 }
 
 // This error code attribute is applied to an enum to designate it as a collection of error codes.
@@ -247,4 +4938,662 @@ pub enum ErrorCode {
     InvalidVestingPeriod,
     #[msg("Calculation overflow.")]
     CalculationOverflow,
+    #[msg("Invalid vesting interval.")]
+    InvalidVestingInterval,
+    #[msg("Cliff unlock cannot exceed 100%.")]
+    InvalidCliffUnlock,
+    #[msg("Signer is not authorized to perform this action.")]
+    Unauthorized,
+    #[msg("Invalid schedule template.")]
+    InvalidTemplate,
+    #[msg("Schedule template is no longer active.")]
+    TemplateInactive,
+    #[msg("Invalid milestone schedule.")]
+    InvalidMilestones,
+    #[msg("Invalid vesting schedule.")]
+    InvalidSchedule,
+    #[msg("Invalid grant amount.")]
+    InvalidAmount,
+    #[msg("Grant has not been fully claimed yet.")]
+    GrantNotSettled,
+    #[msg("Vesting account still has active grants.")]
+    ActiveGrantsRemaining,
+    #[msg("Treasury still holds tokens.")]
+    TreasuryNotEmpty,
+    #[msg("Signer is not allowed to create vesting accounts.")]
+    CreatorNotAllowed,
+    #[msg("Signer is not the pending owner.")]
+    NotPendingOwner,
+    #[msg("This feature is not enabled on this deployment.")]
+    FeatureDisabled,
+    #[msg("Claims are paused for this vesting account.")]
+    VestingPaused,
+    #[msg("Invalid accounts passed to claim_all.")]
+    InvalidClaimAccounts,
+    #[msg("End time cannot be in the past.")]
+    EndTimeInPast,
+    #[msg("Amendment would reduce the amount already vested.")]
+    VestedAmountReduced,
+    #[msg("An external dependency of this vesting account is unavailable.")]
+    DependencyUnavailable,
+    #[msg("Communication pointer is too long.")]
+    CommunicationPointerTooLong,
+    #[msg("Signer is not the claim delegate of this grant.")]
+    NotClaimDelegate,
+    #[msg("Grants cannot be exported to this program.")]
+    InvalidExportProgram,
+    #[msg("Vesting account still has additional treasuries.")]
+    TreasuriesRemaining,
+    #[msg("The primary treasury is closed with the vesting account.")]
+    PrimaryTreasury,
+    #[msg("Grant has not been accepted by the beneficiary yet.")]
+    GrantNotAccepted,
+    #[msg("Grant has already been accepted.")]
+    GrantAlreadyAccepted,
+    #[msg("The grant can still be accepted.")]
+    AcceptanceWindowOpen,
+    #[msg("Vesting account already has the maximum number of treasuries.")]
+    TooManyTreasuries,
+    #[msg("Treasury does not hold enough unreserved tokens.")]
+    TreasuryOvercommitted,
+    #[msg("Vesting account has no treasury for this mint.")]
+    UnknownTreasury,
+    #[msg("Grant has been revoked.")]
+    GrantRevoked,
+    #[msg("Grant has not been revoked.")]
+    GrantNotRevoked,
+    #[msg("Clawback delay has not elapsed yet.")]
+    ClawbackDelayNotElapsed,
+    #[msg("Unvested tokens of this grant have already been clawed back.")]
+    GrantClawedBack,
+    #[msg("Clawback delay cannot be negative.")]
+    InvalidClawbackDelay,
+    #[msg("Action is not allowed in the grant's current status.")]
+    InvalidGrantTransition,
+    #[msg("Transaction deadline has passed.")]
+    DeadlineExceeded,
+    #[msg("Price update account does not match the treasury's price feed.")]
+    InvalidPriceFeed,
+    #[msg("This grant is not paid in native SOL.")]
+    NotASolGrant,
+    #[msg("Claims of this grant go to its registered claim destination, which was not passed.")]
+    ClaimDestinationMissing,
+    #[msg("Token account is not the grant's registered claim destination.")]
+    InvalidClaimDestination,
+    #[msg("Claim destination holds a different mint than the grant pays out in.")]
+    ClaimDestinationWrongMint,
+    #[msg("Beneficiary's token account now belongs to another owner. Register a claim destination to keep claiming.")]
+    TokenAccountReassigned,
+    #[msg("Claim destination token account is frozen.")]
+    ClaimDestinationFrozen,
+    #[msg("Requested amount exceeds what has vested and not been claimed yet.")]
+    ClaimAmountExceedsClaimable,
+    #[msg("Claim fee exceeds the maximum.")]
+    ClaimFeeTooHigh,
+    #[msg("This deployment charges a claim fee, the fee recipient's account was not passed.")]
+    FeeAccountMissing,
+    #[msg("Fee account does not belong to the config's fee recipient.")]
+    InvalidFeeAccount,
+    #[msg("claim_all cannot pay claim fees, use claim_tokens instead.")]
+    ClaimFeeUnsupported,
+    #[msg("Company name must be 1 to 50 letters, digits, single spaces or -_.,&'() characters.")]
+    InvalidCompanyName,
+    #[msg("Pool shares would exceed 100% of the treasury.")]
+    PoolOversubscribed,
+    #[msg("Treasury backs pool grants and fixed grants can't be mixed in it.")]
+    PoolTreasuryInUse,
+    #[msg("Pool grants can't be topped up, amended, revoked or exported.")]
+    PoolGrantUnsupported,
+    #[msg("Claim destination is not an allowed destination of the beneficiary.")]
+    DestinationNotAllowed,
+    #[msg("The beneficiary's allowlist program and record must be passed.")]
+    AllowlistProgramMissing,
+    #[msg("Too many allowed destinations.")]
+    TooManyAllowedDestinations,
+    #[msg("SOL vesting accounts can't require allowed destinations.")]
+    DestinationGateUnsupported,
+    #[msg("Account already has the current layout version.")]
+    AccountAlreadyMigrated,
+    #[msg("set_time_override only works in test-clock builds.")]
+    TestClockDisabled,
+    #[msg("Account has the original layout, migrate it with migrate_legacy_vesting_account or migrate_legacy_employee_account.")]
+    LegacyAccountLayout,
+    #[msg("Account doesn't have the original layout, or belongs to a vesting account that doesn't.")]
+    NotLegacyAccount,
+    #[msg("The company name of a migrated vesting account seeds its address and can't be changed.")]
+    LegacyCompanyName,
+    #[msg("Pool grants need the vesting account's primary treasury to compute their amount.")]
+    PoolTreasuryMissing,
+    #[msg("This vesting account does not vest native SOL.")]
+    NotASolVestingAccount,
+    #[msg("Another company already goes by this name.")]
+    CompanyNameTaken,
+    #[msg("Exported grants have to be escrowed by the destination program's grant_import PDA.")]
+    InvalidExportDestination,
+    #[msg("Vesting account already has a treasury for this mint.")]
+    TreasuryAlreadyExists,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Discriminator;
+
+    // Small xorshift generator, so the property checks below are reproducible without extra dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+    }
+
+    fn random_grant(rng: &mut Rng) -> EmployeeAccount {
+        let start_time = rng.below(1_000_000) as i64;
+        let duration = 1 + rng.below(200_000) as i64;
+        let end_time = start_time + duration;
+        let cliff_time = start_time + rng.below(duration as u64 + 1) as i64;
+        // Mostly tiny grants, where dust is a large share of the total, with the odd huge one.
+        let total_amount = match rng.below(3) {
+            0 => 1 + rng.below(10),
+            1 => 1 + rng.below(1_000_000),
+            // bps_of multiplies by up to BPS_DENOMINATOR, so bigger grants are rejected with CalculationOverflow.
+            _ => 1 + rng.below(u64::MAX / BPS_DENOMINATOR),
+        };
+        let schedule_kind = match rng.below(3) {
+            0 => ScheduleKind::Linear,
+            1 => ScheduleKind::Periodic,
+            _ => ScheduleKind::Lockup,
+        };
+        let vesting_interval_seconds = 1 + rng.below(duration as u64) as i64;
+        let cliff_unlock_bps = if rng.below(2) == 0 || schedule_kind == ScheduleKind::Lockup {
+            0
+        } else {
+            rng.below(BPS_DENOMINATOR + 1) as u16
+        };
+
+        EmployeeAccount {
+            beneficiary: Pubkey::default(),
+            start_time,
+            end_time,
+            total_amount,
+            total_withdrawn: 0,
+            cliff_time,
+            schedule_kind,
+            vesting_interval_seconds,
+            cliff_unlock_bps,
+            milestones: Vec::new(),
+            claim_delegate: None,
+            claim_destination: None,
+            accepted_at: Some(0),
+            acceptance_deadline: 0,
+            revoked_at: None,
+            clawed_back: false,
+            mint: Pubkey::default(),
+            vesting_account: Pubkey::default(),
+            registry_index: 0,
+            bump: 0,
+            share_bps: 0,
+            version: EMPLOYEE_ACCOUNT_VERSION,
+            clawback_available_at: None,
+            pool_base: 0,
+            reserved: [0; EMPLOYEE_ACCOUNT_RESERVED_BYTES],
+        }
+    }
+
+    // Fails when a field is added without taking its bytes out of `reserved`, which would make every existing
+    // account too small to deserialize.
+    #[test]
+    fn new_fields_use_reserved_bytes() {
+        assert_eq!(VestingAccount::INIT_SPACE, VESTING_ACCOUNT_SPACE);
+        assert_eq!(EmployeeAccount::INIT_SPACE, EMPLOYEE_ACCOUNT_SPACE);
+    }
+
+    #[test]
+    fn revocation_stops_vesting_and_clawback_keeps_vested_amount() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let mut grant = random_grant(&mut rng);
+            let revoked_at = grant.start_time + rng.below((grant.end_time - grant.start_time) as u64) as i64;
+            let vested_at_revocation = grant.vested_amount(revoked_at).unwrap();
+            grant.revoked_at = Some(revoked_at);
+            assert_eq!(grant.vested_amount(grant.end_time).unwrap(), vested_at_revocation);
+
+            grant.total_amount = vested_at_revocation;
+            grant.clawed_back = true;
+            assert_eq!(grant.vested_amount(grant.end_time).unwrap(), vested_at_revocation);
+            assert_eq!(grant.vested_amount(revoked_at).unwrap(), vested_at_revocation);
+        }
+    }
+
+    #[test]
+    fn claims_sum_to_total_amount() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2_000 {
+            let mut grant = random_grant(&mut rng);
+            let mut now = grant.start_time - 10;
+            let mut last_vested = 0;
+            while now <= grant.end_time {
+                let vested = grant.vested_amount(now).unwrap();
+                assert!(vested >= last_vested, "vesting went backwards for {grant:?} at {now}");
+                assert!(vested <= grant.total_amount, "vested more than the grant for {grant:?} at {now}");
+                last_vested = vested;
+
+                grant.total_withdrawn += grant.claimable_amount(now).unwrap();
+                assert_eq!(grant.total_withdrawn, vested);
+                now += 1 + rng.below((grant.end_time - grant.start_time) as u64 / 4 + 1) as i64;
+            }
+
+            grant.total_withdrawn += grant.claimable_amount(grant.end_time).unwrap();
+            assert_eq!(grant.total_withdrawn, grant.total_amount, "dust left over for {grant:?}");
+        }
+    }
+
+    #[test]
+    fn lockup_vests_nothing_before_end_time() {
+        let mut rng = Rng(0x5851_f42d_4c95_7f2d);
+        for _ in 0..500 {
+            let mut grant = random_grant(&mut rng);
+            grant.schedule_kind = ScheduleKind::Lockup;
+            grant.cliff_unlock_bps = 0;
+
+            let now = grant.start_time + rng.below((grant.end_time - grant.start_time) as u64) as i64;
+            assert_eq!(grant.vested_amount(now).unwrap(), 0);
+            assert_eq!(grant.vested_amount(grant.end_time).unwrap(), grant.total_amount);
+        }
+    }
+
+    #[test]
+    fn linear_accrual_is_within_one_unit_of_the_exact_rate() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2_000 {
+            let mut grant = random_grant(&mut rng);
+            grant.schedule_kind = ScheduleKind::Linear;
+            grant.cliff_time = grant.start_time;
+            grant.cliff_unlock_bps = 0;
+
+            let duration = (grant.end_time - grant.start_time) as u128;
+            let elapsed = rng.below(duration as u64) as i64;
+            let vested = grant.vested_amount(grant.start_time + elapsed).unwrap() as u128;
+            let exact_times_duration = grant.total_amount as u128 * elapsed as u128;
+            assert!(vested * duration <= exact_times_duration);
+            assert!(exact_times_duration < (vested + 1) * duration);
+        }
+    }
+
+    #[test]
+    fn company_names_are_validated_and_lowercased() {
+        assert_eq!(canonical_company_name("Acme").unwrap(), "acme");
+        assert_eq!(canonical_company_name("ACME").unwrap(), canonical_company_name("acme").unwrap());
+        assert_eq!(canonical_company_name("Smith & Sons (EU), Ltd.").unwrap(), "smith & sons (eu), ltd.");
+        assert!(canonical_company_name(&"a".repeat(MAX_COMPANY_NAME_LEN)).is_ok());
+        // Names that only differ in case reserve the same CompanyNameRecord.
+        assert_eq!(company_name_seed("Acme"), company_name_seed("ACME"));
+        assert_ne!(company_name_seed("Acme"), company_name_seed("Acme Co"));
+
+        let too_long = "a".repeat(MAX_COMPANY_NAME_LEN + 1);
+        // The last name starts with a Cyrillic A.
+        for name in ["", " Acme", "Acme ", "Ac  me", "Acme\n", "Acme\u{200b}", too_long.as_str(), "Аcme"] {
+            assert!(canonical_company_name(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn claim_fee_never_exceeds_the_claim() {
+        let mut config = Config {
+            admin: Pubkey::default(),
+            template_count: 0,
+            permissioned_creation: false,
+            creation_deposit_lamports: 0,
+            enabled_features: 0,
+            fee_recipient: Pubkey::default(),
+            claim_fee_bps: MAX_CLAIM_FEE_BPS,
+            bump: 0,
+        };
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let amount = 1 + rng.below(u64::MAX / BPS_DENOMINATOR);
+            let fee = claim_fee(Some(&config), amount).unwrap();
+            assert!(fee <= amount / (BPS_DENOMINATOR / MAX_CLAIM_FEE_BPS as u64));
+            assert_eq!(claim_fee(None, amount).unwrap(), 0);
+        }
+
+        config.claim_fee_bps = 0;
+        assert_eq!(claim_fee(Some(&config), 1_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn claim_fees_are_capped_and_paid_to_the_fee_recipient() {
+        let fee_recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut config = Config {
+            admin: Pubkey::default(),
+            template_count: 0,
+            permissioned_creation: false,
+            creation_deposit_lamports: 0,
+            enabled_features: 0,
+            fee_recipient: Pubkey::default(),
+            claim_fee_bps: 0,
+            bump: 0,
+        };
+
+        assert_eq!(config.set_claim_fee(fee_recipient, MAX_CLAIM_FEE_BPS + 1), Err(ErrorCode::ClaimFeeTooHigh.into()));
+        assert_eq!((config.fee_recipient, config.claim_fee_bps), (Pubkey::default(), 0));
+        config.set_claim_fee(fee_recipient, MAX_CLAIM_FEE_BPS).unwrap();
+        // 5% of the claim goes to the fee recipient, the beneficiary gets the rest.
+        assert_eq!(claim_fee(Some(&config), 1_000_000).unwrap(), 50_000);
+
+        assert!(check_fee_destination(Some(&config), fee_recipient, mint, mint).is_ok());
+        assert!(check_fee_destination(Some(&config), fee_recipient, NATIVE_SOL_MINT, NATIVE_SOL_MINT).is_ok());
+        for (fee_owner, fee_mint) in [(Pubkey::new_unique(), mint), (fee_recipient, Pubkey::new_unique())] {
+            assert_eq!(
+                check_fee_destination(Some(&config), fee_owner, fee_mint, mint),
+                Err(ErrorCode::InvalidFeeAccount.into())
+            );
+        }
+        assert_eq!(check_fee_destination(None, fee_recipient, mint, mint), Err(ErrorCode::InvalidFeeAccount.into()));
+    }
+
+    #[test]
+    fn only_allowed_destinations_receive_claims_up_to_the_claimable_amount() {
+        let listed = Pubkey::new_unique();
+        let unlisted = Pubkey::new_unique();
+        let mut allowed_destinations = AllowedDestinations {
+            vesting_account: Pubkey::default(),
+            beneficiary: Pubkey::default(),
+            wallets: vec![listed],
+            allowlist_program: None,
+            bump: 0,
+        };
+        assert_eq!(allowlist_program_to_ask(None, listed), Err(ErrorCode::DestinationNotAllowed.into()));
+        assert_eq!(allowlist_program_to_ask(Some(&allowed_destinations), listed), Ok(None));
+        assert_eq!(
+            allowlist_program_to_ask(Some(&allowed_destinations), unlisted),
+            Err(ErrorCode::DestinationNotAllowed.into())
+        );
+        let allowlist_program = Pubkey::new_unique();
+        allowed_destinations.allowlist_program = Some(allowlist_program);
+        assert_eq!(allowlist_program_to_ask(Some(&allowed_destinations), listed), Ok(None));
+        assert_eq!(allowlist_program_to_ask(Some(&allowed_destinations), unlisted), Ok(Some(allowlist_program)));
+
+        // A claim to an allowed destination can take part of what is claimable, but never more.
+        assert_eq!(claim_amount(400, None), Ok(400));
+        assert_eq!(claim_amount(400, Some(150)), Ok(150));
+        assert_eq!(claim_amount(400, Some(400)), Ok(400));
+        assert_eq!(claim_amount(400, Some(401)), Err(ErrorCode::ClaimAmountExceedsClaimable.into()));
+        assert_eq!(claim_amount(400, Some(0)), Err(ErrorCode::InvalidAmount.into()));
+    }
+
+    // Writes accounts byte by byte the way the original program laid them out, then migrates them the way
+    // migrate_legacy_vesting_account and migrate_legacy_employee_account do.
+    #[test]
+    fn legacy_accounts_migrate_to_the_current_layout() {
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let mut data = VestingAccount::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(owner.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        data.extend_from_slice(treasury.as_ref());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(b"Acme");
+        data.extend_from_slice(&[254, 253]);
+        // The original program allocated the full 50 bytes of company_name.
+        data.resize(8 + 32 * 3 + 4 + 50 + 1 + 1, 0);
+
+        let legacy: LegacyVestingAccount = decode_legacy_account(&data, &VestingAccount::DISCRIMINATOR).unwrap();
+        let mut migrated = vec![0; 8 + VESTING_ACCOUNT_SPACE];
+        legacy.into_current().try_serialize(&mut &mut migrated[..]).unwrap();
+        let vesting_account = VestingAccount::try_deserialize(&mut &migrated[..]).unwrap();
+        assert_eq!(vesting_account.owner, owner);
+        assert_eq!(vesting_account.grant_admin, owner);
+        assert_eq!(vesting_account.mint, mint);
+        assert_eq!(vesting_account.treasury_token_account, treasury);
+        assert_eq!(vesting_account.company_name, "Acme");
+        assert_eq!(vesting_account.canonical_name, "acme");
+        assert_eq!((vesting_account.treasury_bump, vesting_account.bump), (254, 253));
+        assert_eq!(vesting_account.version, VESTING_ACCOUNT_VERSION);
+        assert!(vesting_account.legacy_seeds);
+
+        let beneficiary = Pubkey::new_unique();
+        let vesting_account_key = Pubkey::new_unique();
+        let mut data = EmployeeAccount::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(beneficiary.as_ref());
+        for value in [1_000i64, 5_000, 400, 150, 2_000] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(vesting_account_key.as_ref());
+        data.push(252);
+        assert_eq!(data.len(), 8 + 32 + 8 * 5 + 32 + 1);
+
+        let legacy: LegacyEmployeeAccount = decode_legacy_account(&data, &EmployeeAccount::DISCRIMINATOR).unwrap();
+        let mut migrated = vec![0; 8 + EMPLOYEE_ACCOUNT_SPACE];
+        legacy.into_current(mint, 3).unwrap().try_serialize(&mut &mut migrated[..]).unwrap();
+        let grant = EmployeeAccount::try_deserialize(&mut &migrated[..]).unwrap();
+        assert_eq!(grant.beneficiary, beneficiary);
+        // Each migrated grant takes the next registry index, so its grant_entry and voting_power PDAs are its own.
+        assert_eq!(grant.registry_index, 3);
+        assert_eq!((grant.start_time, grant.end_time, grant.cliff_time), (1_000, 5_000, 2_000));
+        assert_eq!((grant.total_amount, grant.total_withdrawn), (400, 150));
+        assert_eq!(grant.vesting_account, vesting_account_key);
+        assert_eq!(grant.mint, mint);
+        assert_eq!(grant.bump, 252);
+        // Same linear vesting as the original claim_tokens: 200 of 400 halfway through, claimable past the cliff.
+        assert_eq!(grant.claimable_amount(3_000).unwrap(), 50);
+
+        // Accounts that already have a newer layout are rejected.
+        assert!(decode_legacy_account::<LegacyEmployeeAccount>(&migrated, &EmployeeAccount::DISCRIMINATOR).is_err());
+    }
+
+    #[test]
+    fn disabled_features_are_rejected() {
+        let features = [
+            Feature::OwnerBeneficiaryReassignment,
+            Feature::GrantExport,
+            Feature::GrantImport,
+            Feature::AccountMigration,
+            Feature::ClaimAll,
+        ];
+        let mut config = Config {
+            admin: Pubkey::default(),
+            template_count: 0,
+            permissioned_creation: false,
+            creation_deposit_lamports: 0,
+            enabled_features: 0,
+            fee_recipient: Pubkey::default(),
+            claim_fee_bps: 0,
+            bump: 0,
+        };
+        for feature in features {
+            // Self-deployments without a config have every feature off.
+            assert_eq!(require_feature(None, feature), Err(ErrorCode::FeatureDisabled.into()));
+            assert_eq!(require_feature(Some(&config), feature), Err(ErrorCode::FeatureDisabled.into()));
+        }
+        for enabled in features {
+            config.enabled_features = enabled.mask();
+            for feature in features {
+                assert_eq!(require_feature(Some(&config), feature).is_ok(), feature == enabled, "{feature:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn each_mint_gets_one_treasury() {
+        let mint = Pubkey::new_unique();
+        let mut vesting_account = LegacyVestingAccount {
+            owner: Pubkey::new_unique(),
+            mint,
+            treasury_token_account: Pubkey::new_unique(),
+            company_name: "Acme".to_string(),
+            treasury_bump: 255,
+            bump: 255,
+        }.into_current();
+
+        // The primary mint already has a treasury, even on a legacy account, where it sits at its original address.
+        assert_eq!(vesting_account.add_treasury(mint), Err(ErrorCode::TreasuryAlreadyExists.into()));
+        let other_mint = Pubkey::new_unique();
+        vesting_account.add_treasury(other_mint).unwrap();
+        assert_eq!(vesting_account.add_treasury(other_mint), Err(ErrorCode::TreasuryAlreadyExists.into()));
+        assert_eq!(vesting_account.treasuries.len(), 2);
+    }
+
+    // A feed's update counts as failed when it is missing or too old, each policy then handles that differently.
+    #[test]
+    fn dependency_failure_policy_applies_to_price_feeds() {
+        let mint = Pubkey::new_unique();
+        let price_feed = Pubkey::new_unique();
+        // Zeroed bytes read as every field's default, see VESTING_ACCOUNT_RESERVED_BYTES.
+        let mut vesting_account: VestingAccount = AnchorDeserialize::deserialize(&mut &[0; VESTING_ACCOUNT_SPACE][..]).unwrap();
+        assert_eq!(price_dependency(&vesting_account, mint, None, 1_000).unwrap(), (None, 1_000));
+        vesting_account.treasuries.push(TreasuryReserve { mint, reserved_amount: 0, price_feed: Some(price_feed) });
+
+        // A fully verified PriceUpdateV2: write authority, verification level, feed id, price, conf, expo, publish_time.
+        let published_at = 1_000i64;
+        let mut data = vec![0; 8 + 32];
+        data.push(1);
+        data.extend_from_slice(&[0; 32]);
+        data.extend_from_slice(&42i64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&(-2i32).to_le_bytes());
+        data.extend_from_slice(&published_at.to_le_bytes());
+        let mut lamports = 0;
+        let price_update = AccountInfo::new(
+            &price_feed,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &PYTH_RECEIVER_PROGRAM_ID,
+            false,
+            0
+        );
+        let price = PriceSnapshot { price: 42, expo: -2, publish_time: published_at };
+        let fresh = published_at + 60;
+        let stale = published_at + MAX_PRICE_AGE_SECONDS + 1;
+
+        for (policy, missing, stale_result) in [
+            (DependencyFailurePolicy::BlockClaims, None, None),
+            (DependencyFailurePolicy::VestedOnly, None, Some((None, published_at))),
+            (DependencyFailurePolicy::TimeBased, Some((None, stale)), Some((None, stale))),
+        ] {
+            vesting_account.dependency_failure_policy = policy;
+            let dependency = price_dependency(&vesting_account, mint, Some(&price_update), fresh);
+            assert_eq!(dependency.unwrap(), (Some(price), fresh));
+            assert_eq!(price_dependency(&vesting_account, mint, None, stale).ok(), missing);
+            assert_eq!(price_dependency(&vesting_account, mint, Some(&price_update), stale).ok(), stale_result);
+        }
+    }
+
+    // Deposits and claims in random order, the way claim_vested_tokens refreshes and claims pool grants.
+    #[test]
+    fn pool_grants_never_overdraw_the_treasury() {
+        let mut rng = Rng(0x5851_f42d_4c95_7f2d);
+        for _ in 0..500 {
+            let mut unallocated_bps = BPS_DENOMINATOR;
+            let mut grants: Vec<EmployeeAccount> = (0..1 + rng.below(4))
+                .map(|_| {
+                    let mut grant = random_grant(&mut rng);
+                    grant.share_bps = rng.below(unallocated_bps + 1) as u16;
+                    unallocated_bps -= grant.share_bps as u64;
+                    grant
+                })
+                .filter(|grant| grant.share_bps > 0)
+                .collect();
+            let end_time = grants.iter().map(|grant| grant.end_time).max().unwrap_or(0);
+            let mut balance = rng.below(1_000_000);
+            let mut pool_withdrawn = 0u64;
+
+            let mut now = 0;
+            while now <= end_time + 1 {
+                if rng.below(3) == 0 {
+                    balance += rng.below(1_000_000);
+                }
+                for grant in grants.iter_mut() {
+                    grant.refresh_pool_amount(balance + pool_withdrawn).unwrap();
+                    let amount = grant.claimable_amount(now).unwrap();
+                    assert!(amount <= balance, "pool overdrawn by {grant:?} at {now}");
+                    balance -= amount;
+                    pool_withdrawn += amount;
+                    grant.total_withdrawn += amount;
+                }
+                now += 1 + rng.below(end_time as u64 / 4 + 1) as i64;
+            }
+
+            // Once everything has vested, each grant has claimed exactly its share of the pool.
+            let pool_size = balance + pool_withdrawn;
+            for grant in grants.iter_mut() {
+                grant.refresh_pool_amount(pool_size).unwrap();
+                grant.total_withdrawn += grant.claimable_amount(end_time).unwrap();
+                assert_eq!(grant.total_withdrawn, bps_of(pool_size, grant.share_bps).unwrap());
+            }
+        }
+    }
+
+    // Pool grants closed once fully claimed, with their share handed out again from the pool size at the close, the
+    // way close_employee_account and create_pool_employee_vesting use pool_released_size.
+    #[test]
+    fn closed_pool_shares_can_be_handed_out_again() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..300 {
+            let mut grants: Vec<EmployeeAccount> = (0..1 + rng.below(3))
+                .map(|_| {
+                    let mut grant = random_grant(&mut rng);
+                    grant.share_bps = 1 + rng.below(BPS_DENOMINATOR / 4) as u16;
+                    grant
+                })
+                .collect();
+            let mut balance = rng.below(1_000_000);
+            let mut pool_withdrawn = 0u64;
+            let mut reissues = 0;
+
+            let mut now = 0;
+            while grants.iter().any(|grant| now <= grant.end_time) {
+                if rng.below(3) == 0 {
+                    balance += rng.below(1_000_000);
+                }
+                for grant in grants.iter_mut() {
+                    grant.refresh_pool_amount(balance + pool_withdrawn).unwrap();
+                    let amount = grant.claimable_amount(now).unwrap();
+                    assert!(amount <= balance, "pool overdrawn by {grant:?} at {now}");
+                    balance -= amount;
+                    pool_withdrawn += amount;
+                    grant.total_withdrawn += amount;
+
+                    // Fully claimed, so it can be closed and its share handed out to a grant starting now.
+                    if now >= grant.end_time && reissues < 3 && rng.below(2) == 0 {
+                        assert_eq!(grant.total_withdrawn, grant.total_amount);
+                        let pool_released_size = balance + pool_withdrawn;
+                        reissues += 1;
+                        let mut reissued = random_grant(&mut rng);
+                        reissued.start_time += now;
+                        reissued.end_time += now;
+                        reissued.cliff_time += now;
+                        reissued.share_bps = grant.share_bps;
+                        reissued.pool_base = pool_released_size;
+                        *grant = reissued;
+                    }
+                }
+                now += 1 + rng.below(50_000) as i64;
+            }
+
+            // Once everything has vested, each open grant has claimed exactly its share of what the pool gained
+            // since it was created.
+            let pool_size = balance + pool_withdrawn;
+            for grant in grants.iter_mut() {
+                grant.refresh_pool_amount(pool_size).unwrap();
+                grant.total_withdrawn += grant.claimable_amount(now).unwrap();
+                assert_eq!(
+                    grant.total_withdrawn,
+                    bps_of(pool_size - grant.pool_base, grant.share_bps).unwrap()
+                );
+            }
+        }
+    }
 }
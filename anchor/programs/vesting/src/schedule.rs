@@ -0,0 +1,270 @@
+// The vesting math as pure functions of a schedule and a timestamp, with no account or sysvar involved, so every
+// schedule kind can be checked exhaustively in unit tests. EmployeeAccount::vested_amount applies revocation and
+// clawback on top of this.
+use anchor_lang::prelude::*;
+
+use crate::{ bps_of, pro_rata, to_u64, ErrorCode, Milestone, ScheduleKind };
+
+// The unlock curve of a grant, as stored in its EmployeeAccount.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Schedule<'a> {
+    pub kind: ScheduleKind,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub cliff_time: i64,
+    pub total_amount: u64,
+    // Length of one tranche for periodic schedules, ignored otherwise.
+    pub vesting_interval_seconds: i64,
+    // Share of total_amount, in basis points, that unlocks all at once at cliff_time.
+    pub cliff_unlock_bps: u16,
+    // Unlock curve of milestone schedules, ignored otherwise.
+    pub milestones: &'a [Milestone],
+}
+
+// Returns the total amount vested at `now`, including anything that has already been withdrawn.
+pub fn vested_amount(schedule: &Schedule, now: i64) -> Result<u64> {
+    let total_vesting_time = schedule.end_time.saturating_sub(schedule.start_time);
+    if total_vesting_time <= 0 {
+        return Err(ErrorCode::InvalidVestingPeriod.into());
+    }
+    if now >= schedule.end_time {
+        return Ok(schedule.total_amount);
+    }
+    // A lockup has no accrual and no cliff unlock, it is all or nothing at end_time.
+    if schedule.kind == ScheduleKind::Lockup {
+        return Ok(0);
+    }
+    if now < schedule.cliff_time {
+        return Ok(0);
+    }
+    // Milestone schedules carry their own unlock curve, so neither the cliff unlock nor accrual applies.
+    if schedule.kind == ScheduleKind::Milestone {
+        return milestone_vested_amount(schedule, now);
+    }
+
+    // With a cliff unlock, the cliff portion unlocks at cliff_time and only the remainder accrues,
+    // starting from the cliff rather than retroactively from start_time.
+    let (cliff_amount, accrual_start) = if schedule.cliff_unlock_bps > 0 {
+        (bps_of(schedule.total_amount, schedule.cliff_unlock_bps)?, schedule.cliff_time)
+    } else {
+        (0, schedule.start_time)
+    };
+
+    // saturating_sub ensures that the subtraction does not go below zero, which can prevent underflow errors.
+    // max(0) keeps a claim made before start_time from producing a negative amount.
+    let elapsed = to_u64(now.saturating_sub(accrual_start).max(0))?;
+    let duration = to_u64(schedule.end_time.saturating_sub(accrual_start))?;
+    let remaining_amount = schedule.total_amount
+        .checked_sub(cliff_amount)
+        .ok_or(ErrorCode::CalculationOverflow)?;
+    let accrued_amount = accrued_amount(schedule, remaining_amount, elapsed, duration)?;
+
+    cliff_amount.checked_add(accrued_amount).ok_or_else(|| ErrorCode::CalculationOverflow.into())
+}
+
+// Returns how much of `amount` has accrued after `elapsed` seconds out of `duration`, following the schedule kind.
+// Linear accrual is computed from the start of accrual rather than per second, so the rounding dust of one second
+// is carried into the next instead of being dropped: the amount accrued so far is never more than one base unit
+// behind the exact rate. Periodic tranches are all the same size and the last one, unlocked at end_time by
+// vested_amount, makes up the remainder. Either way vesting reaches exactly total_amount at end_time.
+fn accrued_amount(schedule: &Schedule, amount: u64, elapsed: u64, duration: u64) -> Result<u64> {
+    match schedule.kind {
+        ScheduleKind::Linear => pro_rata(amount, elapsed, duration),
+        ScheduleKind::Periodic => {
+            if schedule.vesting_interval_seconds <= 0 {
+                return Err(ErrorCode::InvalidVestingInterval.into());
+            }
+            let interval = to_u64(schedule.vesting_interval_seconds)?;
+            // Only whole intervals count, a partially elapsed interval unlocks nothing.
+            let intervals_elapsed = elapsed / interval;
+            // Round up so a trailing partial interval still counts as a tranche.
+            let total_intervals = duration.div_ceil(interval);
+            let per_interval_amount = amount / total_intervals;
+            intervals_elapsed.checked_mul(per_interval_amount).ok_or_else(|| ErrorCode::CalculationOverflow.into())
+        }
+        // Milestone schedules are handled by milestone_vested_amount and never accrue over time.
+        ScheduleKind::Milestone => Err(ErrorCode::InvalidMilestones.into()),
+        // Lockups are handled by vested_amount before accrual and never accrue either.
+        ScheduleKind::Lockup => Ok(0),
+    }
+}
+
+// Walks the milestones and returns the share of total_amount unlocked by the latest one that has passed.
+fn milestone_vested_amount(schedule: &Schedule, now: i64) -> Result<u64> {
+    let unlocked_bps = schedule.milestones
+        .iter()
+        .take_while(|milestone| milestone.timestamp <= now)
+        .last()
+        .map_or(0, |milestone| milestone.cumulative_bps);
+
+    bps_of(schedule.total_amount, unlocked_bps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [ScheduleKind; 4] = [
+        ScheduleKind::Linear,
+        ScheduleKind::Periodic,
+        ScheduleKind::Milestone,
+        ScheduleKind::Lockup,
+    ];
+
+    const MILESTONES: [Milestone; 3] = [
+        Milestone { timestamp: 100, cumulative_bps: 1_000 },
+        Milestone { timestamp: 200, cumulative_bps: 3_000 },
+        Milestone { timestamp: 300, cumulative_bps: 10_000 },
+    ];
+
+    // 1_000 tokens from 0 to 300, no cliff, in tranches of 100 seconds for periodic schedules.
+    fn schedule(kind: ScheduleKind) -> Schedule<'static> {
+        Schedule {
+            kind,
+            start_time: 0,
+            end_time: 300,
+            cliff_time: 0,
+            total_amount: 1_000,
+            vesting_interval_seconds: 100,
+            cliff_unlock_bps: 0,
+            milestones: if kind == ScheduleKind::Milestone { &MILESTONES[..] } else { &[] },
+        }
+    }
+
+    fn vested(schedule: &Schedule, now: i64) -> u64 {
+        vested_amount(schedule, now).unwrap()
+    }
+
+    #[test]
+    fn every_kind_is_empty_at_start_and_full_at_end() {
+        for kind in KINDS {
+            let schedule = schedule(kind);
+            assert_eq!(vested(&schedule, -1_000), 0, "{kind:?}");
+            assert_eq!(vested(&schedule, 0), 0, "{kind:?}");
+            assert_eq!(vested(&schedule, 300), 1_000, "{kind:?}");
+            assert_eq!(vested(&schedule, i64::MAX), 1_000, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn every_kind_vests_monotonically() {
+        for kind in KINDS {
+            let schedule = schedule(kind);
+            let mut previous = 0;
+            for now in -10..=310 {
+                let amount = vested(&schedule, now);
+                assert!(amount >= previous, "{kind:?} at {now}");
+                assert!(amount <= schedule.total_amount, "{kind:?} at {now}");
+                previous = amount;
+            }
+        }
+    }
+
+    #[test]
+    fn linear_accrues_every_second() {
+        let schedule = schedule(ScheduleKind::Linear);
+        assert_eq!(vested(&schedule, 1), 3);
+        assert_eq!(vested(&schedule, 150), 500);
+        assert_eq!(vested(&schedule, 299), 996);
+    }
+
+    #[test]
+    fn periodic_unlocks_whole_tranches_only() {
+        let schedule = schedule(ScheduleKind::Periodic);
+        assert_eq!(vested(&schedule, 99), 0);
+        assert_eq!(vested(&schedule, 100), 333);
+        assert_eq!(vested(&schedule, 199), 333);
+        assert_eq!(vested(&schedule, 200), 666);
+    }
+
+    #[test]
+    fn periodic_final_tranche_trues_up_to_total_amount() {
+        let schedule = Schedule { total_amount: 1_001, ..schedule(ScheduleKind::Periodic) };
+        assert_eq!(vested(&schedule, 100), 333);
+        assert_eq!(vested(&schedule, 200), 666);
+        assert_eq!(vested(&schedule, 299), 666);
+        assert_eq!(vested(&schedule, 300), 1_001);
+    }
+
+    #[test]
+    fn periodic_counts_a_trailing_partial_tranche() {
+        let schedule = Schedule { end_time: 250, ..schedule(ScheduleKind::Periodic) };
+        assert_eq!(vested(&schedule, 100), 333);
+        assert_eq!(vested(&schedule, 200), 666);
+        assert_eq!(vested(&schedule, 250), 1_000);
+    }
+
+    #[test]
+    fn periodic_rejects_a_non_positive_interval() {
+        for vesting_interval_seconds in [0, -1] {
+            let schedule = Schedule { vesting_interval_seconds, ..schedule(ScheduleKind::Periodic) };
+            assert!(vested_amount(&schedule, 150).is_err());
+        }
+    }
+
+    #[test]
+    fn milestones_unlock_their_cumulative_share() {
+        let schedule = schedule(ScheduleKind::Milestone);
+        assert_eq!(vested(&schedule, 99), 0);
+        assert_eq!(vested(&schedule, 100), 100);
+        assert_eq!(vested(&schedule, 250), 300);
+        assert_eq!(vested(&schedule, 300), 1_000);
+    }
+
+    #[test]
+    fn lockup_unlocks_everything_at_end_time() {
+        let schedule = Schedule { cliff_time: 100, ..schedule(ScheduleKind::Lockup) };
+        assert_eq!(vested(&schedule, 150), 0);
+        assert_eq!(vested(&schedule, 299), 0);
+        assert_eq!(vested(&schedule, 300), 1_000);
+    }
+
+    #[test]
+    fn cliff_blocks_every_kind_until_cliff_time() {
+        for kind in KINDS {
+            let schedule = Schedule { cliff_time: 150, ..schedule(kind) };
+            assert_eq!(vested(&schedule, 149), 0, "{kind:?}");
+        }
+        // Without a cliff unlock, linear and periodic accrual is retroactive from start_time.
+        assert_eq!(vested(&Schedule { cliff_time: 150, ..schedule(ScheduleKind::Linear) }, 150), 500);
+        assert_eq!(vested(&Schedule { cliff_time: 150, ..schedule(ScheduleKind::Periodic) }, 150), 333);
+    }
+
+    #[test]
+    fn cliff_unlock_is_released_at_once_and_the_rest_accrues_from_the_cliff() {
+        let linear = Schedule { cliff_time: 100, cliff_unlock_bps: 2_500, ..schedule(ScheduleKind::Linear) };
+        assert_eq!(vested(&linear, 99), 0);
+        assert_eq!(vested(&linear, 100), 250);
+        assert_eq!(vested(&linear, 200), 625);
+
+        let periodic = Schedule { cliff_time: 100, cliff_unlock_bps: 2_500, ..schedule(ScheduleKind::Periodic) };
+        assert_eq!(vested(&periodic, 100), 250);
+        assert_eq!(vested(&periodic, 199), 250);
+        assert_eq!(vested(&periodic, 200), 625);
+    }
+
+    #[test]
+    fn cliff_unlock_does_not_apply_to_milestones() {
+        let schedule = Schedule { cliff_unlock_bps: 5_000, ..schedule(ScheduleKind::Milestone) };
+        assert_eq!(vested(&schedule, 100), 100);
+    }
+
+    #[test]
+    fn empty_vesting_period_is_rejected() {
+        for kind in KINDS {
+            for end_time in [0, -1] {
+                let schedule = Schedule { end_time, ..schedule(kind) };
+                assert!(vested_amount(&schedule, 0).is_err(), "{kind:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn large_amounts_do_not_overflow() {
+        for kind in [ScheduleKind::Linear, ScheduleKind::Periodic] {
+            let schedule = Schedule { total_amount: u64::MAX, ..schedule(kind) };
+            assert!(vested(&schedule, 150) <= u64::MAX / 2 + 1, "{kind:?}");
+            assert_eq!(vested(&schedule, 300), u64::MAX, "{kind:?}");
+        }
+    }
+}
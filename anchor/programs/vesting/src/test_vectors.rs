@@ -0,0 +1,187 @@
+// Deterministic test vectors for the vesting math, so wallets and indexers that reimplement
+// EmployeeAccount::vested_amount can check their results against the program's own.
+// Generate them with `cargo run --example test_vectors --features test-vectors`.
+use crate::{ EmployeeAccount, Milestone, ScheduleKind, EMPLOYEE_ACCOUNT_RESERVED_BYTES, EMPLOYEE_ACCOUNT_VERSION };
+
+// Bump this whenever a case is added or the output format changes.
+pub const TEST_VECTORS_VERSION: u32 = 4;
+
+struct Case {
+    name: &'static str,
+    grant: EmployeeAccount,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn grant(
+    start_time: i64,
+    end_time: i64,
+    cliff_time: i64,
+    total_amount: u64,
+    schedule_kind: ScheduleKind,
+    vesting_interval_seconds: i64,
+    cliff_unlock_bps: u16,
+    milestones: Vec<Milestone>
+) -> EmployeeAccount {
+    EmployeeAccount {
+        beneficiary: Default::default(),
+        start_time,
+        end_time,
+        total_amount,
+        total_withdrawn: 0,
+        cliff_time,
+        schedule_kind,
+        vesting_interval_seconds,
+        cliff_unlock_bps,
+        milestones,
+        claim_delegate: None,
+        claim_destination: None,
+        accepted_at: Some(0),
+        acceptance_deadline: 0,
+        revoked_at: None,
+        clawed_back: false,
+        mint: Default::default(),
+        vesting_account: Default::default(),
+        registry_index: 0,
+        bump: 0,
+        share_bps: 0,
+        version: EMPLOYEE_ACCOUNT_VERSION,
+        clawback_available_at: None,
+        pool_base: 0,
+        reserved: [0; EMPLOYEE_ACCOUNT_RESERVED_BYTES],
+    }
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case { name: "linear_no_cliff", grant: grant(0, 100, 0, 1_000, ScheduleKind::Linear, 0, 0, vec![]) },
+        Case { name: "linear_with_cliff", grant: grant(0, 1_000, 250, 10_000, ScheduleKind::Linear, 0, 0, vec![]) },
+        Case {
+            name: "linear_rounding_down",
+            grant: grant(1_000, 1_007, 1_000, 10, ScheduleKind::Linear, 0, 0, vec![]),
+        },
+        Case {
+            name: "linear_large_amount",
+            grant: grant(0, 126_144_000, 0, u64::MAX / 2, ScheduleKind::Linear, 0, 0, vec![]),
+        },
+        Case { name: "periodic_even", grant: grant(0, 120, 0, 1_200, ScheduleKind::Periodic, 30, 0, vec![]) },
+        Case {
+            name: "periodic_trailing_partial_interval",
+            grant: grant(0, 100, 0, 1_000, ScheduleKind::Periodic, 30, 0, vec![]),
+        },
+        Case {
+            name: "periodic_remainder_at_end",
+            grant: grant(0, 90, 0, 100, ScheduleKind::Periodic, 30, 0, vec![]),
+        },
+        Case {
+            name: "linear_cliff_unlock",
+            grant: grant(0, 1_000, 250, 10_000, ScheduleKind::Linear, 0, 2_500, vec![]),
+        },
+        Case {
+            name: "periodic_cliff_unlock",
+            grant: grant(0, 400, 100, 4_000, ScheduleKind::Periodic, 100, 1_000, vec![]),
+        },
+        Case {
+            name: "milestone_back_loaded",
+            grant: grant(0, 300, 100, 1_000, ScheduleKind::Milestone, 0, 0, vec![
+                Milestone { timestamp: 100, cumulative_bps: 1_000 },
+                Milestone { timestamp: 200, cumulative_bps: 3_000 },
+                Milestone { timestamp: 300, cumulative_bps: 10_000 },
+            ]),
+        },
+        Case {
+            name: "milestone_front_loaded",
+            grant: grant(0, 300, 50, 999, ScheduleKind::Milestone, 0, 0, vec![
+                Milestone { timestamp: 50, cumulative_bps: 6_000 },
+                Milestone { timestamp: 150, cumulative_bps: 6_000 },
+                Milestone { timestamp: 300, cumulative_bps: 10_000 },
+            ]),
+        },
+        Case { name: "lockup", grant: grant(0, 1_000, 250, 10_000, ScheduleKind::Lockup, 0, 0, vec![]) },
+        Case { name: "zero_length_period", grant: grant(100, 100, 100, 1_000, ScheduleKind::Linear, 0, 0, vec![]) }
+    ]
+}
+
+// Probes every grant just around the points where its schedule changes behaviour.
+fn timestamps(grant: &EmployeeAccount) -> Vec<i64> {
+    let mut points = vec![
+        grant.start_time - 1,
+        grant.start_time,
+        grant.cliff_time - 1,
+        grant.cliff_time,
+        grant.cliff_time + 1,
+        grant.start_time + (grant.end_time - grant.start_time) / 2,
+        grant.end_time - 1,
+        grant.end_time,
+        grant.end_time + 1
+    ];
+    if grant.vesting_interval_seconds > 0 {
+        let mut tranche = grant.cliff_time;
+        while tranche < grant.end_time {
+            points.extend([tranche - 1, tranche]);
+            tranche += grant.vesting_interval_seconds;
+        }
+    }
+    points.extend(grant.milestones.iter().flat_map(|m| [m.timestamp - 1, m.timestamp]));
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+fn schedule_kind_name(kind: ScheduleKind) -> &'static str {
+    match kind {
+        ScheduleKind::Linear => "linear",
+        ScheduleKind::Periodic => "periodic",
+        ScheduleKind::Milestone => "milestone",
+        ScheduleKind::Lockup => "lockup",
+    }
+}
+
+// Renders the vectors as canonical JSON: fixed key order, no insignificant whitespace beyond one
+// case per line, and amounts as strings since they don't fit in a JavaScript number.
+pub fn generate_test_vectors() -> String {
+    let mut out = format!("{{\"version\":{},\"cases\":[\n", TEST_VECTORS_VERSION);
+    let cases = cases();
+    for (i, case) in cases.iter().enumerate() {
+        let g = &case.grant;
+        let milestones = g.milestones
+            .iter()
+            .map(|m| format!("{{\"timestamp\":{},\"cumulative_bps\":{}}}", m.timestamp, m.cumulative_bps))
+            .collect::<Vec<_>>()
+            .join(",");
+        let expected = timestamps(g)
+            .into_iter()
+            .map(|now| {
+                match g.vested_amount(now) {
+                    Ok(amount) => format!("{{\"now\":{},\"vested_amount\":\"{}\"}}", now, amount),
+                    Err(err) => format!("{{\"now\":{},\"error\":\"{}\"}}", now, error_name(err)),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        out.push_str(
+            &format!(
+                "{{\"name\":\"{}\",\"start_time\":{},\"end_time\":{},\"cliff_time\":{},\"total_amount\":\"{}\",\"schedule_kind\":\"{}\",\"vesting_interval_seconds\":{},\"cliff_unlock_bps\":{},\"milestones\":[{}],\"expected\":[{}]}}",
+                case.name,
+                g.start_time,
+                g.end_time,
+                g.cliff_time,
+                g.total_amount,
+                schedule_kind_name(g.schedule_kind),
+                g.vesting_interval_seconds,
+                g.cliff_unlock_bps,
+                milestones,
+                expected
+            )
+        );
+        out.push_str(if i + 1 < cases.len() { ",\n" } else { "\n" });
+    }
+    out.push_str("]}\n");
+    out
+}
+
+fn error_name(err: anchor_lang::error::Error) -> String {
+    match err {
+        anchor_lang::error::Error::AnchorError(e) => e.error_name,
+        anchor_lang::error::Error::ProgramError(e) => e.to_string(),
+    }
+}
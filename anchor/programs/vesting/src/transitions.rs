@@ -0,0 +1,431 @@
+// The lifecycle of a grant. A grant's status isn't stored, it follows from its fields and its vesting account, and
+// every instruction that acts on an existing grant asks `guard` first whether its action is allowed in that status.
+// The match in `check` lists every action for every status, so a new instruction can't compile without deciding
+// where it is allowed.
+//
+//   Offered --accept--> Active <--set_paused--> Paused
+//   Active/Paused --revoke--> Revoked --execute_clawback--> ClawedBack
+//   Revoked --rescind_revocation--> Active/Paused
+//   Active/Paused/ClawedBack --claim everything--> Completed --top_up/amend--> Active/Paused
+use anchor_lang::prelude::*;
+
+use crate::{ EmployeeAccount, ErrorCode, VestingAccount };
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GrantStatus {
+    // Created, but the beneficiary hasn't accepted it yet.
+    Offered,
+    // Accepted and vesting.
+    Active,
+    // Accepted, but the vesting account is paused so nothing can be claimed.
+    Paused,
+    // Vesting stopped, the unvested rest is waiting for execute_clawback.
+    Revoked,
+    // The unvested rest went back to the treasury, what had vested can still be claimed.
+    ClawedBack,
+    // Everything has been withdrawn, the grant can be closed.
+    Completed,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GrantAction {
+    Accept,
+    CancelOffer,
+    Claim,
+    SetClaimDelegate,
+    SetClaimDestination,
+    ReassignBeneficiary,
+    TopUp,
+    Amend,
+    Revoke,
+    RescindRevocation,
+    ExecuteClawback,
+    Export,
+    Close,
+}
+
+impl GrantStatus {
+    pub fn of(grant: &EmployeeAccount, vesting_account: &VestingAccount) -> Self {
+        if grant.total_withdrawn >= grant.total_amount {
+            GrantStatus::Completed
+        } else if grant.accepted_at.is_none() {
+            GrantStatus::Offered
+        } else if grant.clawed_back {
+            GrantStatus::ClawedBack
+        } else if grant.revoked_at.is_some() {
+            GrantStatus::Revoked
+        } else if vesting_account.is_paused {
+            GrantStatus::Paused
+        } else {
+            GrantStatus::Active
+        }
+    }
+}
+
+// Fails with the error the action has always failed with in that status, so clients see the same error codes.
+pub fn check(status: GrantStatus, action: GrantAction) -> Result<()> {
+    use GrantAction::*;
+
+    let error = match status {
+        GrantStatus::Offered =>
+            match action {
+                Accept | CancelOffer | SetClaimDelegate | SetClaimDestination | ReassignBeneficiary | TopUp | Amend => None,
+                Claim | Revoke | Export => Some(ErrorCode::GrantNotAccepted),
+                RescindRevocation | ExecuteClawback => Some(ErrorCode::GrantNotRevoked),
+                Close => Some(ErrorCode::GrantNotSettled),
+            }
+        GrantStatus::Active =>
+            match action {
+                Claim | SetClaimDelegate | SetClaimDestination | ReassignBeneficiary | TopUp | Amend | Revoke | Export =>
+                    None,
+                Accept | CancelOffer => Some(ErrorCode::GrantAlreadyAccepted),
+                RescindRevocation | ExecuteClawback => Some(ErrorCode::GrantNotRevoked),
+                Close => Some(ErrorCode::GrantNotSettled),
+            }
+        GrantStatus::Paused =>
+            match action {
+                SetClaimDelegate | SetClaimDestination | ReassignBeneficiary | TopUp | Amend | Revoke => None,
+                Claim | Export => Some(ErrorCode::VestingPaused),
+                Accept | CancelOffer => Some(ErrorCode::GrantAlreadyAccepted),
+                RescindRevocation | ExecuteClawback => Some(ErrorCode::GrantNotRevoked),
+                Close => Some(ErrorCode::GrantNotSettled),
+            }
+        GrantStatus::Revoked =>
+            match action {
+                Claim | SetClaimDelegate | SetClaimDestination | ReassignBeneficiary | RescindRevocation | ExecuteClawback =>
+                    None,
+                TopUp | Amend | Revoke | Export => Some(ErrorCode::GrantRevoked),
+                Accept | CancelOffer => Some(ErrorCode::GrantAlreadyAccepted),
+                Close => Some(ErrorCode::GrantNotSettled),
+            }
+        GrantStatus::ClawedBack =>
+            match action {
+                Claim | SetClaimDelegate | SetClaimDestination | ReassignBeneficiary => None,
+                TopUp | Amend | Revoke | RescindRevocation | ExecuteClawback | Export =>
+                    Some(ErrorCode::GrantClawedBack),
+                Accept | CancelOffer => Some(ErrorCode::GrantAlreadyAccepted),
+                Close => Some(ErrorCode::GrantNotSettled),
+            }
+        GrantStatus::Completed =>
+            match action {
+                Close | SetClaimDelegate | SetClaimDestination | ReassignBeneficiary | TopUp | Amend | Export => None,
+                Claim => Some(ErrorCode::NothingToClaim),
+                Accept | CancelOffer | Revoke | RescindRevocation | ExecuteClawback =>
+                    Some(ErrorCode::InvalidGrantTransition),
+            }
+    };
+    match error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+pub fn guard(grant: &EmployeeAccount, vesting_account: &VestingAccount, action: GrantAction) -> Result<()> {
+    // Pausing is an emergency stop for every claim of the vesting account, revoked grants included. Exports pay out
+    // the vested part, so they stop too.
+    if matches!(action, GrantAction::Claim | GrantAction::Export) && vesting_account.is_paused {
+        return Err(ErrorCode::VestingPaused.into());
+    }
+    // A pool grant is worth a share of the pool rather than an amount, so there is no amount to add to, change,
+    // claw back or move to another program.
+    if grant.share_bps > 0
+        && matches!(
+            action,
+            GrantAction::TopUp
+                | GrantAction::Amend
+                | GrantAction::Revoke
+                | GrantAction::RescindRevocation
+                | GrantAction::ExecuteClawback
+                | GrantAction::Export
+        )
+    {
+        return Err(ErrorCode::PoolGrantUnsupported.into());
+    }
+    check(GrantStatus::of(grant, vesting_account), action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use GrantAction::*;
+    use GrantStatus::*;
+
+    const STATUSES: [GrantStatus; 6] = [Offered, Active, Paused, Revoked, ClawedBack, Completed];
+    const ACTIONS: [GrantAction; 13] = [
+        Accept,
+        CancelOffer,
+        Claim,
+        SetClaimDelegate,
+        SetClaimDestination,
+        ReassignBeneficiary,
+        TopUp,
+        Amend,
+        Revoke,
+        RescindRevocation,
+        ExecuteClawback,
+        Export,
+        Close,
+    ];
+
+    // Written out separately from `check`, so a change to either one shows up here.
+    const ALLOWED: &[(GrantStatus, GrantAction)] = &[
+        (Offered, Accept),
+        (Offered, CancelOffer),
+        (Offered, SetClaimDelegate),
+        (Offered, SetClaimDestination),
+        (Offered, ReassignBeneficiary),
+        (Offered, TopUp),
+        (Offered, Amend),
+        (Active, Claim),
+        (Active, SetClaimDelegate),
+        (Active, SetClaimDestination),
+        (Active, ReassignBeneficiary),
+        (Active, TopUp),
+        (Active, Amend),
+        (Active, Revoke),
+        (Active, Export),
+        (Paused, SetClaimDelegate),
+        (Paused, SetClaimDestination),
+        (Paused, ReassignBeneficiary),
+        (Paused, TopUp),
+        (Paused, Amend),
+        (Paused, Revoke),
+        (Revoked, Claim),
+        (Revoked, SetClaimDelegate),
+        (Revoked, SetClaimDestination),
+        (Revoked, ReassignBeneficiary),
+        (Revoked, RescindRevocation),
+        (Revoked, ExecuteClawback),
+        (ClawedBack, Claim),
+        (ClawedBack, SetClaimDelegate),
+        (ClawedBack, SetClaimDestination),
+        (ClawedBack, ReassignBeneficiary),
+        (Completed, SetClaimDelegate),
+        (Completed, SetClaimDestination),
+        (Completed, ReassignBeneficiary),
+        (Completed, TopUp),
+        (Completed, Amend),
+        (Completed, Export),
+        (Completed, Close),
+    ];
+
+    fn grant() -> EmployeeAccount {
+        EmployeeAccount {
+            beneficiary: Pubkey::default(),
+            start_time: 0,
+            end_time: 100,
+            total_amount: 100,
+            total_withdrawn: 0,
+            cliff_time: 0,
+            schedule_kind: crate::ScheduleKind::Linear,
+            vesting_interval_seconds: 0,
+            cliff_unlock_bps: 0,
+            milestones: Vec::new(),
+            claim_delegate: None,
+            claim_destination: None,
+            accepted_at: Some(0),
+            acceptance_deadline: 0,
+            revoked_at: None,
+            clawed_back: false,
+            mint: Pubkey::default(),
+            vesting_account: Pubkey::default(),
+            registry_index: 0,
+            bump: 0,
+            share_bps: 0,
+            version: crate::EMPLOYEE_ACCOUNT_VERSION,
+            clawback_available_at: None,
+            pool_base: 0,
+            reserved: [0; crate::EMPLOYEE_ACCOUNT_RESERVED_BYTES],
+        }
+    }
+
+    fn vesting_account(is_paused: bool) -> VestingAccount {
+        VestingAccount {
+            owner: Pubkey::default(),
+            creator: Pubkey::default(),
+            grant_admin: Pubkey::default(),
+            revoke_admin: Pubkey::default(),
+            treasury_admin: Pubkey::default(),
+            index: 0,
+            mint: Pubkey::default(),
+            treasury_token_account: Pubkey::default(),
+            company_name: String::new(),
+            treasury_bump: 0,
+            active_grant_count: 0,
+            grant_count: 0,
+            treasuries: Vec::new(),
+            deposit_lamports: 0,
+            pending_owner: None,
+            owner_can_reassign_beneficiary: false,
+            is_paused,
+            clawback_delay_seconds: 0,
+            dependency_failure_policy: crate::DependencyFailurePolicy::BlockClaims,
+            bump: 0,
+            canonical_name: String::new(),
+            pool_share_bps: 0,
+            pool_withdrawn: 0,
+            require_allowed_destinations: false,
+            version: crate::VESTING_ACCOUNT_VERSION,
+            legacy_seeds: false,
+            pool_released_size: 0,
+            reserved: [0; crate::VESTING_ACCOUNT_RESERVED_BYTES],
+        }
+    }
+
+    #[test]
+    fn only_listed_transitions_are_allowed() {
+        for status in STATUSES {
+            for action in ACTIONS {
+                assert_eq!(
+                    check(status, action).is_ok(),
+                    ALLOWED.contains(&(status, action)),
+                    "{action:?} in {status:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn status_follows_grant_fields() {
+        let mut offered = grant();
+        offered.accepted_at = None;
+        assert_eq!(GrantStatus::of(&offered, &vesting_account(false)), Offered);
+        assert_eq!(GrantStatus::of(&grant(), &vesting_account(false)), Active);
+        assert_eq!(GrantStatus::of(&grant(), &vesting_account(true)), Paused);
+
+        let mut revoked = grant();
+        revoked.revoked_at = Some(50);
+        assert_eq!(GrantStatus::of(&revoked, &vesting_account(true)), Revoked);
+        revoked.clawed_back = true;
+        revoked.total_amount = 50;
+        assert_eq!(GrantStatus::of(&revoked, &vesting_account(false)), ClawedBack);
+        revoked.total_withdrawn = 50;
+        assert_eq!(GrantStatus::of(&revoked, &vesting_account(false)), Completed);
+    }
+
+    #[test]
+    fn pool_grants_have_no_amount_to_change() {
+        let mut pool_grant = grant();
+        pool_grant.share_bps = 2_500;
+        for action in [TopUp, Amend, Revoke, Export] {
+            assert!(check(Active, action).is_ok());
+            assert!(guard(&pool_grant, &vesting_account(false), action).is_err(), "{action:?}");
+        }
+        assert!(guard(&pool_grant, &vesting_account(false), Claim).is_ok());
+    }
+
+    #[test]
+    fn pause_blocks_claims_of_revoked_grants() {
+        let mut revoked = grant();
+        revoked.revoked_at = Some(50);
+        assert!(guard(&revoked, &vesting_account(false), Claim).is_ok());
+        assert!(guard(&revoked, &vesting_account(true), Claim).is_err());
+    }
+
+    #[test]
+    fn pause_makes_claim_all_skip_every_grant() {
+        let mut revoked = grant();
+        revoked.revoke(50, 0);
+        for grant in [grant(), revoked] {
+            let vested = grant.vested_amount(60).unwrap();
+            assert_eq!(crate::claim_all_amount(&grant, &vesting_account(false), 60).unwrap(), Some(vested));
+            assert_eq!(crate::claim_all_amount(&grant, &vesting_account(true), 60).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn only_the_beneficiary_or_an_allowed_owner_can_reassign() {
+        use crate::check_reassignment_authority as check;
+
+        let beneficiary = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let grant = EmployeeAccount { beneficiary, ..grant() };
+        let mut vesting_account = VestingAccount { owner, ..vesting_account(false) };
+        let mut config = crate::Config {
+            admin: Pubkey::default(),
+            template_count: 0,
+            permissioned_creation: false,
+            creation_deposit_lamports: 0,
+            enabled_features: 0,
+            fee_recipient: Pubkey::default(),
+            claim_fee_bps: 0,
+            bump: 0,
+        };
+        assert!(check(beneficiary, &grant, &vesting_account, None).is_ok());
+        assert_eq!(check(Pubkey::new_unique(), &grant, &vesting_account, None), Err(ErrorCode::Unauthorized.into()));
+        assert_eq!(check(owner, &grant, &vesting_account, Some(&config)), Err(ErrorCode::Unauthorized.into()));
+        vesting_account.owner_can_reassign_beneficiary = true;
+        assert_eq!(check(owner, &grant, &vesting_account, Some(&config)), Err(ErrorCode::FeatureDisabled.into()));
+        config.enabled_features = crate::Feature::OwnerBeneficiaryReassignment.mask();
+        assert!(check(owner, &grant, &vesting_account, Some(&config)).is_ok());
+    }
+
+    // claim_all pays every grant it can and skips the rest, instead of failing the batch because of one of them.
+    #[test]
+    fn claim_all_skips_grants_it_cannot_pay() {
+        let mut offered = grant();
+        offered.accepted_at = None;
+        let mut before_cliff = grant();
+        before_cliff.cliff_time = 80;
+        let mut pool_grant = grant();
+        pool_grant.share_bps = 2_500;
+        let mut completed = grant();
+        completed.total_withdrawn = completed.total_amount;
+        let mut claimed_so_far = grant();
+        claimed_so_far.total_withdrawn = 60;
+        let batch = [grant(), offered, before_cliff, pool_grant, completed, claimed_so_far];
+
+        let amounts: Vec<Option<u64>> = batch
+            .iter()
+            .map(|grant| crate::claim_all_amount(grant, &vesting_account(false), 60).unwrap())
+            .collect();
+        assert_eq!(amounts, [Some(60), None, None, None, None, None]);
+
+        let mut gated = vesting_account(false);
+        gated.require_allowed_destinations = true;
+        assert_eq!(crate::claim_all_amount(&grant(), &gated, 60).unwrap(), None);
+    }
+
+    #[test]
+    fn clawback_waits_for_the_delay_at_revocation() {
+        let mut revoked = grant();
+        assert_eq!(revoked.revoke(40, 30), 70);
+        assert_eq!(GrantStatus::of(&revoked, &vesting_account(false)), Revoked);
+        assert!(guard(&revoked, &vesting_account(false), ExecuteClawback).is_ok());
+
+        assert_eq!(revoked.claw_back(69), Err(ErrorCode::ClawbackDelayNotElapsed.into()));
+        assert_eq!(revoked.total_amount, 100);
+        // Only what vested by the revocation is kept, however late the clawback runs.
+        assert_eq!(revoked.claw_back(1_000), Ok(60));
+        assert_eq!((revoked.total_amount, revoked.clawed_back), (40, true));
+        assert_eq!(GrantStatus::of(&revoked, &vesting_account(false)), ClawedBack);
+        assert_eq!(revoked.claimable_amount(1_000).unwrap(), 40);
+    }
+
+    #[test]
+    fn rescinded_revocations_cannot_be_clawed_back() {
+        let mut grant = grant();
+        assert_eq!(grant.claw_back(1_000), Err(ErrorCode::GrantNotRevoked.into()));
+        grant.revoke(40, 0);
+        grant.revoked_at = None;
+        grant.clawback_available_at = None;
+        assert!(guard(&grant, &vesting_account(false), ExecuteClawback).is_err());
+        assert_eq!(grant.claw_back(1_000), Err(ErrorCode::GrantNotRevoked.into()));
+        assert_eq!(grant.total_amount, 100);
+    }
+
+    #[test]
+    fn only_accepted_unpaused_grants_can_be_exported() {
+        let mut offered = grant();
+        offered.accepted_at = None;
+        assert!(guard(&offered, &vesting_account(false), Export).is_err());
+        assert!(guard(&grant(), &vesting_account(false), Export).is_ok());
+        assert!(guard(&grant(), &vesting_account(true), Export).is_err());
+
+        // Pausing stops exports of grants that are fully claimed, too.
+        let mut completed = grant();
+        completed.total_withdrawn = completed.total_amount;
+        assert!(guard(&completed, &vesting_account(false), Export).is_ok());
+        assert!(guard(&completed, &vesting_account(true), Export).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+// Stands up the demo company on the cluster configured in Anchor.toml, or the one passed with
+// `anchor run demo --provider.cluster devnet`. Set VESTING_PROGRAM_ID to run it against another deployment.
+import * as anchor from '@coral-xyz/anchor';
+import NodeWallet from '@coral-xyz/anchor/dist/cjs/nodewallet';
+import { PublicKey } from '@solana/web3.js';
+import { VESTING_PROGRAM_ID, getVestingProgram } from '../src/vesting-exports';
+import { runDemoFlow } from '../src/vesting-demo';
+
+async function main() {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+  const programId = process.env.VESTING_PROGRAM_ID
+    ? new PublicKey(process.env.VESTING_PROGRAM_ID)
+    : VESTING_PROGRAM_ID;
+  const program = getVestingProgram(provider, programId);
+
+  const result = await runDemoFlow(program, (provider.wallet as NodeWallet).payer, {
+    log: console.log,
+  });
+  console.log(
+    JSON.stringify(
+      {
+        programId: program.programId.toBase58(),
+        companyName: result.companyName,
+        mint: result.mint.toBase58(),
+        vestingAccount: result.vestingAccount.toBase58(),
+        grants: result.grants.map(({ label, beneficiary, claimSignature }) => ({
+          label,
+          beneficiary: beneficiary.publicKey.toBase58(),
+          // Printed so the beneficiaries can be imported into a wallet to try claiming.
+          beneficiarySecretKey: Array.from(beneficiary.secretKey),
+          claimSignature,
+        })),
+      },
+      null,
+      2
+    )
+  );
+}
+
+main().catch((err) => {
+  console.error(err);
+  process.exit(1);
+});
@@ -0,0 +1,118 @@
+// Operations commands for a deployed vesting program, against the cluster configured in Anchor.toml or
+// the one passed with --provider.cluster. Set VESTING_PROGRAM_ID to run it against another deployment.
+//
+//   anchor run vesting-cli -- status <vesting account> [--json] [--horizon-days 30] [--stale-after-days 90]
+import * as anchor from '@coral-xyz/anchor';
+import { PublicKey } from '@solana/web3.js';
+import { VESTING_PROGRAM_ID, getVestingProgram } from '../src/vesting-exports';
+import { VestingHealth, getVestingHealth } from '../src/vesting-health';
+
+const USAGE =
+  'Usage: vesting-cli status <vesting account> [--json] [--horizon-days <days>] [--stale-after-days <days>]';
+
+function formatTime(timestamp: number | null) {
+  return timestamp === null ? 'now' : new Date(timestamp * 1000).toISOString();
+}
+
+function printHealth(health: VestingHealth) {
+  console.log(`${health.companyName} (${health.vestingAccount.toBase58()})`);
+  console.log(`  owner:  ${health.owner.toBase58()}`);
+  console.log(`  claims: ${health.isPaused ? 'PAUSED' : 'open'}`);
+  console.log(`  grants: ${health.grantCount}`);
+
+  console.log('\nTreasuries');
+  for (const treasury of health.treasuries) {
+    console.log(
+      `  ${treasury.mint.toBase58()}  balance ${treasury.balance}  owed ${treasury.obligations}  reserved ${treasury.reserved}` +
+        (treasury.shortfall.isZero() ? '' : `  SHORT ${treasury.shortfall}`)
+    );
+  }
+
+  console.log('\nPending actions');
+  if (!health.pendingActions.length) {
+    console.log('  none');
+  }
+  for (const action of health.pendingActions) {
+    console.log(`  ${action.kind}  ${action.account.toBase58()}  from ${formatTime(action.availableAt)}`);
+  }
+
+  console.log('\nUpcoming unlocks');
+  if (!health.upcomingUnlocks.length) {
+    console.log('  none');
+  }
+  for (const unlock of health.upcomingUnlocks) {
+    console.log(
+      `  ${formatTime(unlock.day).slice(0, 10)}  ${unlock.amount} of ${unlock.mint.toBase58()} across ${unlock.grants.length} grant(s)`
+    );
+  }
+
+  console.log('\nStale grants');
+  if (!health.staleGrants.length) {
+    console.log('  none');
+  }
+  for (const grant of health.staleGrants) {
+    console.log(`  ${grant.reason}  ${grant.employeeAccount.toBase58()}  beneficiary ${grant.beneficiary.toBase58()}`);
+  }
+
+  console.log('\nWarnings');
+  if (!health.warnings.length) {
+    console.log('  none');
+  }
+  for (const warning of health.warnings) {
+    console.log(`  ! ${warning}`);
+  }
+}
+
+function numberFlag(args: string[], name: string) {
+  const index = args.indexOf(name);
+  if (index === -1) {
+    return undefined;
+  }
+  const value = Number(args[index + 1]);
+  if (!Number.isFinite(value) || value < 0) {
+    throw new Error(`${name} expects a non-negative number`);
+  }
+  return value;
+}
+
+async function main() {
+  const [command, address, ...args] = process.argv.slice(2);
+  if (command !== 'status' || !address) {
+    console.error(USAGE);
+    process.exit(2);
+  }
+
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+  const programId = process.env.VESTING_PROGRAM_ID
+    ? new PublicKey(process.env.VESTING_PROGRAM_ID)
+    : VESTING_PROGRAM_ID;
+  const program = getVestingProgram(provider, programId);
+
+  const health = await getVestingHealth(program, new PublicKey(address), {
+    horizonDays: numberFlag(args, '--horizon-days'),
+    staleAfterDays: numberFlag(args, '--stale-after-days'),
+  });
+  if (args.includes('--json')) {
+    // BN's own toJSON is hex, amounts are printed in decimal like in the text output.
+    console.log(
+      JSON.stringify(
+        health,
+        function (this: Record<string, unknown>, key: string, value: unknown) {
+          const raw = this[key];
+          return anchor.BN.isBN(raw) ? raw.toString() : value;
+        },
+        2
+      )
+    );
+  } else {
+    printHealth(health);
+  }
+  // A non-zero exit lets cron jobs and CI alert on warnings without parsing the output.
+  process.exit(health.warnings.length ? 1 : 0);
+}
+
+main().catch((err) => {
+  console.error(err);
+  process.exit(1);
+});
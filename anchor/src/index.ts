@@ -1,3 +1,12 @@
 // This file was generated by preset-anchor. Programs are exported from this file.
 
 export * from './vesting-exports';
+export * from './vesting-metadata';
+export * from './vesting-errors';
+export * from './vesting-positions';
+export * from './vesting-deeplinks';
+export * from './vesting-demo';
+export * from './vesting-accounting';
+export * from './vesting-subscriptions';
+export * from './vesting-swap';
+export * from './vesting-health';
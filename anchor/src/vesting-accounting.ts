@@ -0,0 +1,185 @@
+// Here we turn the on-chain activity of a vesting account into double-entry journal entries that
+// finance teams can import into their ledger software.
+import { BN, EventParser, Program } from '@coral-xyz/anchor';
+import { getMint } from '@solana/spl-token';
+import { Finality, PublicKey } from '@solana/web3.js';
+import type { Vesting } from '../target/types/vesting';
+
+// Returns the USD price of one whole token of `mint` at `timestamp` (unix seconds), or null if unknown.
+// Only used for claims that didn't record a price snapshot on chain.
+export type PriceFeed = (mint: PublicKey, timestamp: number) => Promise<number | null>;
+
+export interface LedgerAccounts {
+  // Debited for every claim, the cost of the compensation paid out in tokens.
+  compensationExpense: string;
+  // Credited for every claim, the tokens held in the vesting treasury.
+  treasury: string;
+}
+
+export const DEFAULT_LEDGER_ACCOUNTS: LedgerAccounts = {
+  compensationExpense: 'Stock-based compensation expense',
+  treasury: 'Token vesting treasury',
+};
+
+export interface JournalLine {
+  account: string;
+  // Exactly one of debit and credit is set on each line, as a decimal token amount.
+  debit?: string;
+  credit?: string;
+  usd?: number;
+}
+
+export interface JournalEntry {
+  // ISO 8601 date of the block the activity was confirmed in.
+  date: string;
+  // Transaction signature, usable as the document reference in the ledger.
+  reference: string;
+  memo: string;
+  mint: PublicKey;
+  lines: JournalLine[];
+}
+
+export interface JournalExportOptions {
+  accounts?: LedgerAccounts;
+  priceFeed?: PriceFeed;
+  // Only activity after this signature is exported, e.g. the last one of the previous export.
+  until?: string;
+  limit?: number;
+  commitment?: Finality;
+}
+
+// Formats a raw token amount with the mint's decimals, without going through floating point.
+function formatTokenAmount(amount: BN, decimals: number) {
+  const digits = amount.toString().padStart(decimals + 1, '0');
+  if (decimals === 0) {
+    return digits;
+  }
+  return `${digits.slice(0, -decimals)}.${digits.slice(-decimals)}`;
+}
+
+function camelCaseKeys(data: Record<string, unknown>) {
+  return Object.fromEntries(
+    Object.entries(data).map(([key, value]) => [
+      key.replace(/_([a-z])/g, (_, c: string) => c.toUpperCase()),
+      value,
+    ])
+  );
+}
+
+// Builds one journal entry per claim of the vesting account, oldest first.
+export async function exportJournalEntries(
+  program: Program<Vesting>,
+  vestingAccount: PublicKey,
+  {
+    accounts = DEFAULT_LEDGER_ACCOUNTS,
+    priceFeed,
+    until,
+    limit,
+    commitment = 'confirmed',
+  }: JournalExportOptions = {}
+): Promise<JournalEntry[]> {
+  const connection = program.provider.connection;
+  const { companyName } = await program.account.vestingAccount.fetch(
+    vestingAccount,
+    commitment
+  );
+  // A vesting account can pay out in several mints, decimals are looked up once per mint.
+  const decimalsByMint = new Map<string, Promise<number>>();
+  const getDecimals = (mint: PublicKey) => {
+    const key = mint.toBase58();
+    if (!decimalsByMint.has(key)) {
+      decimalsByMint.set(
+        key,
+        connection
+          .getAccountInfo(mint, commitment)
+          .then((mintInfo) => getMint(connection, mint, commitment, mintInfo?.owner))
+          .then(({ decimals }) => decimals)
+      );
+    }
+    return decimalsByMint.get(key)!;
+  };
+
+  const signatures = await connection.getSignaturesForAddress(
+    vestingAccount,
+    { until, limit },
+    commitment
+  );
+  const parser = new EventParser(program.programId, program.coder);
+  const entries: JournalEntry[] = [];
+
+  // Signatures come newest first, the ledger wants them in chronological order.
+  for (const { signature, err } of signatures.reverse()) {
+    if (err) {
+      continue;
+    }
+    const transaction = await connection.getTransaction(signature, {
+      commitment,
+      maxSupportedTransactionVersion: 0,
+    });
+    const logs = transaction?.meta?.logMessages;
+    if (!transaction || !logs) {
+      continue;
+    }
+    const timestamp = transaction.blockTime ?? 0;
+
+    for (const event of parser.parseLogs(logs)) {
+      // Depending on the IDL the coder was built from, names and fields may not be camelCased yet.
+      const name = event.name.charAt(0).toLowerCase() + event.name.slice(1);
+      if (name !== 'tokensClaimed') {
+        continue;
+      }
+      const data = camelCaseKeys(event.data as Record<string, unknown>);
+      const mint = data.mint as PublicKey;
+      const amount = data.amount as BN;
+      const tokenAmount = formatTokenAmount(amount, await getDecimals(mint));
+      // The price recorded at claim time wins over a reconstructed one.
+      const snapshot = data.price
+        ? camelCaseKeys(data.price as Record<string, unknown>)
+        : null;
+      const price = snapshot
+        ? Number((snapshot.price as BN).toString()) * 10 ** (snapshot.expo as number)
+        : priceFeed
+        ? await priceFeed(mint, timestamp)
+        : null;
+      const usd =
+        price === null ? undefined : Math.round(Number(tokenAmount) * price * 100) / 100;
+
+      entries.push({
+        date: new Date(timestamp * 1000).toISOString(),
+        reference: signature,
+        memo: `${companyName}: claim by ${(data.beneficiary as PublicKey).toBase58()}`,
+        mint,
+        lines: [
+          { account: accounts.compensationExpense, debit: tokenAmount, usd },
+          { account: accounts.treasury, credit: tokenAmount, usd },
+        ],
+      });
+    }
+  }
+  return entries;
+}
+
+function csvField(value: string | number | undefined) {
+  const text = value === undefined ? '' : String(value);
+  return /[",\n]/.test(text) ? `"${text.replace(/"/g, '""')}"` : text;
+}
+
+// Renders journal entries as CSV with one row per line, the format most ledger software imports.
+export function journalEntriesToCsv(entries: JournalEntry[]) {
+  const rows = [['date', 'reference', 'memo', 'account', 'debit', 'credit', 'usd', 'mint']];
+  for (const entry of entries) {
+    for (const line of entry.lines) {
+      rows.push([
+        entry.date,
+        entry.reference,
+        entry.memo,
+        line.account,
+        line.debit ?? '',
+        line.credit ?? '',
+        line.usd === undefined ? '' : line.usd.toFixed(2),
+        entry.mint.toBase58(),
+      ]);
+    }
+  }
+  return rows.map((row) => row.map(csvField).join(',')).join('\n');
+}
@@ -0,0 +1,159 @@
+// Here we build the payloads and deep links that let employees act on their grants from a phone wallet.
+import { Program } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { Cluster, PublicKey, Transaction } from '@solana/web3.js';
+import type { Vesting } from '../target/types/vesting';
+import {
+  getClaimFeeTokenAccount,
+  getEmployeeAccountAddress,
+  getSeedNamespace,
+} from './vesting-exports';
+
+export type VestingDeepLinkAction = 'accept' | 'claim';
+
+export interface VestingDeepLinkParams {
+  action: VestingDeepLinkAction;
+  vestingAccount: PublicKey;
+  beneficiary?: PublicKey;
+  // Mint of the grant, for vesting accounts with more than one treasury. Defaults to the primary mint.
+  mint?: PublicKey;
+  cluster?: Cluster;
+}
+
+// This is a helper function to build the dapp URL a wallet's in-app browser should open for an action.
+export function buildVestingActionUrl(
+  appUrl: string,
+  { action, vestingAccount, beneficiary, mint, cluster }: VestingDeepLinkParams
+) {
+  const url = new URL(`vesting/${action}`, appUrl.endsWith('/') ? appUrl : `${appUrl}/`);
+  url.searchParams.set('vesting_account', vestingAccount.toBase58());
+  if (beneficiary) {
+    url.searchParams.set('beneficiary', beneficiary.toBase58());
+  }
+  if (mint) {
+    url.searchParams.set('mint', mint.toBase58());
+  }
+  if (cluster) {
+    url.searchParams.set('cluster', cluster);
+  }
+  return url.toString();
+}
+
+// Opens `url` in Backpack's in-app browser, `ref` is the URL of the referring app.
+export function buildBackpackBrowseLink(url: string, ref = url) {
+  return `https://backpack.app/ul/v1/browse/${encodeURIComponent(
+    url
+  )}?ref=${encodeURIComponent(ref)}`;
+}
+
+// Opens `url` in Phantom's in-app browser, `ref` is the URL of the referring app.
+export function buildPhantomBrowseLink(url: string, ref = url) {
+  return `https://phantom.app/ul/browse/${encodeURIComponent(
+    url
+  )}?ref=${encodeURIComponent(ref)}`;
+}
+
+// Builds a Solana Pay transaction request link. Any Solana Pay compatible mobile wallet will call
+// `transactionRequestUrl` and sign the transaction it returns, see buildClaimTransactionPayload
+// and buildAcceptTransactionPayload.
+export function buildSolanaPayLink(
+  transactionRequestUrl: string,
+  { action, vestingAccount, mint, cluster }: VestingDeepLinkParams
+) {
+  const url = new URL(transactionRequestUrl);
+  url.searchParams.set('action', action);
+  url.searchParams.set('vesting_account', vestingAccount.toBase58());
+  if (mint) {
+    url.searchParams.set('mint', mint.toBase58());
+  }
+  if (cluster) {
+    url.searchParams.set('cluster', cluster);
+  }
+  return `solana:${encodeURIComponent(url.toString())}`;
+}
+
+// Builds an unsigned claim transaction with the beneficiary as fee payer, serialized as base64.
+// The result can be returned as-is from a Solana Pay transaction request endpoint, or decoded and
+// passed to Mobile Wallet Adapter's signAndSendTransactions.
+export async function buildClaimTransactionPayload(
+  program: Program<Vesting>,
+  beneficiary: PublicKey,
+  vestingAccount: PublicKey,
+  tokenProgram: PublicKey = TOKEN_PROGRAM_ID,
+  grantMint?: PublicKey
+) {
+  const company = await program.account.vestingAccount.fetch(vestingAccount);
+  const { companyName } = company;
+  const employeeAccount = getEmployeeAccountAddress(
+    beneficiary,
+    vestingAccount,
+    grantMint ?? company.mint,
+    program.programId,
+    getSeedNamespace(program)
+  );
+  const { claimDestination, mint } = await program.account.employeeAccount.fetch(employeeAccount);
+  const feeTokenAccount = await getClaimFeeTokenAccount(program, mint, tokenProgram);
+  const instruction = await program.methods
+    .claimTokens(companyName, null)
+    .accountsPartial({
+      beneficiary,
+      employeeAccount,
+      vestingAccount,
+      mint,
+      tokenProgram,
+      claimDestination,
+      feeTokenAccount,
+    })
+    .instruction();
+  const { blockhash, lastValidBlockHeight } =
+    await program.provider.connection.getLatestBlockhash();
+  const transaction = new Transaction({
+    feePayer: beneficiary,
+    blockhash,
+    lastValidBlockHeight,
+  }).add(instruction);
+
+  return {
+    transaction: transaction
+      .serialize({ requireAllSignatures: false, verifySignatures: false })
+      .toString('base64'),
+    message: `Claim vested tokens from ${companyName}`,
+  };
+}
+
+// Builds an unsigned accept_grant transaction with the beneficiary as fee payer, serialized as base64,
+// in the same format as buildClaimTransactionPayload.
+export async function buildAcceptTransactionPayload(
+  program: Program<Vesting>,
+  beneficiary: PublicKey,
+  vestingAccount: PublicKey,
+  mint?: PublicKey
+) {
+  const company = await program.account.vestingAccount.fetch(vestingAccount);
+  const { companyName } = company;
+  const employeeAccount = getEmployeeAccountAddress(
+    beneficiary,
+    vestingAccount,
+    mint ?? company.mint,
+    program.programId,
+    getSeedNamespace(program)
+  );
+  const instruction = await program.methods
+    .acceptGrant()
+    .accountsPartial({ beneficiary, employeeAccount })
+    .instruction();
+  const { blockhash, lastValidBlockHeight } =
+    await program.provider.connection.getLatestBlockhash();
+  const transaction = new Transaction({
+    feePayer: beneficiary,
+    blockhash,
+    lastValidBlockHeight,
+  }).add(instruction);
+
+  return {
+    transaction: transaction
+      .serialize({ requireAllSignatures: false, verifySignatures: false })
+      .toString('base64'),
+    message: `Accept your token grant from ${companyName}`,
+  };
+}
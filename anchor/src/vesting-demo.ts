@@ -0,0 +1,260 @@
+// Here we stand up a complete example company, used by integration tests and by integrators who want
+// to see the program in action on devnet. Run it with `anchor run demo --provider.cluster devnet`.
+import { AnchorProvider, BN, Program } from '@coral-xyz/anchor';
+import {
+  TOKEN_PROGRAM_ID,
+  createMint,
+  createMintToInstruction,
+} from '@solana/spl-token';
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from '@solana/web3.js';
+import type { Vesting } from '../target/types/vesting';
+import {
+  getCompanyNameRecordAddress,
+  getEmployeeAccountAddress,
+  getNextVestingAccountAddress,
+  getSeedNamespace,
+  getTreasuryAddress,
+} from './vesting-exports';
+
+const DAY = 24 * 60 * 60;
+
+// Lamports sent to each demo beneficiary so it can pay for its token account when claiming.
+const BENEFICIARY_FUNDING_LAMPORTS = 0.01 * LAMPORTS_PER_SOL;
+
+export interface DemoGrantFixture {
+  label: string;
+  // Offsets in seconds relative to the time the demo runs. Negative offsets put the grant in
+  // the past, so it is already partially vested when the demo finishes.
+  startOffset: number;
+  cliffOffset: number;
+  endOffset: number;
+  totalAmount: number;
+  schedule:
+    | { linear: Record<string, never> }
+    | { periodic: Record<string, never> }
+    | { milestone: Record<string, never> }
+    | { lockup: Record<string, never> };
+  vestingIntervalSeconds?: number;
+  cliffUnlockBps?: number;
+  // Milestone offsets relative to the time the demo runs, only used by milestone grants.
+  milestones?: { offset: number; cumulativeBps: number }[];
+  // Whether the beneficiary claims once the grant is created, to produce claim history.
+  claim: boolean;
+}
+
+// One grant per schedule type, time-warped so each is in an interesting state.
+export const DEMO_GRANTS: DemoGrantFixture[] = [
+  {
+    label: 'linear, half vested, claimed',
+    startOffset: -180 * DAY,
+    cliffOffset: -90 * DAY,
+    endOffset: 180 * DAY,
+    totalAmount: 12_000,
+    schedule: { linear: {} },
+    claim: true,
+  },
+  {
+    label: 'linear, before cliff',
+    startOffset: -30 * DAY,
+    cliffOffset: 335 * DAY,
+    endOffset: 1430 * DAY,
+    totalAmount: 48_000,
+    schedule: { linear: {} },
+    claim: false,
+  },
+  {
+    label: 'monthly tranches, claimed',
+    startOffset: -95 * DAY,
+    cliffOffset: -95 * DAY,
+    endOffset: 265 * DAY,
+    totalAmount: 3_600,
+    schedule: { periodic: {} },
+    vestingIntervalSeconds: 30 * DAY,
+    claim: true,
+  },
+  {
+    label: '25% cliff unlock, unclaimed',
+    startOffset: -400 * DAY,
+    cliffOffset: -35 * DAY,
+    endOffset: 1060 * DAY,
+    totalAmount: 40_000,
+    schedule: { linear: {} },
+    cliffUnlockBps: 2_500,
+    claim: false,
+  },
+  {
+    label: 'back-loaded milestones, claimed',
+    startOffset: -200 * DAY,
+    cliffOffset: -100 * DAY,
+    endOffset: 300 * DAY,
+    totalAmount: 10_000,
+    schedule: { milestone: {} },
+    milestones: [
+      { offset: -100 * DAY, cumulativeBps: 1_000 },
+      { offset: 100 * DAY, cumulativeBps: 4_000 },
+      { offset: 300 * DAY, cumulativeBps: 10_000 },
+    ],
+    claim: true,
+  },
+  {
+    label: 'investor lockup, still locked',
+    startOffset: -90 * DAY,
+    cliffOffset: 275 * DAY,
+    endOffset: 275 * DAY,
+    totalAmount: 20_000,
+    schedule: { lockup: {} },
+    claim: false,
+  },
+];
+
+export interface DemoFlowOptions {
+  // Names are unique per deployment, defaults to one derived from the freshly created mint.
+  companyName?: string;
+  // Unix timestamp the fixture offsets are relative to, defaults to the local clock.
+  now?: number;
+  decimals?: number;
+  // An existing mint with `decimals` decimals and the payer as mint authority, a new one is created otherwise.
+  // Test validators without an RPC connection, like bankrun, need to pass one.
+  mint?: PublicKey;
+  grants?: DemoGrantFixture[];
+  log?: (message: string) => void;
+}
+
+export interface DemoFlowResult {
+  companyName: string;
+  mint: PublicKey;
+  vestingAccount: PublicKey;
+  grants: { label: string; beneficiary: Keypair; claimSignature?: string }[];
+}
+
+// Creates a mint, a company with a funded treasury, one grant per fixture and claims for the fixtures
+// that ask for it. `payer` must be the provider's wallet keypair, it owns the company and the mint.
+export async function runDemoFlow(
+  program: Program<Vesting>,
+  payer: Keypair,
+  {
+    companyName: companyNameOption,
+    now = Math.floor(Date.now() / 1000),
+    decimals = 2,
+    mint: existingMint,
+    grants = DEMO_GRANTS,
+    log = () => undefined,
+  }: DemoFlowOptions = {}
+): Promise<DemoFlowResult> {
+  const provider = program.provider as AnchorProvider;
+  const connection = provider.connection;
+  const scale = 10 ** decimals;
+
+  const mint =
+    existingMint ?? (await createMint(connection, payer, payer.publicKey, null, decimals));
+  log(`${existingMint ? 'Using' : 'Created'} mint ${mint.toBase58()}`);
+
+  const companyName = companyNameOption ?? `Demo Company ${mint.toBase58().slice(0, 8)}`;
+  const seedNamespace = getSeedNamespace(program);
+  const vestingAccount = await getNextVestingAccountAddress(program, payer.publicKey);
+  await program.methods
+    .createVestingAccount(companyName, { blockClaims: {} })
+    .accountsPartial({
+      vestingAccount,
+      mint,
+      companyNameRecord: getCompanyNameRecordAddress(companyName, program.programId, seedNamespace),
+      tokenProgram: TOKEN_PROGRAM_ID,
+    })
+    .rpc();
+  const treasuryTokenAccount = getTreasuryAddress(
+    vestingAccount,
+    mint,
+    program.programId,
+    seedNamespace
+  );
+  log(`Created company "${companyName}" at ${vestingAccount.toBase58()}`);
+
+  const treasuryAmount = grants.reduce((sum, grant) => sum + grant.totalAmount, 0);
+  await provider.sendAndConfirm(
+    new Transaction().add(
+      createMintToInstruction(mint, treasuryTokenAccount, payer.publicKey, treasuryAmount * scale)
+    )
+  );
+  log(`Funded treasury with ${treasuryAmount} tokens`);
+
+  const result: DemoFlowResult = { companyName, mint, vestingAccount, grants: [] };
+  for (const grant of grants) {
+    const beneficiary = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: payer.publicKey,
+          toPubkey: beneficiary.publicKey,
+          lamports: BENEFICIARY_FUNDING_LAMPORTS,
+        })
+      )
+    );
+
+    const accounts = { beneficiary: beneficiary.publicKey, vestingAccount, mint };
+    const totalAmount = new BN(grant.totalAmount * scale);
+    if ('milestone' in grant.schedule) {
+      await program.methods
+        .createEmployeeVestingWithMilestones(
+          new BN(now + grant.startOffset),
+          totalAmount,
+          (grant.milestones ?? []).map(({ offset, cumulativeBps }) => ({
+            timestamp: new BN(now + offset),
+            cumulativeBps,
+          }))
+        )
+        .accounts(accounts)
+        .rpc();
+    } else {
+      await program.methods
+        .createEmployeeVesting(
+          new BN(now + grant.startOffset),
+          new BN(now + grant.endOffset),
+          totalAmount,
+          new BN(now + grant.cliffOffset),
+          grant.schedule,
+          new BN(grant.vestingIntervalSeconds ?? 0),
+          grant.cliffUnlockBps ?? 0
+        )
+        .accounts(accounts)
+        .rpc();
+    }
+    log(`Created grant "${grant.label}" for ${beneficiary.publicKey.toBase58()}`);
+
+    let claimSignature: string | undefined;
+    if (grant.claim) {
+      const employeeAccount = getEmployeeAccountAddress(
+        beneficiary.publicKey,
+        vestingAccount,
+        mint,
+        program.programId,
+        seedNamespace
+      );
+      await program.methods
+        .acceptGrant()
+        .accountsPartial({ beneficiary: beneficiary.publicKey, employeeAccount })
+        .signers([beneficiary])
+        .rpc();
+      claimSignature = await program.methods
+        .claimTokens(companyName, null)
+        .accountsPartial({
+          beneficiary: beneficiary.publicKey,
+          employeeAccount,
+          vestingAccount,
+          mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([beneficiary])
+        .rpc();
+      log(`Claimed "${grant.label}": ${claimSignature}`);
+    }
+    result.grants.push({ label: grant.label, beneficiary, claimSignature });
+  }
+
+  return result;
+}
@@ -0,0 +1,134 @@
+// Here we expose the program's error codes and translate them into user-facing messages.
+import { AnchorError } from '@coral-xyz/anchor';
+import VestingIDL from '../target/idl/vesting.json';
+import type { Vesting } from '../target/types/vesting';
+
+// The IDL keeps the program's variant names, the generated type camelCases them.
+export type VestingErrorName = Capitalize<Vesting['errors'][number]['name']>;
+
+export interface VestingErrorInfo {
+  // The numeric code returned by the program, Anchor offsets custom errors by 6000.
+  code: number;
+  name: VestingErrorName;
+  // The default message declared with #[msg] in the program.
+  msg: string;
+}
+
+// The error catalog is read from the IDL, so it always matches the deployed program's ErrorCode enum.
+export const VESTING_ERRORS: VestingErrorInfo[] = VestingIDL.errors.map(
+  ({ code, name, msg }) => ({ code, name: name as VestingErrorName, msg })
+);
+
+export type VestingErrorMessages = Partial<Record<VestingErrorName, string>>;
+
+// Wallet-friendly messages for the errors a user can run into, keyed by locale.
+// Errors without an entry fall back to the program's default message.
+export const VESTING_ERROR_MESSAGES: Record<string, VestingErrorMessages> = {
+  en: {
+    ClaimNotAvailableYet:
+      'Your tokens are still locked. Claiming opens once the cliff date has passed.',
+    NothingToClaim:
+      'You have already claimed everything that has vested so far.',
+    VestingPaused:
+      'Claims are temporarily paused by the company. Your tokens keep vesting in the meantime.',
+    DependencyUnavailable:
+      'The token this grant pays out in is temporarily unavailable. Please try again later.',
+    GrantNotAccepted: 'Accept your grant before claiming tokens from it.',
+    DeadlineExceeded:
+      'The swap quote for this claim expired before it was confirmed. Please try again.',
+    TokenAccountReassigned:
+      'Your token account for this grant now belongs to another wallet. Set a claim destination to keep claiming.',
+    ClaimDestinationFrozen:
+      'The token account your claims go to is frozen. Set another claim destination or contact the token issuer.',
+    ClaimAmountExceedsClaimable: 'You can only claim up to what has vested and not been claimed yet.',
+    DestinationNotAllowed:
+      'Claims from this grant can only go to verified wallets. Ask the grant issuer to verify your wallet.',
+    InvalidVestingPeriod: 'This grant has an invalid vesting period.',
+    CalculationOverflow:
+      'The claimable amount could not be calculated. Please contact the grant issuer.',
+    Unauthorized: 'Your wallet is not allowed to perform this action.',
+    TemplateInactive: 'This schedule template has been retired.',
+    InvalidAmount: 'The grant amount must be greater than zero.',
+    InvalidSchedule:
+      'The grant dates are invalid. The cliff must fall between the start and end dates.',
+    CreatorNotAllowed:
+      'Company creation is restricted on this deployment. Ask the operator to allowlist your wallet.',
+  },
+  es: {
+    ClaimNotAvailableYet:
+      'Tus tokens siguen bloqueados. Podrás reclamarlos cuando pase la fecha del cliff.',
+    NothingToClaim: 'Ya has reclamado todo lo que ha sido liberado hasta ahora.',
+    VestingPaused:
+      'La empresa ha pausado temporalmente los reclamos. Tus tokens siguen liberándose mientras tanto.',
+    DependencyUnavailable:
+      'El token con el que se paga esta asignación no está disponible temporalmente. Inténtalo más tarde.',
+    GrantNotAccepted: 'Acepta tu asignación antes de reclamar tokens.',
+    DeadlineExceeded:
+      'La cotización del intercambio venció antes de confirmarse el reclamo. Inténtalo de nuevo.',
+    TokenAccountReassigned:
+      'Tu cuenta de tokens para esta asignación ahora pertenece a otra billetera. Configura un destino de reclamo para seguir reclamando.',
+    ClaimDestinationFrozen:
+      'La cuenta de tokens que recibe tus reclamos está congelada. Configura otro destino o contacta al emisor del token.',
+    ClaimAmountExceedsClaimable: 'Solo puedes reclamar hasta lo que ha sido liberado y aún no has reclamado.',
+    DestinationNotAllowed:
+      'Los reclamos de esta asignación solo pueden ir a billeteras verificadas. Pide al emisor que verifique tu billetera.',
+    InvalidVestingPeriod: 'Esta asignación tiene un periodo de vesting no válido.',
+    CalculationOverflow:
+      'No se pudo calcular el monto reclamable. Contacta al emisor de la asignación.',
+    Unauthorized: 'Tu billetera no tiene permiso para realizar esta acción.',
+    TemplateInactive: 'Esta plantilla de calendario ha sido retirada.',
+    InvalidAmount: 'El monto de la asignación debe ser mayor que cero.',
+    InvalidSchedule:
+      'Las fechas de la asignación no son válidas. El cliff debe estar entre el inicio y el fin.',
+    CreatorNotAllowed:
+      'La creación de empresas está restringida en este despliegue. Pide al operador que autorice tu billetera.',
+  },
+};
+
+// This is a helper function to look up an error by its numeric code or name.
+export function getVestingErrorInfo(codeOrName: number | string) {
+  return VESTING_ERRORS.find(
+    (error) => error.code === codeOrName || error.name === codeOrName
+  );
+}
+
+// Extracts the vesting error from anything thrown by an rpc() call: an AnchorError,
+// an error carrying transaction logs, or a raw "custom program error: 0x..." message.
+export function parseVestingError(err: unknown) {
+  if (err instanceof AnchorError) {
+    return getVestingErrorInfo(err.error.errorCode.number);
+  }
+  const logs = (err as { logs?: string[] } | null)?.logs;
+  if (Array.isArray(logs)) {
+    const parsed = AnchorError.parse(logs);
+    if (parsed) {
+      return getVestingErrorInfo(parsed.error.errorCode.number);
+    }
+  }
+  const match = String((err as Error | null)?.message ?? err).match(
+    /custom program error: (0x[0-9a-f]+)/i
+  );
+  return match ? getVestingErrorInfo(parseInt(match[1], 16)) : undefined;
+}
+
+// Returns a localized message for a failed vesting transaction, or undefined when the
+// error didn't come from the vesting program. Unknown locales fall back to English,
+// and `messages` can override or extend the built-in translations.
+export function getVestingErrorMessage(
+  err: unknown,
+  locale = 'en',
+  messages: VestingErrorMessages = {}
+) {
+  const info = parseVestingError(err);
+  if (!info) {
+    return undefined;
+  }
+  const language = locale.split('-')[0];
+  return (
+    messages[info.name] ??
+    VESTING_ERROR_MESSAGES[locale]?.[info.name] ??
+    VESTING_ERROR_MESSAGES[language]?.[info.name] ??
+    VESTING_ERROR_MESSAGES.en[info.name] ??
+    info.msg
+  );
+}
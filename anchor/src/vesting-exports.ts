@@ -1,6 +1,19 @@
 // Here we export some useful types and functions for interacting with the Anchor program.
-import { AnchorProvider, Program } from '@coral-xyz/anchor';
-import { Cluster, PublicKey } from '@solana/web3.js';
+import { AnchorProvider, BN, Program, utils } from '@coral-xyz/anchor';
+import {
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createTransferCheckedWithTransferHookInstruction,
+  getAssociatedTokenAddressSync,
+  getMint,
+} from '@solana/spl-token';
+import {
+  AccountMeta,
+  Cluster,
+  Commitment,
+  Connection,
+  PublicKey,
+} from '@solana/web3.js';
 import VestingIDL from '../target/idl/vesting.json';
 import type { Vesting } from '../target/types/vesting';
 
@@ -10,9 +23,30 @@ export { Vesting, VestingIDL };
 // The programId is imported from the program IDL.
 export const VESTING_PROGRAM_ID = new PublicKey(VestingIDL.address);
 
-// This is a helper function to get the Vesting Anchor program.
-export function getVestingProgram(provider: AnchorProvider) {
-  return new Program(VestingIDL as Vesting, provider);
+// The seed namespace of the canonical deployment, see getSeedNamespace.
+export const DEFAULT_SEED_NAMESPACE = Buffer.alloc(0);
+
+// This is a helper function to get the Vesting Anchor program. Pass `programId` to talk to another
+// deployment of the same program, e.g. a fork running next to the canonical one.
+export function getVestingProgram(
+  provider: AnchorProvider,
+  programId: PublicKey = VESTING_PROGRAM_ID
+) {
+  return new Program(
+    { ...VestingIDL, address: programId.toBase58() } as Vesting,
+    provider
+  );
+}
+
+// This is a helper function to read the namespace byte a deployment prepends to its PDA seeds from its IDL.
+// The canonical deployment and IDLs that predate namespaces use an empty namespace.
+export function getSeedNamespace(program: Program<Vesting>) {
+  const constant = (program.idl.constants ?? []).find(
+    ({ name }) => name === 'SEED_NAMESPACE' || name === 'seedNamespace'
+  );
+  return constant
+    ? Buffer.from(JSON.parse(constant.value) as number[])
+    : DEFAULT_SEED_NAMESPACE;
 }
 
 // This is a helper function to get the program ID for the Vesting program depending on the cluster.
@@ -27,3 +61,394 @@ export function getVestingProgramId(cluster: Cluster) {
       return VESTING_PROGRAM_ID;
   }
 }
+
+// Encodes an index the way the program does in PDA seeds, as a little-endian u64.
+function indexSeed(index: number | bigint) {
+  const seed = Buffer.alloc(8);
+  seed.writeBigUInt64LE(BigInt(index));
+  return seed;
+}
+
+// This is a helper function to derive the PDA counting the vesting accounts created by a key.
+export function getCreatorRegistryAddress(
+  creator: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('creator_registry'), creator.toBuffer()],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA holding a beneficiary's communication pointer.
+export function getBeneficiaryRegistryAddress(
+  beneficiary: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('beneficiary_registry'), beneficiary.toBuffer()],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA of the `index`-th vesting account created by `creator`.
+export function getVestingAccountAddress(
+  creator: PublicKey,
+  index: number | bigint,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('vesting_account'), creator.toBuffer(), indexSeed(index)],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA of the CompanyNameRecord reserving `companyName`. Names that
+// only differ in case share a record, its seed is the hash of the lowercased name.
+export function getCompanyNameRecordAddress(
+  companyName: string,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [
+      seedNamespace,
+      Buffer.from('company_name'),
+      Buffer.from(utils.sha256.hash(companyName.toLowerCase()), 'hex'),
+    ],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA of a vesting account's treasury for `mint`.
+export function getTreasuryAddress(
+  vestingAccount: PublicKey,
+  mint: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('vesting_treasury'), vestingAccount.toBuffer(), mint.toBuffer()],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA holding the lamports of a vesting account created with
+// create_sol_vesting. Fund it with a plain SOL transfer.
+export function getSolTreasuryAddress(
+  vestingAccount: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('sol_treasury'), vestingAccount.toBuffer()],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the claim stats PDA of a vesting account.
+export function getClaimStatsAddress(
+  vestingAccount: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('claim_stats'), vestingAccount.toBuffer()],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA of `beneficiary`'s grant in `mint` from a vesting account.
+// A beneficiary can hold one grant per mint, SOL grants use the system program's ID as their mint.
+export function getEmployeeAccountAddress(
+  beneficiary: PublicKey,
+  vestingAccount: PublicKey,
+  mint: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [
+      seedNamespace,
+      Buffer.from('employee_vesting'),
+      beneficiary.toBuffer(),
+      vestingAccount.toBuffer(),
+      mint.toBuffer(),
+    ],
+    programId
+  )[0];
+}
+
+// Number of days of history kept on chain by ClaimStats.
+const CLAIM_STATS_WINDOW_DAYS = 30;
+
+// This is a helper function to read the amounts claimed from a vesting account during the last
+// 7 and 30 days, mirroring ClaimStats::claimed_in_last_days in the program.
+export async function getClaimRates(
+  program: Program<Vesting>,
+  vestingAccount: PublicKey,
+  now: number = Math.floor(Date.now() / 1000)
+) {
+  const stats = await program.account.claimStats.fetch(
+    getClaimStatsAddress(vestingAccount, program.programId, getSeedNamespace(program))
+  );
+  const today = Math.floor(now / 86_400);
+  const lastClaimDay = stats.lastClaimDay.toNumber();
+  const claimedInLastDays = (days: number) => {
+    let sum = new BN(0);
+    for (let day = today - days + 1; day <= today; day++) {
+      if (day <= lastClaimDay && lastClaimDay - day < CLAIM_STATS_WINDOW_DAYS) {
+        sum = sum.add(stats.dailyClaimed[day % CLAIM_STATS_WINDOW_DAYS]);
+      }
+    }
+    return sum;
+  };
+  return {
+    totalClaimed: stats.totalClaimed,
+    claimCount: stats.claimCount,
+    claimedLast7Days: claimedInLastDays(7),
+    claimedLast30Days: claimedInLastDays(30),
+  };
+}
+
+// This is a helper function to derive the address the next create_vesting_account call of
+// `creator` will use, based on how many vesting accounts it created so far.
+export async function getNextVestingAccountAddress(
+  program: Program<Vesting>,
+  creator: PublicKey
+) {
+  const seedNamespace = getSeedNamespace(program);
+  const registry = await program.account.creatorRegistry.fetchNullable(
+    getCreatorRegistryAddress(creator, program.programId, seedNamespace)
+  );
+  const index = registry?.vestingAccountCount.toNumber() ?? 0;
+  return getVestingAccountAddress(creator, index, program.programId, seedNamespace);
+}
+
+// This is a helper function to derive the PDA of the `index`-th entry of a vesting account's grant registry.
+export function getGrantEntryAddress(
+  vestingAccount: PublicKey,
+  index: number,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('grant_entry'), vestingAccount.toBuffer(), indexSeed(index)],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA of the VotingPower snapshot of the grant at `registryIndex`.
+export function getVotingPowerAddress(
+  vestingAccount: PublicKey,
+  registryIndex: number,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('voting_power'), vestingAccount.toBuffer(), indexSeed(registryIndex)],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the PDA of the wallets a beneficiary's claims may be paid to while the
+// vesting account requires allowed destinations.
+export function getAllowedDestinationsAddress(
+  vestingAccount: PublicKey,
+  beneficiary: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('allowed_destinations'), vestingAccount.toBuffer(), beneficiary.toBuffer()],
+    programId
+  )[0];
+}
+
+// This is a helper function to page through the grants of a vesting account in creation order, using its
+// grant registry instead of a getProgramAccounts scan. Grants closed since are left out, so a page can hold
+// fewer than `limit` grants. `nextOffset` is null once the registry is exhausted.
+export async function getVestingAccountGrants(
+  program: Program<Vesting>,
+  vestingAccount: PublicKey,
+  { offset = 0, limit = 100 }: { offset?: number; limit?: number } = {}
+) {
+  const seedNamespace = getSeedNamespace(program);
+  const { grantCount } = await program.account.vestingAccount.fetch(vestingAccount);
+  const end = Math.min(offset + limit, grantCount.toNumber());
+  const entryAddresses = [];
+  for (let index = offset; index < end; index++) {
+    entryAddresses.push(
+      getGrantEntryAddress(vestingAccount, index, program.programId, seedNamespace)
+    );
+  }
+  const entries = (await program.account.grantRegistryEntry.fetchMultiple(entryAddresses)).filter(
+    (entry) => entry !== null
+  );
+  const employeeAccounts = entries.map((entry) => entry.employeeAccount);
+  const grants = await program.account.employeeAccount.fetchMultiple(employeeAccounts);
+  return {
+    grants: grants.flatMap((account, i) =>
+      account ? [{ publicKey: employeeAccounts[i], account }] : []
+    ),
+    nextOffset: end < grantCount.toNumber() ? end : null,
+  };
+}
+
+// This is a helper function to fetch every open grant of a vesting account from its grant registry.
+export async function getAllVestingAccountGrants(program: Program<Vesting>, vestingAccount: PublicKey) {
+  const grants = [];
+  let offset: number | null = 0;
+  while (offset !== null) {
+    const page = await getVestingAccountGrants(program, vestingAccount, { offset });
+    grants.push(...page.grants);
+    offset = page.nextOffset;
+  }
+  return grants;
+}
+
+// This is a helper function to derive the program config PDA.
+export function getConfigAddress(
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('config')],
+    programId
+  )[0];
+}
+
+// This is a helper function to get the claim fee of the deployment, null when it charges none.
+export async function getClaimFee(program: Program<Vesting>) {
+  const config = await program.account.config.fetchNullable(
+    getConfigAddress(program.programId, getSeedNamespace(program))
+  );
+  if (!config || config.claimFeeBps === 0) {
+    return null;
+  }
+  return { feeRecipient: config.feeRecipient, claimFeeBps: config.claimFeeBps };
+}
+
+// This is a helper function to get the fee token account claim_tokens pays the claim fee of `mint` into, to be
+// passed as `feeTokenAccount`. Returns null when the deployment charges no claim fee.
+export async function getClaimFeeTokenAccount(
+  program: Program<Vesting>,
+  mint: PublicKey,
+  tokenProgram: PublicKey = TOKEN_PROGRAM_ID
+) {
+  const claimFee = await getClaimFee(program);
+  return claimFee
+    ? getAssociatedTokenAddressSync(mint, claimFee.feeRecipient, true, tokenProgram)
+    : null;
+}
+
+// This is a helper function to derive the PDA of a shared schedule template by its id.
+export function getScheduleTemplateAddress(
+  templateId: number | bigint,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('schedule_template'), indexSeed(templateId)],
+    programId
+  )[0];
+}
+
+// This is a helper function to derive the allowlist entry PDA of a company creator.
+export function getCreatorAllowlistAddress(
+  creator: PublicKey,
+  programId: PublicKey = VESTING_PROGRAM_ID,
+  seedNamespace: Buffer = DEFAULT_SEED_NAMESPACE
+) {
+  return PublicKey.findProgramAddressSync(
+    [seedNamespace, Buffer.from('creator_allowlist'), creator.toBuffer()],
+    programId
+  )[0];
+}
+
+// This is a helper function to resolve the extra accounts a Token-2022 transfer hook needs during
+// claim_tokens, to be passed with `.remainingAccounts()`. Returns an empty list for other mints.
+export async function getClaimRemainingAccounts(
+  connection: Connection,
+  mint: PublicKey,
+  treasuryTokenAccount: PublicKey,
+  employeeTokenAccount: PublicKey,
+  amount: bigint,
+  commitment?: Commitment
+) {
+  const mintInfo = await connection.getAccountInfo(mint, commitment);
+  if (!mintInfo?.owner.equals(TOKEN_2022_PROGRAM_ID)) {
+    return [];
+  }
+  const { decimals } = await getMint(
+    connection,
+    mint,
+    commitment,
+    TOKEN_2022_PROGRAM_ID
+  );
+  // The treasury is its own authority, and the first four keys are the ones claim_tokens already passes.
+  const instruction = await createTransferCheckedWithTransferHookInstruction(
+    connection,
+    treasuryTokenAccount,
+    mint,
+    employeeTokenAccount,
+    treasuryTokenAccount,
+    amount,
+    decimals,
+    [],
+    commitment,
+    TOKEN_2022_PROGRAM_ID
+  );
+  return instruction.keys.slice(4);
+}
+
+// This is a helper function to build the remaining accounts of claim_all for every grant of
+// `beneficiary` paid out by `tokenProgram`. The beneficiary's token accounts must already exist.
+// Grants with a registered claim destination pay into it instead.
+export async function getClaimAllRemainingAccounts(
+  program: Program<Vesting>,
+  beneficiary: PublicKey,
+  tokenProgram: PublicKey
+): Promise<AccountMeta[]> {
+  // The beneficiary is the first field of EmployeeAccount, right after the 8-byte discriminator.
+  const grants = await program.account.employeeAccount.all([
+    { memcmp: { offset: 8, bytes: beneficiary.toBase58() } },
+  ]);
+  const seedNamespace = getSeedNamespace(program);
+  const mintInfos = await program.provider.connection.getMultipleAccountsInfo(
+    grants.map((grant) => grant.account.mint)
+  );
+
+  return grants.flatMap((grant, i) => {
+    const { mint, vestingAccount } = grant.account;
+    if (!mintInfos[i]?.owner.equals(tokenProgram)) {
+      return [];
+    }
+    return [
+      { pubkey: grant.publicKey, isSigner: false, isWritable: true },
+      // Writable, claim_all releases the claimed amount from the treasury's reservation.
+      { pubkey: vestingAccount, isSigner: false, isWritable: true },
+      { pubkey: mint, isSigner: false, isWritable: false },
+      {
+        pubkey: getTreasuryAddress(vestingAccount, mint, program.programId, seedNamespace),
+        isSigner: false,
+        isWritable: true,
+      },
+      {
+        pubkey:
+          grant.account.claimDestination ??
+          getAssociatedTokenAddressSync(mint, beneficiary, false, tokenProgram),
+        isSigner: false,
+        isWritable: true,
+      },
+      {
+        pubkey: getClaimStatsAddress(vestingAccount, program.programId, seedNamespace),
+        isSigner: false,
+        isWritable: true,
+      },
+    ];
+  });
+}
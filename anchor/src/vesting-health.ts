@@ -0,0 +1,258 @@
+// Here we put together the health of a vesting account in one report: whether its treasuries cover what
+// they owe, what is waiting on a timelock, which unlocks are coming up and which grants need attention.
+import { BN, Program } from '@coral-xyz/anchor';
+import { PublicKey, SystemProgram } from '@solana/web3.js';
+import type { Vesting } from '../target/types/vesting';
+import {
+  getAllVestingAccountGrants,
+  getSeedNamespace,
+  getSolTreasuryAddress,
+  getTreasuryAddress,
+} from './vesting-exports';
+import {
+  EmployeeAccountData,
+  VestingAccountData,
+  getVestedAmount,
+  refreshPoolAmount,
+} from './vesting-positions';
+
+const DAY = 24 * 60 * 60;
+
+export interface TreasuryHealth {
+  mint: PublicKey;
+  treasury: PublicKey;
+  // Raw token amount held by the treasury. For SOL treasuries, lamports above the rent-exempt minimum.
+  balance: BN;
+  // What the program has reserved for grants in this mint.
+  reserved: BN;
+  // Granted and not withdrawn yet, summed over the fixed grants in this mint.
+  obligations: BN;
+  // How far the balance falls short of the obligations, zero when they are covered.
+  shortfall: BN;
+}
+
+export type PendingActionKind = 'ownershipTransfer' | 'clawback' | 'offerExpiry';
+
+export interface PendingAction {
+  kind: PendingActionKind;
+  // The vesting account for ownership transfers, the grant otherwise.
+  account: PublicKey;
+  // When the action becomes executable (clawback) or the offer can be cancelled (offerExpiry),
+  // null when it only waits on a signature.
+  availableAt: number | null;
+}
+
+export interface UpcomingUnlock {
+  // Start of the UTC day the unlocks land on.
+  day: number;
+  mint: PublicKey;
+  // Unlocked at once on that day by cliffs, lockup ends and milestones, on top of regular accrual.
+  amount: BN;
+  grants: PublicKey[];
+}
+
+export type StaleGrantReason = 'expiredOffer' | 'unclaimed' | 'unclosed';
+
+export interface StaleGrant {
+  employeeAccount: PublicKey;
+  beneficiary: PublicKey;
+  reason: StaleGrantReason;
+}
+
+export interface VestingHealth {
+  vestingAccount: PublicKey;
+  companyName: string;
+  owner: PublicKey;
+  isPaused: boolean;
+  now: number;
+  grantCount: number;
+  treasuries: TreasuryHealth[];
+  pendingActions: PendingAction[];
+  upcomingUnlocks: UpcomingUnlock[];
+  staleGrants: StaleGrant[];
+  // One line per problem, empty when everything looks fine.
+  warnings: string[];
+}
+
+export interface GetVestingHealthOptions {
+  // Unix timestamp the report is computed at, defaults to the local clock.
+  now?: number;
+  // How far ahead to look for unlock spikes.
+  horizonDays?: number;
+  // How long a fully vested grant may sit unclaimed before it is reported.
+  staleAfterDays?: number;
+}
+
+// Returns the timestamps at which a grant unlocks a lump sum rather than accruing: its cliff,
+// its end for lockups and each of its milestones.
+function unlockPoints(grant: EmployeeAccountData) {
+  const points = [grant.cliffTime.toNumber()];
+  if ('lockup' in grant.scheduleKind) {
+    points.push(grant.endTime.toNumber());
+  }
+  points.push(...grant.milestones.map((milestone) => milestone.timestamp.toNumber()));
+  return [...new Set(points)];
+}
+
+async function getTreasuryBalance(program: Program<Vesting>, treasury: PublicKey, mint: PublicKey) {
+  const connection = program.provider.connection;
+  if (mint.equals(SystemProgram.programId)) {
+    const [lamports, rent] = await Promise.all([
+      connection.getBalance(treasury),
+      connection.getMinimumBalanceForRentExemption(0),
+    ]);
+    return new BN(Math.max(lamports - rent, 0));
+  }
+  const info = await connection.getAccountInfo(treasury);
+  if (!info) {
+    return new BN(0);
+  }
+  const { value } = await connection.getTokenAccountBalance(treasury);
+  return new BN(value.amount);
+}
+
+// This is a helper function to build the health report of a vesting account, see VestingHealth.
+export async function getVestingHealth(
+  program: Program<Vesting>,
+  vestingAccount: PublicKey,
+  {
+    now = Math.floor(Date.now() / 1000),
+    horizonDays = 30,
+    staleAfterDays = 90,
+  }: GetVestingHealthOptions = {}
+): Promise<VestingHealth> {
+  const seedNamespace = getSeedNamespace(program);
+  const company: VestingAccountData = await program.account.vestingAccount.fetch(vestingAccount);
+  const grants = await getAllVestingAccountGrants(program, vestingAccount);
+  const warnings: string[] = [];
+  if (company.isPaused) {
+    warnings.push('Claims are paused');
+  }
+
+  const treasuries = await Promise.all(
+    company.treasuries.map(async ({ mint, reservedAmount }): Promise<TreasuryHealth> => {
+      const treasury = mint.equals(SystemProgram.programId)
+        ? getSolTreasuryAddress(vestingAccount, program.programId, seedNamespace)
+        : getTreasuryAddress(vestingAccount, mint, program.programId, seedNamespace);
+      const balance = await getTreasuryBalance(program, treasury, mint);
+      const obligations = grants
+        // Pool grants own shares of whatever the treasury holds, so they can't make it fall short.
+        .filter(({ account }) => account.mint.equals(mint) && account.shareBps === 0)
+        .reduce(
+          (sum, { account }) => sum.add(BN.max(account.totalAmount.sub(account.totalWithdrawn), new BN(0))),
+          new BN(0)
+        );
+      return {
+        mint,
+        treasury,
+        balance,
+        reserved: reservedAmount,
+        obligations,
+        shortfall: BN.max(obligations.sub(balance), new BN(0)),
+      };
+    })
+  );
+  for (const treasury of treasuries) {
+    if (!treasury.shortfall.isZero()) {
+      warnings.push(
+        `Treasury for ${treasury.mint.toBase58()} is undercollateralized by ${treasury.shortfall.toString()}`
+      );
+    }
+    if (!treasury.reserved.eq(treasury.obligations)) {
+      warnings.push(
+        `Treasury for ${treasury.mint.toBase58()} reserves ${treasury.reserved.toString()} but its grants owe ${treasury.obligations.toString()}`
+      );
+    }
+  }
+
+  const pendingActions: PendingAction[] = [];
+  if (company.pendingOwner) {
+    pendingActions.push({ kind: 'ownershipTransfer', account: vestingAccount, availableAt: null });
+  }
+  for (const { publicKey, account } of grants) {
+    if (account.revokedAt && !account.clawedBack) {
+      pendingActions.push({
+        kind: 'clawback',
+        account: publicKey,
+        availableAt: account.clawbackAvailableAt.toNumber(),
+      });
+    }
+    if (!account.acceptedAt && account.acceptanceDeadline.toNumber() >= now) {
+      pendingActions.push({
+        kind: 'offerExpiry',
+        account: publicKey,
+        availableAt: account.acceptanceDeadline.toNumber() + 1,
+      });
+    }
+  }
+
+  const horizon = now + horizonDays * DAY;
+  const unlocksByDay = new Map<string, UpcomingUnlock>();
+  // Pool grants unlock shares of what the primary treasury holds now.
+  const poolBalance =
+    treasuries.find((treasury) => treasury.mint.equals(company.mint))?.balance ?? new BN(0);
+  for (const { publicKey, account: storedAccount } of grants) {
+    if (storedAccount.revokedAt || storedAccount.clawedBack) {
+      continue;
+    }
+    const account = refreshPoolAmount(storedAccount, company, poolBalance);
+    for (const point of unlockPoints(account)) {
+      if (point < now || point > horizon) {
+        continue;
+      }
+      const amount = getVestedAmount(account, point).sub(getVestedAmount(account, point - 1));
+      if (amount.isZero()) {
+        continue;
+      }
+      const day = point - (point % DAY);
+      const key = `${day}:${account.mint.toBase58()}`;
+      const unlock = unlocksByDay.get(key) ?? { day, mint: account.mint, amount: new BN(0), grants: [] };
+      unlock.amount = unlock.amount.add(amount);
+      unlock.grants.push(publicKey);
+      unlocksByDay.set(key, unlock);
+    }
+  }
+  const upcomingUnlocks = [...unlocksByDay.values()].sort((a, b) => a.day - b.day);
+  for (const unlock of upcomingUnlocks) {
+    const treasury = treasuries.find((treasury) => treasury.mint.equals(unlock.mint));
+    if (treasury && unlock.amount.gt(treasury.balance)) {
+      warnings.push(
+        `${unlock.amount.toString()} of ${unlock.mint.toBase58()} unlocks on ${new Date(
+          unlock.day * 1000
+        ).toISOString().slice(0, 10)}, more than the treasury holds`
+      );
+    }
+  }
+
+  const staleGrants: StaleGrant[] = [];
+  for (const { publicKey, account } of grants) {
+    const stale = (reason: StaleGrantReason) =>
+      staleGrants.push({ employeeAccount: publicKey, beneficiary: account.beneficiary, reason });
+    if (account.totalWithdrawn.gte(account.totalAmount)) {
+      stale('unclosed');
+    } else if (!account.acceptedAt) {
+      if (account.acceptanceDeadline.toNumber() < now) {
+        stale('expiredOffer');
+      }
+    } else if (account.endTime.toNumber() + staleAfterDays * DAY < now) {
+      stale('unclaimed');
+    }
+  }
+  if (staleGrants.length) {
+    warnings.push(`${staleGrants.length} stale grant(s) need attention`);
+  }
+
+  return {
+    vestingAccount,
+    companyName: company.companyName,
+    owner: company.owner,
+    isPaused: company.isPaused,
+    now,
+    grantCount: grants.length,
+    treasuries,
+    pendingActions,
+    upcomingUnlocks,
+    staleGrants,
+    warnings,
+  };
+}
@@ -0,0 +1,174 @@
+// Here we resolve display metadata (name, symbol, logo) for the mints used by vesting accounts.
+import { Program } from '@coral-xyz/anchor';
+import { TOKEN_2022_PROGRAM_ID, getTokenMetadata } from '@solana/spl-token';
+import { Commitment, Connection, PublicKey } from '@solana/web3.js';
+import type { Vesting } from '../target/types/vesting';
+import { getAllVestingAccountGrants } from './vesting-exports';
+
+// The Metaplex Token Metadata program, used by most mints created with the legacy token program.
+export const METAPLEX_METADATA_PROGRAM_ID = new PublicKey(
+  'metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s'
+);
+
+export interface VestingTokenMetadata {
+  mint: PublicKey;
+  name: string;
+  symbol: string;
+  // The metadata URI as stored on-chain, usually pointing to an off-chain JSON document.
+  uri: string;
+  // The `image` field of the off-chain JSON document, only set when `resolveLogo` is enabled.
+  logoUri?: string;
+  source: 'token-2022' | 'metaplex';
+}
+
+export interface ResolveTokenMetadataOptions {
+  commitment?: Commitment;
+  // Fetch the off-chain JSON document behind `uri` to find the logo.
+  resolveLogo?: boolean;
+}
+
+// This is a helper function to derive the Metaplex metadata PDA for a mint.
+export function getMetaplexMetadataAddress(mint: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [
+      Buffer.from('metadata'),
+      METAPLEX_METADATA_PROGRAM_ID.toBuffer(),
+      mint.toBuffer(),
+    ],
+    METAPLEX_METADATA_PROGRAM_ID
+  )[0];
+}
+
+// Metaplex pads strings with null bytes up to their maximum length, so we strip those.
+function readBorshString(data: Buffer, offset: number): [string, number] {
+  const length = data.readUInt32LE(offset);
+  const start = offset + 4;
+  const value = data
+    .subarray(start, start + length)
+    .toString('utf8')
+    .replace(/\0/g, '');
+  return [value, start + length];
+}
+
+// Decodes the leading fields of a Metaplex metadata account:
+// key (1) | update authority (32) | mint (32) | name | symbol | uri
+export function decodeMetaplexMetadata(mint: PublicKey, data: Buffer) {
+  let offset = 1 + 32 + 32;
+  let name: string;
+  let symbol: string;
+  let uri: string;
+  [name, offset] = readBorshString(data, offset);
+  [symbol, offset] = readBorshString(data, offset);
+  [uri] = readBorshString(data, offset);
+  return {
+    mint,
+    name,
+    symbol,
+    uri,
+    source: 'metaplex',
+  } satisfies VestingTokenMetadata;
+}
+
+async function resolveLogoUri(uri: string) {
+  if (!uri) {
+    return undefined;
+  }
+  try {
+    const response = await fetch(uri);
+    const json = await response.json();
+    return typeof json?.image === 'string' ? json.image : undefined;
+  } catch {
+    return undefined;
+  }
+}
+
+// Resolves metadata for a single mint. The Token-2022 metadata extension takes precedence
+// over Metaplex metadata; null is returned when the mint has neither.
+export async function resolveTokenMetadata(
+  connection: Connection,
+  mint: PublicKey,
+  { commitment, resolveLogo = false }: ResolveTokenMetadataOptions = {}
+): Promise<VestingTokenMetadata | null> {
+  const mintInfo = await connection.getAccountInfo(mint, commitment);
+  if (!mintInfo) {
+    return null;
+  }
+
+  let metadata: VestingTokenMetadata | null = null;
+  if (mintInfo.owner.equals(TOKEN_2022_PROGRAM_ID)) {
+    const extension = await getTokenMetadata(
+      connection,
+      mint,
+      commitment,
+      TOKEN_2022_PROGRAM_ID
+    );
+    if (extension) {
+      metadata = {
+        mint,
+        name: extension.name,
+        symbol: extension.symbol,
+        uri: extension.uri,
+        source: 'token-2022',
+      };
+    }
+  }
+
+  if (!metadata) {
+    const metaplexAccount = await connection.getAccountInfo(
+      getMetaplexMetadataAddress(mint),
+      commitment
+    );
+    if (metaplexAccount) {
+      metadata = decodeMetaplexMetadata(mint, metaplexAccount.data);
+    }
+  }
+
+  if (metadata && resolveLogo) {
+    metadata.logoUri = await resolveLogoUri(metadata.uri);
+  }
+  return metadata;
+}
+
+// Attaches token metadata to any list of accounts that carry a `mint` field, such as the
+// results of `program.account.vestingAccount.all()`. Each mint is only resolved once.
+export async function withTokenMetadata<T extends { account: { mint: PublicKey } }>(
+  connection: Connection,
+  accounts: T[],
+  options: ResolveTokenMetadataOptions = {}
+): Promise<(T & { tokenMetadata: VestingTokenMetadata | null })[]> {
+  const cache = new Map<string, Promise<VestingTokenMetadata | null>>();
+  return Promise.all(
+    accounts.map(async (item) => {
+      const key = item.account.mint.toBase58();
+      if (!cache.has(key)) {
+        cache.set(
+          key,
+          resolveTokenMetadata(connection, item.account.mint, options)
+        );
+      }
+      return { ...item, tokenMetadata: await cache.get(key)! };
+    })
+  );
+}
+
+// This is a helper function to fetch all vesting accounts (companies) with their token metadata.
+export async function fetchVestingAccountsWithMetadata(
+  program: Program<Vesting>,
+  options: ResolveTokenMetadataOptions = {}
+) {
+  const accounts = await program.account.vestingAccount.all();
+  return withTokenMetadata(program.provider.connection, accounts, options);
+}
+
+// This is a helper function to fetch all employee grants of a vesting account with the
+// metadata of the token they pay out in.
+export async function fetchEmployeeAccountsWithMetadata(
+  program: Program<Vesting>,
+  vestingAccount: PublicKey,
+  options: ResolveTokenMetadataOptions = {}
+) {
+  const { mint } = await program.account.vestingAccount.fetch(vestingAccount);
+  const grants = await getAllVestingAccountGrants(program, vestingAccount);
+  const companyGrants = grants.map((grant) => ({ ...grant, account: { ...grant.account, mint } }));
+  return withTokenMetadata(program.provider.connection, companyGrants, options);
+}
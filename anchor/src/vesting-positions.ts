@@ -0,0 +1,297 @@
+// Here we expose a stable read interface for portfolio trackers that want to show a wallet's
+// vesting grants next to its liquid balances.
+import { BN, Program } from '@coral-xyz/anchor';
+import { PublicKey } from '@solana/web3.js';
+import VestingIDL from '../target/idl/vesting.json';
+import type { Vesting } from '../target/types/vesting';
+import {
+  ResolveTokenMetadataOptions,
+  VestingTokenMetadata,
+  resolveTokenMetadata,
+} from './vesting-metadata';
+
+// The 8-byte discriminators that prefix every account owned by the program, keyed by account name.
+// Trackers indexing raw account data can use these with a memcmp filter at offset 0.
+export const VESTING_ACCOUNT_DISCRIMINATORS: Record<string, number[]> =
+  Object.fromEntries(
+    VestingIDL.accounts.map(({ name, discriminator }) => [name, discriminator])
+  );
+
+// The beneficiary is the first field of EmployeeAccount, so its offset is stable across layout changes.
+export const EMPLOYEE_ACCOUNT_BENEFICIARY_OFFSET = 8;
+
+export type EmployeeAccountData = Awaited<
+  ReturnType<Program<Vesting>['account']['employeeAccount']['fetch']>
+>;
+
+export type VestingAccountData = Awaited<
+  ReturnType<Program<Vesting>['account']['vestingAccount']['fetch']>
+>;
+
+export interface VestingPosition {
+  employeeAccount: PublicKey;
+  vestingAccount: PublicKey;
+  companyName: string;
+  mint: PublicKey;
+  // Raw token amounts, in the mint's base units.
+  totalAmount: BN;
+  withdrawnAmount: BN;
+  vestedAmount: BN;
+  // Vested but not withdrawn yet.
+  claimableAmount: BN;
+  // Not vested yet.
+  lockedAmount: BN;
+  startTime: number;
+  cliffTime: number;
+  endTime: number;
+  tokenMetadata?: VestingTokenMetadata | null;
+}
+
+export interface GetPositionsOptions extends ResolveTokenMetadataOptions {
+  // Unix timestamp used to compute vested amounts, defaults to the local clock.
+  now?: number;
+  // Resolve token metadata for each position's mint.
+  withMetadata?: boolean;
+  // How long results are reused for the same wallet, 0 disables caching.
+  cacheTtlMs?: number;
+}
+
+const BPS_DENOMINATOR = new BN(10_000);
+
+// Mirrors EmployeeAccount::refresh_pool_amount in the program. A pool grant's stored totalAmount
+// is only as recent as its last claim, its real amount is its share of everything the pool has held
+// since poolBase, computed from the primary treasury's current balance.
+export function refreshPoolAmount(
+  grant: EmployeeAccountData,
+  company: VestingAccountData,
+  treasuryBalance: BN
+): EmployeeAccountData {
+  if (!grant.shareBps) {
+    return grant;
+  }
+  const poolSize = BN.max(
+    treasuryBalance.add(company.poolWithdrawn).sub(grant.poolBase),
+    new BN(0)
+  );
+  return { ...grant, totalAmount: poolSize.muln(grant.shareBps).div(BPS_DENOMINATOR) };
+}
+
+// Mirrors EmployeeAccount::vested_amount in the program. The test vectors generated by the
+// program's `test_vectors` example can be used to check that both stay in sync. Pool grants have
+// to go through refreshPoolAmount first.
+export function getVestedAmount(grant: EmployeeAccountData, now: number): BN {
+  if (grant.clawedBack) {
+    return grant.totalAmount;
+  }
+  if (grant.revokedAt) {
+    now = Math.min(now, grant.revokedAt.toNumber());
+  }
+  const total = grant.totalAmount;
+  const start = grant.startTime.toNumber();
+  const end = grant.endTime.toNumber();
+  const cliff = grant.cliffTime.toNumber();
+  if (end <= start) {
+    throw new Error('Invalid vesting period');
+  }
+  if (now >= end) {
+    return total;
+  }
+  if ('lockup' in grant.scheduleKind) {
+    return new BN(0);
+  }
+  if (now < cliff) {
+    return new BN(0);
+  }
+  if ('milestone' in grant.scheduleKind) {
+    const passed = grant.milestones.filter((m) => m.timestamp.toNumber() <= now);
+    const bps = passed.length ? passed[passed.length - 1].cumulativeBps : 0;
+    return total.muln(bps).div(BPS_DENOMINATOR);
+  }
+
+  const cliffAmount = grant.cliffUnlockBps
+    ? total.muln(grant.cliffUnlockBps).div(BPS_DENOMINATOR)
+    : new BN(0);
+  const accrualStart = grant.cliffUnlockBps ? cliff : start;
+  const amount = total.sub(cliffAmount);
+  const elapsed = new BN(Math.max(now - accrualStart, 0));
+  const duration = new BN(end - accrualStart);
+
+  if ('periodic' in grant.scheduleKind) {
+    const interval = grant.vestingIntervalSeconds;
+    const intervalsElapsed = elapsed.div(interval);
+    const totalIntervals = duration.add(interval).subn(1).div(interval);
+    return cliffAmount.add(intervalsElapsed.mul(amount.div(totalIntervals)));
+  }
+  return cliffAmount.add(amount.mul(elapsed).div(duration));
+}
+
+// Asks the program itself for a grant's vested, withdrawn and claimable amounts at `timestamp` (or
+// the cluster's clock when omitted) by simulating get_claimable_amount, nothing is sent on chain.
+// Prefer this over getVestedAmount when the result has to match claim_tokens exactly.
+export async function getClaimableAmount(
+  program: Program<Vesting>,
+  employeeAccount: PublicKey,
+  timestamp?: number
+) {
+  // Pool grants compute their amount from the primary treasury's balance, so it's always passed along.
+  const { vestingAccount } = await program.account.employeeAccount.fetch(employeeAccount);
+  const { treasuryTokenAccount } = await program.account.vestingAccount.fetch(vestingAccount);
+  return program.methods
+    .getClaimableAmount(timestamp === undefined ? null : new BN(timestamp))
+    .accounts({ employeeAccount, vestingAccount, treasuryTokenAccount })
+    .view();
+}
+
+// Computes the position of a single grant at `now` from already fetched account data. Pool grants
+// also need the balance of the vesting account's primary treasury, see refreshPoolAmount.
+export function buildVestingPosition(
+  employeeAccount: PublicKey,
+  storedGrant: EmployeeAccountData,
+  company: VestingAccountData,
+  now: number,
+  treasuryBalance?: BN
+): VestingPosition {
+  if (storedGrant.shareBps && !treasuryBalance) {
+    throw new Error('Pool grants need the primary treasury balance');
+  }
+  const grant = treasuryBalance
+    ? refreshPoolAmount(storedGrant, company, treasuryBalance)
+    : storedGrant;
+  const vestedAmount = getVestedAmount(grant, now);
+  return {
+    employeeAccount,
+    vestingAccount: grant.vestingAccount,
+    companyName: company.companyName,
+    mint: grant.mint,
+    totalAmount: grant.totalAmount,
+    withdrawnAmount: grant.totalWithdrawn,
+    vestedAmount,
+    claimableAmount: BN.max(vestedAmount.sub(grant.totalWithdrawn), new BN(0)),
+    lockedAmount: grant.totalAmount.sub(vestedAmount),
+    startTime: grant.startTime.toNumber(),
+    cliffTime: grant.cliffTime.toNumber(),
+    endTime: grant.endTime.toNumber(),
+  };
+}
+
+const positionsCache = new Map<
+  string,
+  { expiresAt: number; positions: Promise<VestingPosition[]> }
+>();
+
+// Returns every grant held by `wallet`, with vested, claimable and locked amounts computed at `now`.
+export function getPositions(
+  program: Program<Vesting>,
+  wallet: PublicKey,
+  { cacheTtlMs = 30_000, ...options }: GetPositionsOptions = {}
+): Promise<VestingPosition[]> {
+  const key = `${program.programId.toBase58()}:${wallet.toBase58()}:${
+    options.now ?? ''
+  }:${options.withMetadata ?? false}`;
+  const cached = positionsCache.get(key);
+  if (cached && cached.expiresAt > Date.now()) {
+    return cached.positions;
+  }
+
+  const positions = fetchPositions(program, wallet, options);
+  if (cacheTtlMs > 0) {
+    positionsCache.set(key, { expiresAt: Date.now() + cacheTtlMs, positions });
+    // Failed lookups shouldn't be served from the cache.
+    positions.catch(() => positionsCache.delete(key));
+  }
+  return positions;
+}
+
+// Drops cached positions, e.g. after the wallet claimed tokens.
+export function clearPositionsCache(wallet?: PublicKey) {
+  if (!wallet) {
+    positionsCache.clear();
+    return;
+  }
+  for (const key of positionsCache.keys()) {
+    if (key.includes(`:${wallet.toBase58()}:`)) {
+      positionsCache.delete(key);
+    }
+  }
+}
+
+async function fetchPositions(
+  program: Program<Vesting>,
+  wallet: PublicKey,
+  { now = Math.floor(Date.now() / 1000), withMetadata = false, ...options }: GetPositionsOptions
+): Promise<VestingPosition[]> {
+  const grants = await program.account.employeeAccount.all([
+    {
+      memcmp: {
+        offset: EMPLOYEE_ACCOUNT_BENEFICIARY_OFFSET,
+        bytes: wallet.toBase58(),
+      },
+    },
+  ]);
+  if (!grants.length) {
+    return [];
+  }
+
+  const vestingAccountKeys = [
+    ...new Map(
+      grants.map((grant) => [
+        grant.account.vestingAccount.toBase58(),
+        grant.account.vestingAccount,
+      ])
+    ).values(),
+  ];
+  const vestingAccounts = await program.account.vestingAccount.fetchMultiple(
+    vestingAccountKeys
+  );
+  const companies = new Map(
+    vestingAccountKeys.map((key, i) => [key.toBase58(), vestingAccounts[i]])
+  );
+
+  // Pool grants are worth a share of their primary treasury, so its balance is fetched once per company.
+  const treasuryBalances = new Map<string, Promise<BN>>();
+  const getTreasuryBalance = (company: VestingAccountData) => {
+    const treasury = company.treasuryTokenAccount.toBase58();
+    if (!treasuryBalances.has(treasury)) {
+      treasuryBalances.set(
+        treasury,
+        program.provider.connection
+          .getTokenAccountBalance(company.treasuryTokenAccount)
+          .then(({ value }) => new BN(value.amount))
+      );
+    }
+    return treasuryBalances.get(treasury)!;
+  };
+
+  const metadata = new Map<string, Promise<VestingTokenMetadata | null>>();
+  return Promise.all(
+    grants.map(async ({ publicKey, account }) => {
+      const company = companies.get(account.vestingAccount.toBase58());
+      if (!company) {
+        throw new Error(
+          `Vesting account ${account.vestingAccount.toBase58()} not found`
+        );
+      }
+      const treasuryBalance = account.shareBps
+        ? await getTreasuryBalance(company)
+        : undefined;
+      const position = buildVestingPosition(
+        publicKey,
+        account,
+        company,
+        now,
+        treasuryBalance
+      );
+      if (withMetadata) {
+        const mint = account.mint.toBase58();
+        if (!metadata.has(mint)) {
+          metadata.set(
+            mint,
+            resolveTokenMetadata(program.provider.connection, account.mint, options)
+          );
+        }
+        position.tokenMetadata = await metadata.get(mint)!;
+      }
+      return position;
+    })
+  );
+}
@@ -0,0 +1,223 @@
+// Here we keep a wallet's positions up to date over websocket subscriptions instead of polling, so
+// front-ends can show live claimable balances without hammering their RPC provider.
+import { BN, Program, utils } from '@coral-xyz/anchor';
+import { AccountLayout } from '@solana/spl-token';
+import { AccountInfo, Commitment, PublicKey } from '@solana/web3.js';
+import type { Vesting } from '../target/types/vesting';
+import {
+  EMPLOYEE_ACCOUNT_BENEFICIARY_OFFSET,
+  EmployeeAccountData,
+  VESTING_ACCOUNT_DISCRIMINATORS,
+  VestingAccountData,
+  VestingPosition,
+  buildVestingPosition,
+} from './vesting-positions';
+
+export interface SubscribeToPositionsOptions {
+  commitment?: Commitment;
+  // Notifications arriving within this window are merged into a single onUpdate call.
+  coalesceMs?: number;
+  // How often claimable amounts are recomputed locally as time passes, 0 disables it. This never hits the RPC.
+  tickMs?: number;
+  // How often everything is refetched over HTTP, to recover from notifications dropped while the
+  // websocket was reconnecting. 0 disables it.
+  resyncMs?: number;
+  onError?: (err: unknown) => void;
+}
+
+export interface PositionsSubscription {
+  // Refetches every grant and company now, e.g. after the wallet sent a claim.
+  refresh(): Promise<void>;
+  unsubscribe(): Promise<void>;
+}
+
+// Calls `onUpdate` with every grant held by `wallet` whenever one of them or one of their companies
+// changes on chain, and every `tickMs` as tokens keep vesting.
+export async function subscribeToPositions(
+  program: Program<Vesting>,
+  wallet: PublicKey,
+  onUpdate: (positions: VestingPosition[]) => void,
+  {
+    commitment = 'confirmed',
+    coalesceMs = 250,
+    tickMs = 1_000,
+    resyncMs = 5 * 60_000,
+    onError = () => undefined,
+  }: SubscribeToPositionsOptions = {}
+): Promise<PositionsSubscription> {
+  const connection = program.provider.connection;
+  const grants = new Map<string, { publicKey: PublicKey; account: EmployeeAccountData }>();
+  const companies = new Map<string, VestingAccountData>();
+  // One account subscription per company, added and removed as grants come and go.
+  const companySubscriptions = new Map<string, number>();
+  // Pool grants are worth a share of their primary treasury, so the treasuries they draw from are watched too.
+  const treasuryBalances = new Map<string, BN>();
+  const treasurySubscriptions = new Map<string, number>();
+  let closed = false;
+  let pendingEmit: ReturnType<typeof setTimeout> | undefined;
+
+  const emit = () => {
+    const now = Math.floor(Date.now() / 1000);
+    const positions: VestingPosition[] = [];
+    for (const { publicKey, account } of grants.values()) {
+      const company = companies.get(account.vestingAccount.toBase58());
+      const treasuryBalance = company && treasuryBalances.get(company.treasuryTokenAccount.toBase58());
+      if (company && (!account.shareBps || treasuryBalance)) {
+        positions.push(buildVestingPosition(publicKey, account, company, now, treasuryBalance));
+      }
+    }
+    onUpdate(positions);
+  };
+
+  const scheduleEmit = () => {
+    if (closed || pendingEmit) {
+      return;
+    }
+    pendingEmit = setTimeout(() => {
+      pendingEmit = undefined;
+      if (!closed) {
+        emit();
+      }
+    }, coalesceMs);
+  };
+
+  const onCompanyChange = (key: string) => (info: AccountInfo<Buffer>) => {
+    if (info.data.length) {
+      companies.set(key, program.coder.accounts.decode('vestingAccount', info.data));
+    } else {
+      companies.delete(key);
+    }
+    scheduleEmit();
+  };
+
+  const syncCompanySubscriptions = async () => {
+    const wanted = new Set(
+      [...grants.values()].map(({ account }) => account.vestingAccount.toBase58())
+    );
+    for (const [key, id] of companySubscriptions) {
+      if (!wanted.has(key)) {
+        companySubscriptions.delete(key);
+        companies.delete(key);
+        await connection.removeAccountChangeListener(id);
+      }
+    }
+    const missing = [...wanted].filter((key) => !companySubscriptions.has(key));
+    for (const key of missing) {
+      companySubscriptions.set(
+        key,
+        connection.onAccountChange(new PublicKey(key), onCompanyChange(key), { commitment })
+      );
+    }
+    if (missing.length) {
+      const accounts = await program.account.vestingAccount.fetchMultiple(
+        missing.map((key) => new PublicKey(key)),
+        commitment
+      );
+      missing.forEach((key, i) => {
+        const account = accounts[i];
+        if (account) {
+          companies.set(key, account);
+        }
+      });
+    }
+    await syncTreasurySubscriptions();
+  };
+
+  const syncTreasurySubscriptions = async () => {
+    const wanted = new Set<string>();
+    for (const { account } of grants.values()) {
+      const company = companies.get(account.vestingAccount.toBase58());
+      if (account.shareBps && company) {
+        wanted.add(company.treasuryTokenAccount.toBase58());
+      }
+    }
+    for (const [key, id] of treasurySubscriptions) {
+      if (!wanted.has(key)) {
+        treasurySubscriptions.delete(key);
+        treasuryBalances.delete(key);
+        await connection.removeAccountChangeListener(id);
+      }
+    }
+    const missing = [...wanted].filter((key) => !treasurySubscriptions.has(key));
+    for (const key of missing) {
+      const treasury = new PublicKey(key);
+      treasurySubscriptions.set(
+        key,
+        connection.onAccountChange(
+          treasury,
+          (info) => {
+            treasuryBalances.set(key, new BN(AccountLayout.decode(info.data).amount.toString()));
+            scheduleEmit();
+          },
+          { commitment }
+        )
+      );
+      const { value } = await connection.getTokenAccountBalance(treasury, commitment);
+      treasuryBalances.set(key, new BN(value.amount));
+    }
+  };
+
+  const refresh = async () => {
+    const all = await program.account.employeeAccount.all([
+      { memcmp: { offset: EMPLOYEE_ACCOUNT_BENEFICIARY_OFFSET, bytes: wallet.toBase58() } },
+    ]);
+    grants.clear();
+    for (const grant of all) {
+      grants.set(grant.publicKey.toBase58(), grant);
+    }
+    await syncCompanySubscriptions();
+    scheduleEmit();
+  };
+
+  // Grants don't have a fixed set of addresses, so they're watched through a filtered program subscription.
+  const grantSubscription = connection.onProgramAccountChange(
+    program.programId,
+    ({ accountId, accountInfo }) => {
+      const key = accountId.toBase58();
+      if (accountInfo.data.length) {
+        grants.set(key, {
+          publicKey: accountId,
+          account: program.coder.accounts.decode('employeeAccount', accountInfo.data),
+        });
+      } else {
+        grants.delete(key);
+      }
+      syncCompanySubscriptions().then(scheduleEmit, onError);
+    },
+    {
+      commitment,
+      filters: [
+        {
+          memcmp: {
+            offset: 0,
+            bytes: utils.bytes.bs58.encode(VESTING_ACCOUNT_DISCRIMINATORS.EmployeeAccount),
+          },
+        },
+        { memcmp: { offset: EMPLOYEE_ACCOUNT_BENEFICIARY_OFFSET, bytes: wallet.toBase58() } },
+      ],
+    }
+  );
+
+  await refresh();
+  const tick = tickMs > 0 ? setInterval(scheduleEmit, tickMs) : undefined;
+  const resync =
+    resyncMs > 0 ? setInterval(() => refresh().catch(onError), resyncMs) : undefined;
+
+  return {
+    refresh,
+    async unsubscribe() {
+      closed = true;
+      clearTimeout(pendingEmit);
+      clearInterval(tick);
+      clearInterval(resync);
+      await connection.removeProgramAccountChangeListener(grantSubscription);
+      await Promise.all(
+        [...companySubscriptions.values(), ...treasurySubscriptions.values()].map((id) =>
+          connection.removeAccountChangeListener(id)
+        )
+      );
+      companySubscriptions.clear();
+      treasurySubscriptions.clear();
+    },
+  };
+}
@@ -0,0 +1,259 @@
+// Here we build claim-and-swap transactions, so employees can turn a claim into stablecoins in one atomic
+// transaction whose price can't be moved against them by more than their slippage bound.
+import { BN, Program, utils } from '@coral-xyz/anchor';
+import { TOKEN_PROGRAM_ID, getAssociatedTokenAddressSync } from '@solana/spl-token';
+import {
+  AccountMeta,
+  PublicKey,
+  SystemProgram,
+  TransactionInstruction,
+  TransactionMessage,
+  VersionedTransaction,
+} from '@solana/web3.js';
+import type { Vesting } from '../target/types/vesting';
+import { getClaimFee, getEmployeeAccountAddress, getSeedNamespace } from './vesting-exports';
+import { getClaimableAmount } from './vesting-positions';
+
+export interface SwapRequest {
+  inputMint: PublicKey;
+  outputMint: PublicKey;
+  // Exact amount of inputMint to sell.
+  amount: BN;
+  slippageBps: number;
+  user: PublicKey;
+}
+
+export interface SwapInstructions {
+  instructions: TransactionInstruction[];
+  addressLookupTableAddresses: PublicKey[];
+  // Least amount of outputMint the swap accepts, enforced on chain by the swap program.
+  minOutAmount: BN;
+}
+
+// Quotes a swap and returns the instructions executing it, with the slippage bound built into them.
+export type SwapProvider = (request: SwapRequest) => Promise<SwapInstructions>;
+
+// Slippage above this makes sandwiching nearly free, so buildClaimAndSwapTransaction refuses it.
+export const MAX_SWAP_SLIPPAGE_BPS = 500;
+
+export const JITO_BLOCK_ENGINE_URL = 'https://mainnet.block-engine.jito.wtf';
+
+interface JupiterInstruction {
+  programId: string;
+  accounts: { pubkey: string; isSigner: boolean; isWritable: boolean }[];
+  data: string;
+}
+
+function toInstruction({ programId, accounts, data }: JupiterInstruction) {
+  return new TransactionInstruction({
+    programId: new PublicKey(programId),
+    keys: accounts.map(
+      ({ pubkey, isSigner, isWritable }): AccountMeta => ({
+        pubkey: new PublicKey(pubkey),
+        isSigner,
+        isWritable,
+      })
+    ),
+    data: Buffer.from(data, 'base64'),
+  });
+}
+
+// This is a helper function to get a SwapProvider backed by the Jupiter swap API.
+export function jupiterSwapProvider(apiUrl = 'https://quote-api.jup.ag/v6'): SwapProvider {
+  return async ({ inputMint, outputMint, amount, slippageBps, user }) => {
+    const params = new URLSearchParams({
+      inputMint: inputMint.toBase58(),
+      outputMint: outputMint.toBase58(),
+      amount: amount.toString(),
+      slippageBps: String(slippageBps),
+    });
+    const quoteResponse = await fetch(`${apiUrl}/quote?${params}`).then((res) => {
+      if (!res.ok) {
+        throw new Error(`Jupiter quote failed with status ${res.status}`);
+      }
+      return res.json();
+    });
+    const swap = await fetch(`${apiUrl}/swap-instructions`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({
+        quoteResponse,
+        userPublicKey: user.toBase58(),
+        wrapAndUnwrapSol: true,
+        dynamicComputeUnitLimit: true,
+      }),
+    }).then((res) => {
+      if (!res.ok) {
+        throw new Error(`Jupiter swap instructions failed with status ${res.status}`);
+      }
+      return res.json();
+    });
+
+    return {
+      instructions: [
+        ...swap.computeBudgetInstructions,
+        ...swap.setupInstructions,
+        swap.swapInstruction,
+        ...(swap.cleanupInstruction ? [swap.cleanupInstruction] : []),
+      ].map(toInstruction),
+      addressLookupTableAddresses: swap.addressLookupTableAddresses.map(
+        (address: string) => new PublicKey(address)
+      ),
+      // For exact-in swaps, otherAmountThreshold is the quoted output minus the slippage.
+      minOutAmount: new BN(quoteResponse.otherAmountThreshold),
+    };
+  };
+}
+
+export interface ClaimAndSwapOptions {
+  outputMint: PublicKey;
+  swapProvider?: SwapProvider;
+  slippageBps?: number;
+  // Seconds from now after which the transaction fails instead of landing against a stale quote.
+  deadlineSeconds?: number;
+  // Amount to swap, defaults to everything claimable right now minus the claim fee. Mints with a transfer
+  // fee need to pass the amount left after the fee.
+  amount?: BN;
+  // Mint of the grant to claim, defaults to the vesting account's primary mint.
+  mint?: PublicKey;
+  tokenProgram?: PublicKey;
+  // Tip paid to a Jito tip account at the end of the transaction, so it can be sent with sendJitoBundle.
+  jitoTip?: { tipAccount: PublicKey; lamports: number };
+}
+
+// Builds an unsigned v0 transaction that checks the deadline, claims a grant and sells the claimed tokens.
+// Everything is atomic: if the swap can't meet minOutAmount or the deadline has passed, the claim is
+// reverted too and the tokens stay in the grant. Mints with a transfer hook aren't supported.
+export async function buildClaimAndSwapTransaction(
+  program: Program<Vesting>,
+  beneficiary: PublicKey,
+  vestingAccount: PublicKey,
+  {
+    outputMint,
+    swapProvider = jupiterSwapProvider(),
+    slippageBps = 50,
+    deadlineSeconds = 60,
+    amount,
+    mint,
+    tokenProgram = TOKEN_PROGRAM_ID,
+    jitoTip,
+  }: ClaimAndSwapOptions
+) {
+  if (slippageBps < 0 || slippageBps > MAX_SWAP_SLIPPAGE_BPS) {
+    throw new Error(`Slippage must be between 0 and ${MAX_SWAP_SLIPPAGE_BPS} bps`);
+  }
+  const connection = program.provider.connection;
+  const company = await program.account.vestingAccount.fetch(vestingAccount);
+  const { companyName } = company;
+  const employeeAccount = getEmployeeAccountAddress(
+    beneficiary,
+    vestingAccount,
+    mint ?? company.mint,
+    program.programId,
+    getSeedNamespace(program)
+  );
+  const grant = await program.account.employeeAccount.fetch(employeeAccount);
+  // The swap sells out of the beneficiary's associated token account, which a redirected claim never reaches.
+  if (grant.claimDestination) {
+    throw new Error('Grants with a registered claim destination cannot be claimed and swapped');
+  }
+  const claimFee = await getClaimFee(program);
+  let swapAmount = amount;
+  if (swapAmount === undefined) {
+    const { claimableAmount } = await getClaimableAmount(program, employeeAccount);
+    swapAmount = claimableAmount.sub(claimableAmount.muln(claimFee?.claimFeeBps ?? 0).divn(10_000));
+  }
+  if (swapAmount.isZero()) {
+    throw new Error('Nothing to claim');
+  }
+
+  const swap = await swapProvider({
+    inputMint: grant.mint,
+    outputMint,
+    amount: swapAmount,
+    slippageBps,
+    user: beneficiary,
+  });
+  const deadline = Math.floor(Date.now() / 1000) + deadlineSeconds;
+  const instructions = [
+    await program.methods.checkDeadline(new BN(deadline)).instruction(),
+    await program.methods
+      .claimTokens(companyName, null)
+      .accountsPartial({
+        beneficiary,
+        employeeAccount,
+        vestingAccount,
+        mint: grant.mint,
+        tokenProgram,
+        feeTokenAccount: claimFee
+          ? getAssociatedTokenAddressSync(grant.mint, claimFee.feeRecipient, true, tokenProgram)
+          : null,
+      })
+      .instruction(),
+    ...swap.instructions,
+  ];
+  // The transaction is atomic, so the tip is only paid when the claim and the swap go through.
+  if (jitoTip) {
+    instructions.push(
+      SystemProgram.transfer({
+        fromPubkey: beneficiary,
+        toPubkey: jitoTip.tipAccount,
+        lamports: jitoTip.lamports,
+      })
+    );
+  }
+
+  const lookupTables = await Promise.all(
+    swap.addressLookupTableAddresses.map(async (address) => {
+      const { value } = await connection.getAddressLookupTable(address);
+      if (!value) {
+        throw new Error(`Address lookup table ${address.toBase58()} not found`);
+      }
+      return value;
+    })
+  );
+  const { blockhash, lastValidBlockHeight } = await connection.getLatestBlockhash();
+  const message = new TransactionMessage({
+    payerKey: beneficiary,
+    recentBlockhash: blockhash,
+    instructions,
+  }).compileToV0Message(lookupTables);
+
+  return {
+    transaction: new VersionedTransaction(message),
+    amount: swapAmount,
+    minOutAmount: swap.minOutAmount,
+    deadline,
+    lastValidBlockHeight,
+  };
+}
+
+async function jitoRequest(blockEngineUrl: string, method: string, params: unknown[]) {
+  const res = await fetch(`${blockEngineUrl}/api/v1/bundles`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ jsonrpc: '2.0', id: 1, method, params }),
+  });
+  const { result, error } = await res.json();
+  if (error) {
+    throw new Error(`Jito ${method} failed: ${error.message}`);
+  }
+  return result;
+}
+
+// This is a helper function to get the accounts a Jito block engine accepts tips on.
+export async function getJitoTipAccounts(blockEngineUrl = JITO_BLOCK_ENGINE_URL) {
+  const accounts: string[] = await jitoRequest(blockEngineUrl, 'getTipAccounts', []);
+  return accounts.map((account) => new PublicKey(account));
+}
+
+// This is a helper function to send signed transactions as a Jito bundle. Bundles skip the public
+// mempool, so searchers never see the claim-and-swap before it lands. Returns the bundle id.
+export async function sendJitoBundle(
+  transactions: VersionedTransaction[],
+  blockEngineUrl = JITO_BLOCK_ENGINE_URL
+): Promise<string> {
+  return jitoRequest(blockEngineUrl, 'sendBundle', [
+    transactions.map((transaction) => utils.bytes.bs58.encode(transaction.serialize())),
+  ]);
+}
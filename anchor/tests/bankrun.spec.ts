@@ -1,7 +1,7 @@
 // No imports needed: web3, anchor, pg and more are globally available
 import * as anchor from "@coral-xyz/anchor";
 import { BankrunProvider } from "anchor-bankrun";
-import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { ACCOUNT_SIZE, AccountLayout, AccountState, TOKEN_PROGRAM_ID } from "@solana/spl-token";
 import { BN, Program } from "@coral-xyz/anchor";
 
 import {
@@ -11,12 +11,13 @@ import {
   ProgramTestContext,
 } from "solana-bankrun";
 
-import { createMint, mintTo } from "spl-token-bankrun";
+import { createAssociatedTokenAccount, createMint, mintTo } from "spl-token-bankrun";
 import { PublicKey, Keypair } from "@solana/web3.js";
 import NodeWallet from "@coral-xyz/anchor/dist/cjs/nodewallet";
 
 import IDL from "../target/idl/vesting.json";
 import { Vesting } from "../target/types/vesting";
+import { runDemoFlow } from "../src/vesting-demo";
 import { SYSTEM_PROGRAM_ID } from "@coral-xyz/anchor/dist/cjs/native/system";
 
 describe("Vesting Smart Contract Tests", () => {
@@ -34,6 +35,16 @@ describe("Vesting Smart Contract Tests", () => {
   let program2: Program<Vesting>;
   let context: ProgramTestContext;
 
+  // Mirrors getCompanyNameRecordAddress, names that only differ in case share a record.
+  const companyNameRecord = (name: string) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("company_name"),
+        Buffer.from(anchor.utils.sha256.hash(name.toLowerCase()), "hex"),
+      ],
+      program.programId
+    )[0];
+
   beforeAll(async () => {
     beneficiary = new anchor.web3.Keypair();
 
@@ -74,13 +85,22 @@ describe("Vesting Smart Contract Tests", () => {
     program2 = new Program<Vesting>(IDL as Vesting, beneficiaryProvider);
 
     // Derive PDAs
+    // The employer hasn't created any vesting account yet, so this one gets index 0.
     [vestingAccountKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from(companyName)],
+      [
+        Buffer.from("vesting_account"),
+        employer.publicKey.toBuffer(),
+        new BN(0).toArrayLike(Buffer, "le", 8),
+      ],
       program.programId
     );
 
     [treasuryTokenAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("vesting_treasury"), Buffer.from(companyName)],
+      [
+        Buffer.from("vesting_treasury"),
+        vestingAccountKey.toBuffer(),
+        mint.toBuffer(),
+      ],
       program.programId
     );
 
@@ -89,6 +109,7 @@ describe("Vesting Smart Contract Tests", () => {
         Buffer.from("employee_vesting"),
         beneficiary.publicKey.toBuffer(),
         vestingAccountKey.toBuffer(),
+        mint.toBuffer(),
       ],
       program.programId
     );
@@ -96,10 +117,12 @@ describe("Vesting Smart Contract Tests", () => {
 
   it("should create a vesting account", async () => {
     const tx = await program.methods
-      .createVestingAccount(companyName)
-      .accounts({
+      .createVestingAccount(companyName, { blockClaims: {} })
+      .accountsPartial({
         signer: employer.publicKey,
+        vestingAccount: vestingAccountKey,
         mint,
+        companyNameRecord: companyNameRecord(companyName),
         tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc({ commitment: "confirmed" });
@@ -133,10 +156,19 @@ describe("Vesting Smart Contract Tests", () => {
 
   it("should create an employee vesting account", async () => {
     const tx2 = await program.methods
-      .createEmployeeVesting(new BN(0), new BN(100), new BN(100), new BN(0))
+      .createEmployeeVesting(
+        new BN(0),
+        new BN(100),
+        new BN(100),
+        new BN(0),
+        { linear: {} },
+        new BN(0),
+        0
+      )
       .accounts({
         beneficiary: beneficiary.publicKey,
         vestingAccount: vestingAccountKey,
+        mint,
       })
       .rpc({ commitment: "confirmed", skipPreflight: true });
 
@@ -144,6 +176,216 @@ describe("Vesting Smart Contract Tests", () => {
     console.log("Employee account", employeeAccount.toBase58());
   });
 
+  it("should create an employee vesting account with milestones", async () => {
+    const milestoneBeneficiary = new anchor.web3.Keypair();
+
+    const tx = await program.methods
+      .createEmployeeVestingWithMilestones(new BN(0), new BN(100), [
+        { timestamp: new BN(10), cumulativeBps: 1_000 },
+        { timestamp: new BN(50), cumulativeBps: 2_500 },
+        { timestamp: new BN(100), cumulativeBps: 10_000 },
+      ])
+      .accounts({
+        beneficiary: milestoneBeneficiary.publicKey,
+        vestingAccount: vestingAccountKey,
+        mint,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const [milestoneEmployeeAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("employee_vesting"),
+        milestoneBeneficiary.publicKey.toBuffer(),
+        vestingAccountKey.toBuffer(),
+        mint.toBuffer(),
+      ],
+      program.programId
+    );
+    const data = await program.account.employeeAccount.fetch(
+      milestoneEmployeeAccount,
+      "confirmed"
+    );
+    expect(data.cliffTime.toNumber()).toEqual(10);
+    expect(data.endTime.toNumber()).toEqual(100);
+    expect(data.milestones).toHaveLength(3);
+
+    console.log("Create Milestone Employee Account Transaction Signature:", tx);
+  });
+
+  it("should not create a pool grant in a treasury backing fixed grants", async () => {
+    // The primary treasury already reserves tokens for the grants above.
+    await expect(
+      program.methods
+        .createPoolEmployeeVesting(
+          new BN(0),
+          new BN(100),
+          2_500,
+          new BN(0),
+          { linear: {} },
+          new BN(0),
+          0
+        )
+        .accounts({
+          beneficiary: new anchor.web3.Keypair().publicKey,
+          vestingAccount: vestingAccountKey,
+          mint,
+        })
+        .rpc({ commitment: "confirmed" })
+    ).rejects.toThrow();
+  });
+
+  it("should list the grants in the grant registry", async () => {
+    const vestingAccountData = await program.account.vestingAccount.fetch(vestingAccountKey);
+    // The plain grant and the milestone grant.
+    expect(vestingAccountData.grantCount.toNumber()).toEqual(2);
+
+    const [firstEntry] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("grant_entry"),
+        vestingAccountKey.toBuffer(),
+        new BN(0).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const entry = await program.account.grantRegistryEntry.fetch(firstEntry);
+    expect(entry.employeeAccount.equals(employeeAccount)).toBe(true);
+  });
+
+  it("should accept the grant", async () => {
+    await program2.methods
+      .acceptGrant()
+      .accounts({ employeeAccount })
+      .rpc({ commitment: "confirmed" });
+
+    const employee = await program.account.employeeAccount.fetch(employeeAccount);
+    expect(employee.acceptedAt).not.toBeNull();
+  });
+
+  it("should top up a grant", async () => {
+    const sourceTokenAccount = await createAssociatedTokenAccount(
+      // @ts-ignore
+      banksClient,
+      employer,
+      mint,
+      employer.publicKey
+    );
+    // @ts-ignore
+    await mintTo(banksClient, employer, mint, sourceTokenAccount, employer, 50);
+
+    await program.methods
+      .topUpGrant(new BN(50))
+      .accounts({
+        employeeAccount,
+        mint,
+        sourceTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const employee = await program.account.employeeAccount.fetch(employeeAccount);
+    expect(employee.totalAmount.toNumber()).toEqual(150);
+    const vestingAccountData = await program.account.vestingAccount.fetch(vestingAccountKey);
+    // The plain grant, the milestone grant and the top-up.
+    expect(vestingAccountData.treasuries[0].reservedAmount.toNumber()).toEqual(250);
+  });
+
+  it("should snapshot vested power", async () => {
+    const currentClock = await banksClient.getClock();
+    context.setClock(
+      new Clock(
+        currentClock.slot,
+        currentClock.epochStartTimestamp,
+        currentClock.epoch,
+        currentClock.leaderScheduleEpoch,
+        1000n
+      )
+    );
+
+    // Anyone can crank it, here the employer pays for the beneficiary's snapshot.
+    await program.methods
+      .snapshotVestedAmount()
+      .accounts({ employeeAccount })
+      .rpc({ commitment: "confirmed" });
+
+    const [votingPower] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("voting_power"),
+        vestingAccountKey.toBuffer(),
+        new BN(0).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const snapshot = await program.account.votingPower.fetch(votingPower);
+    expect(snapshot.beneficiary.equals(beneficiary.publicKey)).toBe(true);
+    // Fully vested after the top-up and nothing claimed yet.
+    expect(snapshot.amount.toNumber()).toEqual(150);
+  });
+
+  it("should claim part of the vested tokens", async () => {
+    // The clock was moved to 1000 in the snapshot test, so all 150 tokens have vested.
+    await expect(
+      program2.methods
+        .claimTokens(companyName, new BN(151))
+        .accountsPartial({
+          vestingAccount: vestingAccountKey,
+          mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" })
+    ).rejects.toThrow();
+
+    await program2.methods
+      .claimTokens(companyName, new BN(50))
+      .accountsPartial({
+        vestingAccount: vestingAccountKey,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const employee = await program.account.employeeAccount.fetch(employeeAccount);
+    expect(employee.totalWithdrawn.toNumber()).toEqual(50);
+  });
+
+  it("should only claim into allowed destinations", async () => {
+    await program.methods
+      .setRequireAllowedDestinations(true)
+      .accounts({ vestingAccount: vestingAccountKey })
+      .rpc({ commitment: "confirmed" });
+
+    // The beneficiary has no AllowedDestinations yet.
+    await expect(
+      program2.methods
+        .claimTokens(companyName, new BN(10))
+        .accountsPartial({
+          vestingAccount: vestingAccountKey,
+          mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" })
+    ).rejects.toThrow();
+
+    await program.methods
+      .setAllowedDestinations(beneficiary.publicKey, [beneficiary.publicKey], null)
+      .accounts({ vestingAccount: vestingAccountKey })
+      .rpc({ commitment: "confirmed" });
+    await program2.methods
+      .claimTokens(companyName, new BN(10))
+      .accountsPartial({
+        vestingAccount: vestingAccountKey,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    await program.methods
+      .setRequireAllowedDestinations(false)
+      .accounts({ vestingAccount: vestingAccountKey })
+      .rpc({ commitment: "confirmed" });
+    const employee = await program.account.employeeAccount.fetch(employeeAccount);
+    expect(employee.totalWithdrawn.toNumber()).toEqual(60);
+  });
+
   it("should claim tokens", async () => {
     await new Promise((resolve) => setTimeout(resolve, 1000));
 
@@ -161,12 +403,437 @@ describe("Vesting Smart Contract Tests", () => {
     console.log("Employee account", employeeAccount.toBase58());
 
     const tx3 = await program2.methods
-      .claimTokens(companyName)
-      .accounts({
+      .claimTokens(companyName, null)
+      .accountsPartial({
+        vestingAccount: vestingAccountKey,
+        mint,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
       .rpc({ commitment: "confirmed" });
 
     console.log("Claim Tokens transaction signature", tx3);
   });
+
+  it("should not amend a grant to end in the past", async () => {
+    // The clock was moved to 1000 in the claim test.
+    await expect(
+      program.methods
+        .updateEmployeeVesting(new BN(500), new BN(200), new BN(0))
+        .accounts({
+          vestingAccount: vestingAccountKey,
+          employeeAccount,
+        })
+        .rpc({ commitment: "confirmed" })
+    ).rejects.toThrow();
+
+    const employee = await program.account.employeeAccount.fetch(employeeAccount);
+    expect(employee.endTime.toNumber()).toEqual(100);
+  });
+
+  it("should record the claim in the claim stats", async () => {
+    const [claimStats] = PublicKey.findProgramAddressSync(
+      [Buffer.from("claim_stats"), vestingAccountKey.toBuffer()],
+      program.programId
+    );
+    const stats = await program.account.claimStats.fetch(claimStats);
+    const employee = await program.account.employeeAccount.fetch(employeeAccount);
+
+    // The two partial claims and the claim of the rest.
+    expect(stats.claimCount.toNumber()).toEqual(3);
+    expect(stats.totalClaimed.eq(employee.totalWithdrawn)).toBe(true);
+  });
+
+  it("should register and clear a claim destination", async () => {
+    // A token account of the grant's mint owned by another wallet, e.g. a custodian.
+    const custodian = new anchor.web3.Keypair();
+    const destination = await createAssociatedTokenAccount(
+      // @ts-ignore
+      banksClient,
+      employer,
+      mint,
+      custodian.publicKey
+    );
+
+    await program2.methods
+      .setClaimDestination(destination)
+      .accounts({ employeeAccount, destinationTokenAccount: destination })
+      .rpc({ commitment: "confirmed" });
+    let employee = await program.account.employeeAccount.fetch(employeeAccount);
+    expect(employee.claimDestination?.equals(destination)).toBe(true);
+
+    await program2.methods
+      .setClaimDestination(null)
+      .accounts({ employeeAccount, destinationTokenAccount: null })
+      .rpc({ commitment: "confirmed" });
+    employee = await program.account.employeeAccount.fetch(employeeAccount);
+    expect(employee.claimDestination).toBeNull();
+  });
+
+  it("should assign the revoke admin role", async () => {
+    const revokeAdmin = new anchor.web3.Keypair();
+    await program.methods
+      .setRole({ revokeAdmin: {} }, revokeAdmin.publicKey)
+      .accounts({ vestingAccount: vestingAccountKey })
+      .rpc({ commitment: "confirmed" });
+
+    const vestingAccountData = await program.account.vestingAccount.fetch(vestingAccountKey);
+    expect(vestingAccountData.revokeAdmin.equals(revokeAdmin.publicKey)).toBe(true);
+    expect(vestingAccountData.grantAdmin.equals(employer.publicKey)).toBe(true);
+  });
+
+  it("should rename the company", async () => {
+    await expect(
+      program.methods
+        .renameCompany(" Company")
+        .accounts({
+          vestingAccount: vestingAccountKey,
+          companyNameRecord: companyNameRecord(" Company"),
+          previousCompanyNameRecord: companyNameRecord(companyName),
+        })
+        .rpc({ commitment: "confirmed" })
+    ).rejects.toThrow();
+
+    await program.methods
+      .renameCompany("Company Inc.")
+      .accounts({
+        vestingAccount: vestingAccountKey,
+        companyNameRecord: companyNameRecord("Company Inc."),
+        previousCompanyNameRecord: companyNameRecord(companyName),
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const vestingAccountData = await program.account.vestingAccount.fetch(vestingAccountKey);
+    expect(vestingAccountData.companyName).toEqual("Company Inc.");
+    expect(vestingAccountData.canonicalName).toEqual("company inc.");
+    // The new name is reserved and the previous one released.
+    const record = await program.account.companyNameRecord.fetch(companyNameRecord("Company Inc."));
+    expect(record.vestingAccount.equals(vestingAccountKey)).toBe(true);
+    expect(await banksClient.getAccount(companyNameRecord(companyName))).toBeNull();
+  });
+
+  it("should not migrate an up to date vesting account", async () => {
+    const vestingAccountData = await program.account.vestingAccount.fetch(vestingAccountKey);
+    expect(vestingAccountData.version).toEqual(1);
+
+    await expect(
+      program.methods
+        .migrateVestingAccount()
+        .accounts({ vestingAccount: vestingAccountKey })
+        .rpc({ commitment: "confirmed" })
+    ).rejects.toThrow();
+  });
+
+  it("should close a fully claimed employee account", async () => {
+    const tx = await program.methods
+      .closeEmployeeAccount()
+      .accounts({
+        vestingAccount: vestingAccountKey,
+        employeeAccount,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const closed = await banksClient.getAccount(employeeAccount);
+    expect(closed).toBeNull();
+
+    console.log("Close Employee Account transaction signature", tx);
+  });
+
+  it("should vest and claim native SOL", async () => {
+    // The employer's second vesting account.
+    const [solVestingAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("vesting_account"),
+        employer.publicKey.toBuffer(),
+        new BN(1).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const [solTreasury] = PublicKey.findProgramAddressSync(
+      [Buffer.from("sol_treasury"), solVestingAccount.toBuffer()],
+      program.programId
+    );
+
+    // The first vesting account goes by this name now.
+    await expect(
+      program.methods
+        .createSolVesting("COMPANY INC.", { blockClaims: {} })
+        .accountsPartial({
+          signer: employer.publicKey,
+          vestingAccount: solVestingAccount,
+          companyNameRecord: companyNameRecord("COMPANY INC."),
+        })
+        .rpc({ commitment: "confirmed" })
+    ).rejects.toThrow();
+
+    // The first vesting account was renamed, which released its original name.
+    await program.methods
+      .createSolVesting(companyName, { blockClaims: {} })
+      .accountsPartial({
+        signer: employer.publicKey,
+        vestingAccount: solVestingAccount,
+        companyNameRecord: companyNameRecord(companyName),
+      })
+      .rpc({ commitment: "confirmed" });
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.transfer({
+          fromPubkey: employer.publicKey,
+          toPubkey: solTreasury,
+          lamports: 1_000_000_000,
+        })
+      )
+    );
+
+    await program.methods
+      .createSolEmployeeVesting(
+        new BN(0),
+        new BN(100),
+        new BN(500_000_000),
+        new BN(0),
+        { linear: {} },
+        new BN(0),
+        0
+      )
+      .accounts({ beneficiary: beneficiary.publicKey, vestingAccount: solVestingAccount })
+      .rpc({ commitment: "confirmed" });
+
+    const [solEmployeeAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("employee_vesting"),
+        beneficiary.publicKey.toBuffer(),
+        solVestingAccount.toBuffer(),
+        anchor.web3.SystemProgram.programId.toBuffer(),
+      ],
+      program.programId
+    );
+    await program2.methods
+      .acceptGrant()
+      .accounts({ employeeAccount: solEmployeeAccount })
+      .rpc({ commitment: "confirmed" });
+
+    // The clock was moved to 1000 in the claim test, so the whole grant has vested.
+    const treasuryBefore = await banksClient.getBalance(solTreasury);
+    await program2.methods
+      .claimSol()
+      .accounts({ vestingAccount: solVestingAccount })
+      .rpc({ commitment: "confirmed" });
+
+    const treasuryAfter = await banksClient.getBalance(solTreasury);
+    expect(treasuryBefore - treasuryAfter).toEqual(500_000_000n);
+    const employee = await program.account.employeeAccount.fetch(solEmployeeAccount);
+    expect(employee.totalWithdrawn.toNumber()).toEqual(500_000_000);
+  });
+
+  it("should close a SOL vesting account and drain its treasury", async () => {
+    const [solVestingAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("vesting_account"),
+        employer.publicKey.toBuffer(),
+        new BN(1).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const [solTreasury] = PublicKey.findProgramAddressSync(
+      [Buffer.from("sol_treasury"), solVestingAccount.toBuffer()],
+      program.programId
+    );
+    const [solEmployeeAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("employee_vesting"),
+        beneficiary.publicKey.toBuffer(),
+        solVestingAccount.toBuffer(),
+        anchor.web3.SystemProgram.programId.toBuffer(),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .closeEmployeeAccount()
+      .accounts({ vestingAccount: solVestingAccount, employeeAccount: solEmployeeAccount })
+      .rpc({ commitment: "confirmed" });
+
+    const ownerBefore = await banksClient.getBalance(employer.publicKey);
+    const treasuryBalance = await banksClient.getBalance(solTreasury);
+    await program.methods
+      .closeSolVesting()
+      .accounts({ vestingAccount: solVestingAccount, companyNameRecord: companyNameRecord(companyName) })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await banksClient.getAccount(solVestingAccount)).toBeNull();
+    expect(await banksClient.getAccount(companyNameRecord(companyName))).toBeNull();
+    expect(await banksClient.getBalance(solTreasury)).toEqual(0n);
+    // The unclaimed half of the funding comes back, on top of the rent of the closed accounts.
+    const ownerAfter = await banksClient.getBalance(employer.publicKey);
+    expect(ownerAfter - ownerBefore > treasuryBalance - 10_000n).toBe(true);
+  });
+
+  it("should migrate, claim and close a grant of the original program", async () => {
+    // Accounts the original program created, written in its layout at its addresses.
+    const legacyName = "Legacy Co";
+    const [legacyVestingAccount, vestingBump] = PublicKey.findProgramAddressSync(
+      [Buffer.from(legacyName)],
+      program.programId
+    );
+    const [legacyTreasury, treasuryBump] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vesting_treasury"), Buffer.from(legacyName)],
+      program.programId
+    );
+    const [legacyEmployeeAccount, employeeBump] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("employee_vesting"),
+        beneficiary.publicKey.toBuffer(),
+        legacyVestingAccount.toBuffer(),
+      ],
+      program.programId
+    );
+    const discriminator = (name: string) =>
+      Buffer.from(IDL.accounts.find((account) => account.name === name)!.discriminator);
+    const i64 = (value: number) => new BN(value).toArrayLike(Buffer, "le", 8);
+
+    // The original program allocated the full 50 bytes of company_name, the unused ones trail the account.
+    const vestingData = Buffer.concat([
+      discriminator("VestingAccount"),
+      employer.publicKey.toBuffer(),
+      mint.toBuffer(),
+      legacyTreasury.toBuffer(),
+      new BN(legacyName.length).toArrayLike(Buffer, "le", 4),
+      Buffer.from(legacyName),
+      Buffer.from([treasuryBump, vestingBump]),
+      Buffer.alloc(50 - legacyName.length),
+    ]);
+    const employeeData = Buffer.concat([
+      discriminator("EmployeeAccount"),
+      beneficiary.publicKey.toBuffer(),
+      i64(0),
+      i64(100),
+      i64(1_000),
+      i64(0),
+      i64(0),
+      legacyVestingAccount.toBuffer(),
+      Buffer.from([employeeBump]),
+    ]);
+    const treasuryData = Buffer.alloc(ACCOUNT_SIZE);
+    AccountLayout.encode(
+      {
+        mint,
+        owner: legacyTreasury,
+        amount: 1_000n,
+        delegateOption: 0,
+        delegate: PublicKey.default,
+        state: AccountState.Initialized,
+        isNativeOption: 0,
+        isNative: 0n,
+        delegatedAmount: 0n,
+        closeAuthorityOption: 0,
+        closeAuthority: PublicKey.default,
+      },
+      treasuryData
+    );
+    // Migrations have to be enabled in the config, AccountMigration is the fourth Feature.
+    const [config, configBump] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+    const configData = Buffer.concat([
+      discriminator("Config"),
+      employer.publicKey.toBuffer(),
+      i64(0),
+      Buffer.from([0]),
+      i64(0),
+      i64(1 << 3),
+      employer.publicKey.toBuffer(),
+      Buffer.from([0, 0, configBump]),
+    ]);
+    for (const [address, data, owner] of [
+      [legacyVestingAccount, vestingData, program.programId],
+      [legacyEmployeeAccount, employeeData, program.programId],
+      [legacyTreasury, treasuryData, TOKEN_PROGRAM_ID],
+      [config, configData, program.programId],
+    ] as const) {
+      context.setAccount(address, { lamports: 10_000_000, data, owner, executable: false });
+    }
+
+    await program.methods
+      .migrateLegacyVestingAccount()
+      .accounts({ vestingAccount: legacyVestingAccount })
+      .rpc({ commitment: "confirmed" });
+    await program.methods
+      .migrateLegacyEmployeeAccount()
+      .accounts({ employeeAccount: legacyEmployeeAccount, vestingAccount: legacyVestingAccount })
+      .rpc({ commitment: "confirmed" });
+    const vestingAccountData = await program.account.vestingAccount.fetch(legacyVestingAccount);
+    expect(vestingAccountData.legacySeeds).toBe(true);
+    expect(vestingAccountData.companyName).toEqual(legacyName);
+    // The migrated grant was added to the grant registry.
+    expect(vestingAccountData.grantCount.toNumber()).toEqual(1);
+
+    // The clock was moved to 1000 in the claim test, so the whole grant has vested.
+    await program2.methods
+      .claimLegacyTokens()
+      .accountsPartial({
+        employeeAccount: legacyEmployeeAccount,
+        vestingAccount: legacyVestingAccount,
+        mint,
+        treasuryTokenAccount: legacyTreasury,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const employee = await program.account.employeeAccount.fetch(legacyEmployeeAccount);
+    expect(employee.totalWithdrawn.toNumber()).toEqual(1_000);
+
+    // The migrated grant counts as active, and the account closes once it is gone, signing for the treasury
+    // with its original seeds.
+    expect(vestingAccountData.activeGrantCount.toNumber()).toEqual(1);
+    await program.methods
+      .closeEmployeeAccount()
+      .accounts({ vestingAccount: legacyVestingAccount, employeeAccount: legacyEmployeeAccount })
+      .rpc({ commitment: "confirmed" });
+    await program.methods
+      .closeVestingAccount()
+      .accountsPartial({
+        vestingAccount: legacyVestingAccount,
+        treasuryTokenAccount: legacyTreasury,
+        companyNameRecord: companyNameRecord(legacyName),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await banksClient.getAccount(legacyVestingAccount)).toBeNull();
+    expect(await banksClient.getAccount(legacyTreasury)).toBeNull();
+  });
+
+  it("should run the demo flow", async () => {
+    // The demo's grants start up to a year before `now`.
+    const currentClock = await banksClient.getClock();
+    context.setClock(
+      new Clock(
+        currentClock.slot,
+        currentClock.epochStartTimestamp,
+        currentClock.epoch,
+        currentClock.leaderScheduleEpoch,
+        1_700_000_000n
+      )
+    );
+
+    const result = await runDemoFlow(program, employer, { mint, now: 1_700_000_000 });
+
+    const vestingAccountData = await program.account.vestingAccount.fetch(result.vestingAccount);
+    expect(vestingAccountData.activeGrantCount.toNumber()).toEqual(result.grants.length);
+    const claimed = result.grants.filter(({ claimSignature }) => claimSignature);
+    expect(claimed.length).toBeGreaterThan(0);
+    for (const { beneficiary: demoBeneficiary } of claimed) {
+      const [demoEmployeeAccount] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("employee_vesting"),
+          demoBeneficiary.publicKey.toBuffer(),
+          result.vestingAccount.toBuffer(),
+          mint.toBuffer(),
+        ],
+        program.programId
+      );
+      const grant = await program.account.employeeAccount.fetch(demoEmployeeAccount);
+      expect(grant.totalWithdrawn.toNumber()).toBeGreaterThan(0);
+    }
+  });
 });
@@ -1,6 +1,11 @@
 "use client";
 
-import { getVestingProgram, getVestingProgramId } from "@token-vesting/anchor";
+import {
+  getCompanyNameRecordAddress,
+  getNextVestingAccountAddress,
+  getVestingProgram,
+  getVestingProgramId,
+} from "@token-vesting/anchor";
 import { useConnection } from "@solana/wallet-adapter-react";
 import { Cluster, PublicKey } from "@solana/web3.js";
 import { useMutation, useQuery } from "@tanstack/react-query";
@@ -21,6 +26,10 @@ interface CreateEmployeeArgs {
   endTime: number;
   totalAmount: number;
   cliffTime: number;
+  // Defaults to a linear schedule when omitted.
+  vestingIntervalSeconds?: number;
+  // Share of the grant, in basis points, unlocked at the cliff. Defaults to 0.
+  cliffUnlockBps?: number;
 }
 
 export function useVestingProgram() {
@@ -32,7 +41,10 @@ export function useVestingProgram() {
     () => getVestingProgramId(cluster.network as Cluster),
     [cluster]
   );
-  const program = getVestingProgram(provider);
+  const program = useMemo(
+    () => getVestingProgram(provider, programId),
+    [provider, programId]
+  );
 
   const accounts = useQuery({
     queryKey: ["vesting", "all", { cluster }],
@@ -46,10 +58,18 @@ export function useVestingProgram() {
 
   const createVestingAccount = useMutation<string, Error, CreateVestingArgs>({
     mutationKey: ["vestingAccount", "create", { cluster }],
-    mutationFn: ({ companyName, mint }) =>
+    mutationFn: async ({ companyName, mint }) =>
       program.methods
-        .createVestingAccount(companyName)
-        .accounts({ mint: new PublicKey(mint), tokenProgram: TOKEN_PROGRAM_ID })
+        .createVestingAccount(companyName, { blockClaims: {} })
+        .accountsPartial({
+          vestingAccount: await getNextVestingAccountAddress(
+            program,
+            provider.publicKey
+          ),
+          mint: new PublicKey(mint),
+          companyNameRecord: getCompanyNameRecordAddress(companyName, program.programId),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
         .rpc(),
     onSuccess: (signature) => {
       transactionToast(signature);
@@ -79,9 +99,24 @@ export function useVestingProgramAccount({ account }: { account: PublicKey }) {
 
   const createEmployeeVesting = useMutation<string, Error, CreateEmployeeArgs>({
     mutationKey: ["vesting", "close", { cluster, account }],
-    mutationFn: ({ startTime, endTime, totalAmount, cliffTime }) =>
+    mutationFn: ({
+      startTime,
+      endTime,
+      totalAmount,
+      cliffTime,
+      vestingIntervalSeconds,
+      cliffUnlockBps,
+    }) =>
       program.methods
-        .createEmployeeVesting(startTime, endTime, totalAmount, cliffTime)
+        .createEmployeeVesting(
+          startTime,
+          endTime,
+          totalAmount,
+          cliffTime,
+          vestingIntervalSeconds ? { periodic: {} } : { linear: {} },
+          vestingIntervalSeconds ?? 0,
+          cliffUnlockBps ?? 0
+        )
         .rpc(),
     onSuccess: (tx) => {
       transactionToast(tx);